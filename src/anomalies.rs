@@ -0,0 +1,282 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::forecast::pick_sparkline;
+use crate::stats::percentile;
+use crate::types::*;
+
+/// Weight of each signal at its threshold; a signal twice as strong scores twice as much
+const WEIGHT: f64 = 25.0;
+const THIN_WEIGHT: f64 = 30.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Signal {
+    /// A day-over-day move in the sparkline
+    Jump,
+    /// A high value backed by very few listings
+    ThinListings,
+    /// Buying and selling prices of a currency disagree
+    PayReceiveDivergence,
+    /// A variant priced far from the other variants of the same item
+    VariantOutlier,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Reason {
+    pub signal: Signal,
+    pub detail: String,
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Flagged {
+    pub details_id: String,
+    pub name: String,
+    pub chaos_value: f64,
+    /// Sum of the reason scores, at most 100
+    pub score: f64,
+    pub reasons: Vec<Reason>,
+}
+
+/// Thresholds at which each signal starts to count
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Heuristics {
+    /// Day-over-day change, in percent
+    pub jump_percent: f64,
+    /// At most this many listings...
+    pub thin_listings: i32,
+    /// ...for a value at or above this percentile of the category
+    pub thin_percentile: f64,
+    /// Ratio between the receive price and the inverted pay price
+    pub divergence_ratio: f64,
+    /// Ratio between a variant and the median of its siblings
+    pub sibling_ratio: f64,
+}
+
+impl Default for Heuristics {
+    fn default() -> Self {
+        Self {
+            jump_percent: 50.0,
+            thin_listings: 5,
+            thin_percentile: 75.0,
+            divergence_ratio: 1.5,
+            sibling_ratio: 5.0,
+        }
+    }
+}
+
+/// `weight` at the threshold, growing with `severity` (observed / threshold) up to twice that
+fn score(weight: f64, severity: f64) -> f64 {
+    (weight * severity.min(2.0) * 10.0).round() / 10.0
+}
+
+/// The largest day-over-day move of a sparkline, as (day, percent). Points are
+/// percent changes since the first day, so moves are taken between price ratios.
+pub fn largest_jump(sparkline: &SparkLine) -> Option<(usize, f64)> {
+    let points: Vec<(usize, f64)> = sparkline
+        .data
+        .iter()
+        .flatten()
+        .enumerate()
+        .filter_map(|(day, change)| Some((day, 1.0 + (*change)? / 100.0)))
+        .collect();
+    points
+        .windows(2)
+        .filter(|pair| pair[0].1 > 0.0)
+        .map(|pair| (pair[1].0, (pair[1].1 / pair[0].1 - 1.0) * 100.0))
+        .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+}
+
+fn jump_reason(sparkline: &SparkLine, heuristics: &Heuristics) -> Option<Reason> {
+    let (day, change) = largest_jump(sparkline)?;
+    (change.abs() >= heuristics.jump_percent).then(|| Reason {
+        signal: Signal::Jump,
+        detail: format!("{:+.0}% in one day (day {} of the sparkline)", change, day + 1),
+        score: score(WEIGHT, change.abs() / heuristics.jump_percent),
+    })
+}
+
+fn thin_reason(chaos_value: f64, listings: i32, floor: f64, heuristics: &Heuristics) -> Option<Reason> {
+    (chaos_value > 0.0 && chaos_value >= floor && listings <= heuristics.thin_listings).then(|| Reason {
+        signal: Signal::ThinListings,
+        detail: format!("{:.0} chaos on {} listings", chaos_value, listings),
+        score: score(THIN_WEIGHT, heuristics.thin_listings as f64 / listings.max(1) as f64),
+    })
+}
+
+fn thin_floor(values: impl Iterator<Item = f64>, heuristics: &Heuristics) -> f64 {
+    let mut values: Vec<f64> = values.collect();
+    values.sort_by(f64::total_cmp);
+    percentile(&values, heuristics.thin_percentile)
+}
+
+fn flagged(details_id: &str, name: String, chaos_value: f64, reasons: Vec<Reason>) -> Option<Flagged> {
+    if reasons.is_empty() {
+        return None;
+    }
+    Some(Flagged {
+        details_id: details_id.to_string(),
+        name,
+        chaos_value,
+        score: reasons.iter().map(|reason| reason.score).sum::<f64>().min(100.0),
+        reasons,
+    })
+}
+
+fn by_score(mut flagged: Vec<Flagged>) -> Vec<Flagged> {
+    flagged.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| b.chaos_value.total_cmp(&a.chaos_value)));
+    flagged
+}
+
+/// Median value of the other lines sharing `item`'s name and links, when there are any
+fn sibling_median(items: &[ItemLine], groups: &BTreeMap<(&str, Option<i32>), Vec<usize>>, index: usize) -> Option<f64> {
+    let item = &items[index];
+    let mut values: Vec<f64> = groups[&(item.name.as_str(), item.links)]
+        .iter()
+        .filter(|other| **other != index)
+        .map(|other| items[*other].chaos_value)
+        .collect();
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    Some(percentile(&values, 50.0))
+}
+
+pub fn scan_items(items: &[ItemLine], heuristics: &Heuristics) -> Vec<Flagged> {
+    let floor = thin_floor(items.iter().map(|item| item.chaos_value), heuristics);
+    let mut groups: BTreeMap<(&str, Option<i32>), Vec<usize>> = BTreeMap::new();
+    for (index, item) in items.iter().enumerate() {
+        groups.entry((item.name.as_str(), item.links)).or_default().push(index);
+    }
+
+    let flagged = items.iter().enumerate().filter_map(|(index, item)| {
+        let sparkline = pick_sparkline(&item.sparkline, &item.low_confidence_sparkline);
+        let listings = item.listing_count.unwrap_or(item.count);
+        let mut reasons: Vec<Reason> = [
+            jump_reason(sparkline, heuristics),
+            thin_reason(item.chaos_value, listings, floor, heuristics),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        if let Some(median) = sibling_median(items, &groups, index).filter(|median| *median > 0.0) {
+            let ratio = item.chaos_value / median;
+            let spread = ratio.max(1.0 / ratio.max(f64::MIN_POSITIVE));
+            if spread >= heuristics.sibling_ratio {
+                reasons.push(Reason {
+                    signal: Signal::VariantOutlier,
+                    detail: format!("{:.1}x the {:.0} chaos median of its other variants", ratio, median),
+                    score: score(WEIGHT, spread / heuristics.sibling_ratio),
+                });
+            }
+        }
+
+        let name = match &item.variant {
+            Some(variant) => format!("{} ({})", item.name, variant),
+            None => item.name.clone(),
+        };
+        flagged(&item.details_id, name, item.chaos_value, reasons)
+    });
+    by_score(flagged.collect())
+}
+
+/// Currencies are checked on the receive side, the direction of `chaosEquivalent`
+pub fn scan_currencies(currencies: &[CurrencyLine], heuristics: &Heuristics) -> Vec<Flagged> {
+    let floor = thin_floor(currencies.iter().filter_map(|currency| currency.chaos_equivalent), heuristics);
+
+    let flagged = currencies.iter().filter_map(|currency| {
+        let chaos_value = currency.chaos_equivalent.unwrap_or(0.0);
+        let sparkline = pick_sparkline(&currency.receive_spark_line, &currency.low_confidence_receive_spark_line);
+        let mut reasons: Vec<Reason> = jump_reason(sparkline, heuristics).into_iter().collect();
+
+        if let Some(receive) = &currency.receive {
+            let listings = receive.listing_count.unwrap_or(receive.count);
+            reasons.extend(thin_reason(chaos_value, listings, floor, heuristics));
+        }
+
+        // `pay.value` is units per chaos, `receive.value` chaos per unit
+        if let (Some(pay), Some(receive)) = (&currency.pay, &currency.receive) {
+            if pay.value > 0.0 && receive.value > 0.0 {
+                let paid = 1.0 / pay.value;
+                let ratio = paid.max(receive.value) / paid.min(receive.value);
+                if ratio >= heuristics.divergence_ratio {
+                    reasons.push(Reason {
+                        signal: Signal::PayReceiveDivergence,
+                        detail: format!("sells for {:.1} chaos but buys at {:.1}", receive.value, paid),
+                        score: score(WEIGHT, ratio / heuristics.divergence_ratio),
+                    });
+                }
+            }
+        }
+
+        flagged(&currency.details_id, currency.currency_type_name.clone(), chaos_value, reasons)
+    });
+    by_score(flagged.collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items() -> Vec<ItemLine> {
+        let response: ItemOverviewResponse =
+            serde_json::from_str(include_str!("../tests/fixtures/anomalies/standard/UniqueArmour.json")).unwrap();
+        response.lines
+    }
+
+    fn signals(flagged: &Flagged) -> Vec<Signal> {
+        flagged.reasons.iter().map(|reason| reason.signal).collect()
+    }
+
+    #[test]
+    fn test_largest_jump() {
+        let sparkline = SparkLine {
+            data: Some(vec![Some(0.0), Some(10.0), None, Some(120.0), Some(115.0)]),
+            total_change: Some(115.0),
+        };
+        let (day, change) = largest_jump(&sparkline).unwrap();
+        assert_eq!(day, 3);
+        assert!((change - 100.0).abs() < 1e-9);
+        assert!(largest_jump(&SparkLine::default()).is_none());
+    }
+
+    #[test]
+    fn test_scan_crafted_items() {
+        let flagged = scan_items(&items(), &Heuristics::default());
+        let names: Vec<&str> = flagged.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, vec!["Shavronne's Wrappings", "Atziri's Splendour (Evasion/ES)", "Kaom's Heart"]);
+
+        assert_eq!(signals(&flagged[0]), vec![Signal::ThinListings]);
+        assert_eq!(flagged[0].score, 60.0);
+        assert_eq!(signals(&flagged[1]), vec![Signal::VariantOutlier]);
+        assert!(flagged[1].reasons[0].detail.starts_with("18.0x the 50 chaos median"));
+        assert_eq!(signals(&flagged[2]), vec![Signal::Jump]);
+        assert!(flagged[2].reasons[0].detail.contains("day 4"));
+    }
+
+    #[test]
+    fn test_scan_crafted_currencies() {
+        let response: CurrencyOverviewResponse =
+            serde_json::from_str(include_str!("../tests/fixtures/anomalies/standard/Currency.json")).unwrap();
+        let flagged = scan_currencies(&response.lines, &Heuristics::default());
+
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].name, "Exalted Orb");
+        assert_eq!(signals(&flagged[0]), vec![Signal::PayReceiveDivergence]);
+        assert!(flagged[0].reasons[0].detail.contains("sells for 15.0 chaos but buys at 50.0"));
+    }
+
+    #[test]
+    fn test_thresholds_are_tunable() {
+        let strict = Heuristics {
+            jump_percent: 200.0,
+            sibling_ratio: 20.0,
+            thin_listings: 0,
+            ..Heuristics::default()
+        };
+        assert!(scan_items(&items(), &strict).is_empty());
+    }
+}
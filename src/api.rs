@@ -1,15 +1,108 @@
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "cache")]
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+use crate::schema::{self, Parsed, SchemaReport, SkippedLine};
+#[cfg(feature = "cache")]
+use crate::snapshots::{SnapshotStore, SnapshotTransport};
 use crate::types::*;
 
-pub struct PoeNinjaClient {
+/// Which game's economy to query
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Game {
+    #[default]
+    Poe1,
+    Poe2,
+}
+
+impl Game {
+    pub fn base_url(&self) -> &'static str {
+        match self {
+            Game::Poe1 => "https://poe.ninja/api/data",
+            Game::Poe2 => "https://poe.ninja/poe2/api",
+        }
+    }
+
+    /// Short name used in file paths
+    pub fn slug(&self) -> &'static str {
+        match self {
+            Game::Poe1 => "poe1",
+            Game::Poe2 => "poe2",
+        }
+    }
+}
+
+impl std::fmt::Display for Game {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Game::Poe1 => write!(f, "Path of Exile"),
+            Game::Poe2 => write!(f, "Path of Exile 2"),
+        }
+    }
+}
+
+/// Identifies one poe.ninja request independently of how it is served
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RequestKey {
+    pub game: Game,
+    pub endpoint: String,
+    pub league: Option<String>,
+    pub kind: Option<String>,
+}
+
+impl RequestKey {
+    /// The currency or item overview that serves `overview_type`
+    pub fn overview(game: Game, league: &str, overview_type: &str) -> Self {
+        let endpoint = if crate::get_currency_types().contains(&overview_type) {
+            "currencyoverview"
+        } else {
+            "itemoverview"
+        };
+        Self {
+            game,
+            endpoint: endpoint.to_string(),
+            league: Some(league.to_string()),
+            kind: Some(overview_type.to_string()),
+        }
+    }
+
+    /// Relative path of the recorded response, e.g. `poe1/itemoverview/standard/Oil.json`
+    pub fn path(&self) -> PathBuf {
+        let mut path = PathBuf::from(self.game.slug());
+        path.push(&self.endpoint);
+        if let Some(league) = &self.league {
+            path.push(crate::pricing::slugify(league));
+        }
+        match &self.kind {
+            Some(kind) => path.push(format!("{}.json", kind)),
+            None => {
+                path.set_extension("json");
+            }
+        }
+        path
+    }
+}
+
+/// Where raw JSON responses come from
+#[async_trait]
+pub trait Transport: Send + Sync {
+    async fn fetch(&self, url: &str, key: &RequestKey) -> Result<String>;
+}
+
+/// Live requests to poe.ninja
+pub struct HttpTransport {
     client: Client,
-    base_url: String,
 }
 
-impl PoeNinjaClient {
+impl HttpTransport {
     pub fn new() -> Self {
         let client = Client::builder()
             .timeout(Duration::from_secs(30))
@@ -17,28 +110,22 @@ impl PoeNinjaClient {
             .build()
             .expect("Failed to create HTTP client");
 
-        Self {
-            client,
-            base_url: "https://poe.ninja/api/data".to_string(),
-        }
+        Self { client }
     }
+}
 
-    /// Get currency overview data
-    pub async fn get_currency_overview(
-        &self,
-        league: &str,
-        currency_type: &str,
-    ) -> Result<CurrencyOverviewResponse> {
-        let url = format!(
-            "{}/currencyoverview?league={}&type={}",
-            self.base_url,
-            urlencoding::encode(league),
-            currency_type
-        );
+impl Default for HttpTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
+#[async_trait]
+impl Transport for HttpTransport {
+    async fn fetch(&self, url: &str, _key: &RequestKey) -> Result<String> {
         let response = self
             .client
-            .get(&url)
+            .get(url)
             .send()
             .await
             .context("Failed to send request")?;
@@ -50,59 +137,620 @@ impl PoeNinjaClient {
             ));
         }
 
-        let data: CurrencyOverviewResponse = response
-            .json()
-            .await
-            .context("Failed to parse currency response")?;
+        response.text().await.context("Failed to read response body")
+    }
+}
+
+#[cfg(feature = "cache")]
+/// Receives the error of a best-effort step, such as a failed cache write or
+/// overview lines a command could not use; the command carries on regardless
+pub type WriteWarning = std::sync::Arc<dyn Fn(&anyhow::Error) + Send + Sync>;
 
-        Ok(data)
+#[cfg(feature = "cache")]
+/// Passes requests through and writes every response under `dir`
+pub struct RecordTransport {
+    inner: Box<dyn Transport>,
+    dir: PathBuf,
+    warn: Option<WriteWarning>,
+}
+
+#[cfg(feature = "cache")]
+impl RecordTransport {
+    /// A response that cannot be written fails the request
+    pub fn new(inner: Box<dyn Transport>, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            inner,
+            dir: dir.into(),
+            warn: None,
+        }
     }
 
-    /// Get item overview data
-    pub async fn get_item_overview(
-        &self,
-        league: &str,
-        item_type: &str,
-    ) -> Result<ItemOverviewResponse> {
+    /// Report write failures to `warn` instead of failing the request
+    pub fn with_write_warning(mut self, warn: WriteWarning) -> Self {
+        self.warn = Some(warn);
+        self
+    }
+}
+
+#[cfg(feature = "cache")]
+#[async_trait]
+impl Transport for RecordTransport {
+    async fn fetch(&self, url: &str, key: &RequestKey) -> Result<String> {
+        let body = self.inner.fetch(url, key).await?;
+        warn_or_fail(write_response(&self.dir, key, &body), self.warn.as_ref())?;
+        Ok(body)
+    }
+}
+
+#[cfg(feature = "cache")]
+/// Hand an error to `warn` when there is one, otherwise return it
+pub(crate) fn warn_or_fail(result: Result<()>, warn: Option<&WriteWarning>) -> Result<()> {
+    match (result, warn) {
+        (Err(err), Some(warn)) => {
+            warn(&err);
+            Ok(())
+        }
+        (result, _) => result,
+    }
+}
+
+#[cfg(feature = "cache")]
+fn write_response(dir: &Path, key: &RequestKey, body: &str) -> Result<()> {
+    let path = dir.join(key.path());
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("Failed to create '{}'", parent.display()))?;
+    }
+    std::fs::write(&path, body).with_context(|| format!("Failed to record response to '{}'", path.display()))
+}
+
+#[cfg(feature = "cache")]
+/// Serves previously recorded responses from `dir` and never touches the network
+pub struct ReplayTransport {
+    dir: PathBuf,
+}
+
+#[cfg(feature = "cache")]
+impl ReplayTransport {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+#[cfg(feature = "cache")]
+#[async_trait]
+impl Transport for ReplayTransport {
+    async fn fetch(&self, _url: &str, key: &RequestKey) -> Result<String> {
+        let path = self.dir.join(key.path());
+        std::fs::read_to_string(&path)
+            .with_context(|| format!("No recorded response at '{}'", path.display()))
+    }
+}
+
+#[cfg(feature = "cache")]
+/// Read-through cache: responses younger than `ttl` are served from `dir`
+/// without asking `inner`, everything else is fetched and written back
+pub struct CacheTransport {
+    inner: Box<dyn Transport>,
+    dir: PathBuf,
+    ttl: Duration,
+    warn: Option<WriteWarning>,
+}
+
+#[cfg(feature = "cache")]
+impl CacheTransport {
+    pub fn new(inner: Box<dyn Transport>, dir: impl Into<PathBuf>, ttl: Duration) -> Self {
+        Self {
+            inner,
+            dir: dir.into(),
+            ttl,
+            warn: None,
+        }
+    }
+
+    /// Report write-back failures to `warn` instead of failing the request
+    pub fn with_write_warning(mut self, warn: WriteWarning) -> Self {
+        self.warn = Some(warn);
+        self
+    }
+
+    fn fresh(&self, path: &Path) -> Option<String> {
+        let age = std::fs::metadata(path).ok()?.modified().ok()?.elapsed().ok()?;
+        if age >= self.ttl {
+            return None;
+        }
+        std::fs::read_to_string(path).ok()
+    }
+}
+
+#[cfg(feature = "cache")]
+#[async_trait]
+impl Transport for CacheTransport {
+    async fn fetch(&self, url: &str, key: &RequestKey) -> Result<String> {
+        if let Some(body) = self.fresh(&self.dir.join(key.path())) {
+            return Ok(body);
+        }
+        let body = self.inner.fetch(url, key).await?;
+        warn_or_fail(write_response(&self.dir, key, &body), self.warn.as_ref())?;
+        Ok(body)
+    }
+}
+
+#[cfg(feature = "cache")]
+/// Cache directory written by live runs and read by `--offline`:
+/// `$POE_NINJA_CACHE_DIR`, else `$XDG_CACHE_HOME/poe-ninja-cli`, else `~/.cache/poe-ninja-cli`
+pub fn default_cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("POE_NINJA_CACHE_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .map(|dir| dir.join("poe-ninja-cli"))
+}
+
+#[cfg(feature = "cache")]
+/// Pick the transport for the `--offline`, `--record` and `--replay` flags.
+///
+/// Every live call writes to disk: the response is written through to the cache so a
+/// later `--offline` run can use it, and with `snapshots` an overview is also kept as
+/// an hourly snapshot for `diff`. With a `cache_ttl`, live calls read from the cache
+/// while responses are fresh. Cache and snapshot writes are best effort and report
+/// failures to `warn`; only an explicit `record` directory fails the request when it
+/// cannot be written.
+pub fn select_transport(
+    offline: bool,
+    record: Option<&Path>,
+    replay: Option<&Path>,
+    cache_ttl: Option<u64>,
+    snapshots: bool,
+    warn: WriteWarning,
+) -> Result<Box<dyn Transport>> {
+    if let Some(dir) = replay {
+        return Ok(Box::new(ReplayTransport::new(dir)));
+    }
+    if offline {
+        let dir = default_cache_dir().context("No cache directory available for --offline")?;
+        return Ok(Box::new(ReplayTransport::new(dir)));
+    }
+
+    let mut transport: Box<dyn Transport> = Box::new(HttpTransport::new());
+    if let Some(dir) = default_cache_dir() {
+        if snapshots {
+            let store = SnapshotStore::new(dir.join("snapshots"));
+            transport = Box::new(
+                SnapshotTransport::new(transport, store, Duration::from_secs(3600)).with_write_warning(warn.clone()),
+            );
+        }
+        transport = match cache_ttl {
+            Some(ttl) => Box::new(
+                CacheTransport::new(transport, dir, Duration::from_secs(ttl)).with_write_warning(warn.clone()),
+            ),
+            None => Box::new(RecordTransport::new(transport, dir).with_write_warning(warn.clone())),
+        };
+    }
+    if let Some(dir) = record {
+        transport = Box::new(RecordTransport::new(transport, dir));
+    }
+    Ok(transport)
+}
+
+/// URL building and response parsing shared by the async and the blocking client
+#[derive(Debug, Clone)]
+pub(crate) struct Endpoints {
+    base_url: String,
+    game: Game,
+    strict: bool,
+    /// Lines left out by lenient parsing, until the caller takes them
+    skipped: Arc<Mutex<Vec<SkippedLine>>>,
+}
+
+impl Endpoints {
+    pub(crate) fn new(game: Game) -> Self {
+        Self {
+            base_url: game.base_url().to_string(),
+            game,
+            strict: false,
+            skipped: Arc::default(),
+        }
+    }
+
+    pub(crate) fn game(&self) -> Game {
+        self.game
+    }
+
+    /// Switch games; a base URL set with `set_base_url` is kept
+    pub(crate) fn set_game(&mut self, game: Game) {
+        if self.base_url == self.game.base_url() {
+            self.base_url = game.base_url().to_string();
+        }
+        self.game = game;
+    }
+
+    pub(crate) fn set_base_url(&mut self, base_url: String) {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+    }
+
+    pub(crate) fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub(crate) fn take_skipped(&self) -> Vec<SkippedLine> {
+        std::mem::take(&mut *self.skipped.lock().unwrap_or_else(|poisoned| poisoned.into_inner()))
+    }
+
+    fn key(&self, endpoint: &str, league: Option<&str>, kind: Option<&str>) -> RequestKey {
+        RequestKey {
+            game: self.game,
+            endpoint: endpoint.to_string(),
+            league: league.map(str::to_string),
+            kind: kind.map(str::to_string),
+        }
+    }
+
+    fn require_poe1(&self, endpoint: &str) -> Result<()> {
+        if self.game != Game::Poe1 {
+            anyhow::bail!("{} is only available for Path of Exile 1", endpoint);
+        }
+        Ok(())
+    }
+
+    /// Currency or item overview, whichever serves `overview_type`
+    pub(crate) fn overview(&self, league: &str, overview_type: &str) -> Result<(String, RequestKey)> {
+        self.require_poe1("overviews")?;
+        let key = RequestKey::overview(self.game, league, overview_type);
+        let url = format!(
+            "{}/{}?league={}&type={}",
+            self.base_url,
+            key.endpoint,
+            urlencoding::encode(league),
+            overview_type
+        );
+        Ok((url, key))
+    }
+
+    pub(crate) fn currency_overview(&self, league: &str, currency_type: &str) -> Result<(String, RequestKey)> {
+        self.require_poe1("currencyoverview")?;
+        let url = format!(
+            "{}/currencyoverview?league={}&type={}",
+            self.base_url,
+            urlencoding::encode(league),
+            currency_type
+        );
+        Ok((url, self.key("currencyoverview", Some(league), Some(currency_type))))
+    }
+
+    pub(crate) fn item_overview(&self, league: &str, item_type: &str) -> Result<(String, RequestKey)> {
+        self.require_poe1("itemoverview")?;
         let url = format!(
             "{}/itemoverview?league={}&type={}",
             self.base_url,
             urlencoding::encode(league),
             item_type
         );
+        Ok((url, self.key("itemoverview", Some(league), Some(item_type))))
+    }
 
-        let response = self
-            .client
-            .get(&url)
-            .send()
-            .await
-            .context("Failed to send request")?;
+    pub(crate) fn currency_history(
+        &self,
+        league: &str,
+        currency_type: &str,
+        currency_id: i32,
+    ) -> Result<(String, RequestKey)> {
+        self.require_poe1("currencyhistory")?;
+        let url = format!(
+            "{}/currencyhistory?league={}&type={}&currencyId={}",
+            self.base_url,
+            urlencoding::encode(league),
+            currency_type,
+            currency_id
+        );
+        let kind = format!("{}-{}", currency_type, currency_id);
+        Ok((url, self.key("currencyhistory", Some(league), Some(&kind))))
+    }
 
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!(
-                "API request failed with status: {}",
-                response.status()
-            ));
+    pub(crate) fn item_history(&self, league: &str, item_type: &str, item_id: i32) -> Result<(String, RequestKey)> {
+        self.require_poe1("itemhistory")?;
+        let url = format!(
+            "{}/itemhistory?league={}&type={}&itemId={}",
+            self.base_url,
+            urlencoding::encode(league),
+            item_type,
+            item_id
+        );
+        let kind = format!("{}-{}", item_type, item_id);
+        Ok((url, self.key("itemhistory", Some(league), Some(&kind))))
+    }
+
+    pub(crate) fn exchange_overview(&self, league: &str, overview_type: &str) -> Result<(String, RequestKey)> {
+        if self.game != Game::Poe2 {
+            anyhow::bail!("The currency exchange overview is only available for Path of Exile 2");
         }
+        let url = format!(
+            "{}/economy/exchange/current/overview?league={}&type={}",
+            self.base_url,
+            urlencoding::encode(league),
+            overview_type
+        );
+        Ok((url, self.key("exchange", Some(league), Some(overview_type))))
+    }
 
-        let data: ItemOverviewResponse = response
-            .json()
-            .await
-            .context("Failed to parse item response")?;
+    pub(crate) fn build_overview(&self, league_url: &str) -> Result<(String, RequestKey)> {
+        self.require_poe1("getbuildoverview")?;
+        let url = format!(
+            "{}/0/getbuildoverview?overview={}&type=exp&language=en",
+            self.base_url,
+            urlencoding::encode(league_url)
+        );
+        Ok((url, self.key("getbuildoverview", Some(league_url), None)))
+    }
 
-        Ok(data)
+    pub(crate) fn leagues(&self) -> (String, RequestKey) {
+        let url = match self.game {
+            Game::Poe1 => format!("{}/getindexstate", self.base_url),
+            Game::Poe2 => format!("{}/data/index-state", self.base_url),
+        };
+        (url, self.key("indexstate", None, None))
+    }
+
+    pub(crate) fn parse_json<T: DeserializeOwned>(&self, body: &str, what: &str) -> Result<T> {
+        serde_json::from_str(body).with_context(|| format!("Failed to parse {} response", what))
+    }
+
+    pub(crate) fn parse_currency_overview(&self, body: &str) -> Result<CurrencyOverviewResponse> {
+        self.parse_overview(body, "currency", schema::check_currency_overview, schema::parse_currency_overview)
+    }
+
+    pub(crate) fn parse_item_overview(&self, body: &str) -> Result<ItemOverviewResponse> {
+        self.parse_overview(body, "item", schema::check_item_overview, schema::parse_item_overview)
+    }
+
+    pub(crate) fn parse_leagues(&self, body: &str) -> Result<Vec<EconomyLeague>> {
+        let state: IndexStateResponse = self.parse_json(body, "index state")?;
+        Ok(state.economy_leagues)
+    }
+
+    /// Parse an overview leniently; with `strict`, any drift from the model is an error
+    fn parse_overview<T>(
+        &self,
+        body: &str,
+        what: &str,
+        check: fn(&str) -> Result<SchemaReport>,
+        parse: fn(&str) -> Parsed<T>,
+    ) -> Result<T> {
+        if self.strict {
+            let report = check(body).with_context(|| format!("Failed to parse {} response", what))?;
+            if !report.is_clean() {
+                anyhow::bail!("{} response drifted from the expected model: {}", what, report.summary());
+            }
+        }
+
+        let (response, skipped) = parse(body).with_context(|| format!("Failed to parse {} response", what))?;
+        self.skipped
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .extend(skipped);
+        Ok(response)
+    }
+}
+
+/// One line matched by a name search across overviews
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SearchHit {
+    pub name: String,
+    pub category: String,
+    pub details_id: String,
+    pub chaos_value: f64,
+}
+
+/// The overview types to search: the given ones, or every known type when empty
+pub fn search_types(types: &[&str]) -> Result<Vec<&'static str>> {
+    let known: Vec<&'static str> = crate::get_currency_types().into_iter().chain(crate::get_item_types()).collect();
+    if types.is_empty() {
+        return Ok(known);
+    }
+    types
+        .iter()
+        .map(|overview_type| {
+            known
+                .iter()
+                .find(|known| *known == overview_type)
+                .copied()
+                .with_context(|| format!("Unknown type '{}'", overview_type))
+        })
+        .collect()
+}
+
+/// Currency lines whose name contains `needle` (lowercase)
+pub fn currency_hits(response: &CurrencyOverviewResponse, category: &str, needle: &str) -> Vec<SearchHit> {
+    response
+        .lines
+        .iter()
+        .filter(|line| line.currency_type_name.to_lowercase().contains(needle))
+        .map(|line| SearchHit {
+            name: line.currency_type_name.clone(),
+            category: category.to_string(),
+            details_id: line.details_id.clone(),
+            chaos_value: line.chaos_equivalent.unwrap_or(0.0),
+        })
+        .collect()
+}
+
+/// Item lines whose name contains `needle` (lowercase)
+pub fn item_hits(response: &ItemOverviewResponse, category: &str, needle: &str) -> Vec<SearchHit> {
+    response
+        .lines
+        .iter()
+        .filter(|line| line.name.to_lowercase().contains(needle))
+        .map(|line| SearchHit {
+            name: line.name.clone(),
+            category: category.to_string(),
+            details_id: line.details_id.clone(),
+            chaos_value: line.chaos_value,
+        })
+        .collect()
+}
+
+/// Most valuable hits first
+pub fn sort_hits(hits: &mut [SearchHit]) {
+    hits.sort_by(|a, b| b.chaos_value.total_cmp(&a.chaos_value));
+}
+
+pub struct PoeNinjaClient {
+    transport: Box<dyn Transport>,
+    endpoints: Endpoints,
+}
+
+impl PoeNinjaClient {
+    pub fn new() -> Self {
+        Self {
+            transport: Box::new(HttpTransport::new()),
+            endpoints: Endpoints::new(Game::Poe1),
+        }
+    }
+
+    /// Switch endpoints to the given game
+    pub fn with_game(mut self, game: Game) -> Self {
+        self.endpoints.set_game(game);
+        self
+    }
+
+    /// Serve requests through another transport (recording, replay, ...)
+    pub fn with_transport(mut self, transport: Box<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Point requests at a mirror or proxy of the game's API
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.endpoints.set_base_url(base_url.into());
+        self
+    }
+
+    /// Fail on any drift from the response model instead of skipping bad lines
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.endpoints.set_strict(strict);
+        self
+    }
+
+    pub fn game(&self) -> Game {
+        self.endpoints.game()
+    }
+
+    /// Overview lines that did not match the model and were left out since the last call
+    pub fn take_skipped(&self) -> Vec<SkippedLine> {
+        self.endpoints.take_skipped()
+    }
+
+    async fn fetch(&self, (url, key): (String, RequestKey)) -> Result<String> {
+        self.transport.fetch(&url, &key).await
+    }
+
+    /// The unparsed overview body, for schema checks
+    pub async fn get_raw_overview(&self, league: &str, overview_type: &str) -> Result<String> {
+        self.fetch(self.endpoints.overview(league, overview_type)?).await
+    }
+
+    /// Get currency overview data
+    pub async fn get_currency_overview(
+        &self,
+        league: &str,
+        currency_type: &str,
+    ) -> Result<CurrencyOverviewResponse> {
+        let body = self.fetch(self.endpoints.currency_overview(league, currency_type)?).await?;
+        self.endpoints.parse_currency_overview(&body)
+    }
+
+    /// Get item overview data
+    pub async fn get_item_overview(
+        &self,
+        league: &str,
+        item_type: &str,
+    ) -> Result<ItemOverviewResponse> {
+        let body = self.fetch(self.endpoints.item_overview(league, item_type)?).await?;
+        self.endpoints.parse_item_overview(&body)
+    }
+
+    /// Get the daily price history of one currency (`CurrencyDetail::id`)
+    pub async fn get_currency_history(
+        &self,
+        league: &str,
+        currency_type: &str,
+        currency_id: i32,
+    ) -> Result<CurrencyHistoryResponse> {
+        let body = self
+            .fetch(self.endpoints.currency_history(league, currency_type, currency_id)?)
+            .await?;
+        self.endpoints.parse_json(&body, "currency history")
+    }
+
+    /// Get the daily price history of one item (`ItemLine::id`)
+    pub async fn get_item_history(
+        &self,
+        league: &str,
+        item_type: &str,
+        item_id: i32,
+    ) -> Result<Vec<HistoryPoint>> {
+        let body = self.fetch(self.endpoints.item_history(league, item_type, item_id)?).await?;
+        self.endpoints.parse_json(&body, "item history")
+    }
+
+    /// Get PoE2 currency exchange overview data
+    pub async fn get_exchange_overview(
+        &self,
+        league: &str,
+        overview_type: &str,
+    ) -> Result<Poe2ExchangeOverviewResponse> {
+        let body = self.fetch(self.endpoints.exchange_overview(league, overview_type)?).await?;
+        self.endpoints.parse_json(&body, "exchange")
+    }
+
+    /// Get build aggregates for a league, addressed by its index state url (e.g. "settlers")
+    pub async fn get_build_overview(&self, league_url: &str) -> Result<BuildOverviewResponse> {
+        let body = self.fetch(self.endpoints.build_overview(league_url)?).await?;
+        self.endpoints.parse_json(&body, "build")
+    }
+
+    /// Get the leagues poe.ninja currently tracks for the selected game
+    pub async fn get_leagues(&self) -> Result<Vec<EconomyLeague>> {
+        let body = self.fetch(self.endpoints.leagues()).await?;
+        self.endpoints.parse_leagues(&body)
+    }
+
+    /// Search names containing `query` across `types` (every overview type when empty)
+    pub async fn search(&self, league: &str, query: &str, types: &[&str]) -> Result<Vec<SearchHit>> {
+        let needle = query.to_lowercase();
+        let mut hits = Vec::new();
+        for overview_type in search_types(types)? {
+            if crate::get_currency_types().contains(&overview_type) {
+                let response = self.get_currency_overview(league, overview_type).await?;
+                hits.extend(currency_hits(&response, overview_type, &needle));
+            } else {
+                let response = self.get_item_overview(league, overview_type).await?;
+                hits.extend(item_hits(&response, overview_type, &needle));
+            }
+        }
+        sort_hits(&mut hits);
+        Ok(hits)
+    }
+}
+
+impl Default for PoeNinjaClient {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 // URL encoding helper
-mod urlencoding {
+pub(crate) mod urlencoding {
     pub fn encode(input: &str) -> String {
         input
-            .chars()
-            .map(|c| match c {
-                ' ' => "%20".to_string(),
-                c if c.is_ascii_alphanumeric() => c.to_string(),
-                c => format!("%{:02X}", c as u8),
+            .bytes()
+            .map(|b| match b {
+                b' ' => "%20".to_string(),
+                b if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'~') => {
+                    (b as char).to_string()
+                }
+                b => format!("%{:02X}", b),
             })
             .collect()
     }
@@ -152,30 +800,154 @@ mod tests {
         }
     }
 
+    struct StaticTransport(&'static str);
+
+    #[async_trait]
+    impl Transport for StaticTransport {
+        async fn fetch(&self, _url: &str, _key: &RequestKey) -> Result<String> {
+            Ok(self.0.to_string())
+        }
+    }
+
+    #[cfg(feature = "cache")]
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("poe-ninja-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_request_key_paths() {
+        let client = PoeNinjaClient::new();
+        let key = client.endpoints.key("itemoverview", Some("Hardcore Settlers"), Some("UniqueWeapon"));
+        assert_eq!(key.path(), PathBuf::from("poe1/itemoverview/hardcore-settlers/UniqueWeapon.json"));
+
+        let client = PoeNinjaClient::new().with_game(Game::Poe2);
+        assert_eq!(client.endpoints.key("indexstate", None, None).path(), PathBuf::from("poe2/indexstate.json"));
+    }
+
+    #[test]
+    fn test_custom_base_url_survives_game_switch() {
+        let client = PoeNinjaClient::new().with_base_url("http://mirror.local/api/").with_game(Game::Poe2);
+        assert_eq!(client.endpoints.base_url, "http://mirror.local/api");
+        assert_eq!(client.endpoints.game(), Game::Poe2);
+
+        let client = PoeNinjaClient::new().with_game(Game::Poe2).with_base_url("http://mirror.local/api");
+        assert_eq!(client.endpoints.base_url, "http://mirror.local/api");
+
+        let client = PoeNinjaClient::new().with_game(Game::Poe2);
+        assert_eq!(client.endpoints.base_url, Game::Poe2.base_url());
+        let client = client.with_game(Game::Poe1);
+        assert_eq!(client.endpoints.base_url, Game::Poe1.base_url());
+    }
+
+    #[cfg(feature = "cache")]
+    #[tokio::test]
+    async fn test_replay_fixture_directory() {
+        let client = PoeNinjaClient::new()
+            .with_transport(Box::new(ReplayTransport::new("tests/fixtures/replay")));
+
+        let currencies = client.get_currency_overview("Standard", "Currency").await.unwrap();
+        assert_eq!(currencies.lines[0].currency_type_name, "Divine Orb");
+
+        let error = client.get_item_overview("Standard", "Oil").await.unwrap_err();
+        assert!(error.to_string().contains("No recorded response"), "{}", error);
+    }
+
+    #[cfg(feature = "cache")]
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let dir = scratch_dir("record");
+        let body = include_str!("../tests/fixtures/replay/poe1/itemoverview/standard/UniqueWeapon.json");
+
+        let recording = PoeNinjaClient::new()
+            .with_transport(Box::new(RecordTransport::new(Box::new(StaticTransport(body)), &dir)));
+        let live = recording.get_item_overview("Settlers", "UniqueWeapon").await.unwrap();
+
+        let replaying = PoeNinjaClient::new().with_transport(Box::new(ReplayTransport::new(&dir)));
+        let replayed = replaying.get_item_overview("Settlers", "UniqueWeapon").await.unwrap();
+
+        assert_eq!(live.lines.len(), replayed.lines.len());
+        assert_eq!(replayed.lines[0].name, "Starforge");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "cache")]
+    #[tokio::test]
+    async fn test_cache_transport_ttl() {
+        let dir = scratch_dir("cache");
+        let key = PoeNinjaClient::new().endpoints.key("indexstate", None, None);
+
+        let warm = CacheTransport::new(Box::new(StaticTransport("first")), &dir, Duration::from_secs(3600));
+        assert_eq!(warm.fetch("", &key).await.unwrap(), "first");
+
+        let fresh = CacheTransport::new(Box::new(StaticTransport("second")), &dir, Duration::from_secs(3600));
+        assert_eq!(fresh.fetch("", &key).await.unwrap(), "first");
+
+        let expired = CacheTransport::new(Box::new(StaticTransport("second")), &dir, Duration::ZERO);
+        assert_eq!(expired.fetch("", &key).await.unwrap(), "second");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_skipped_lines_are_handed_to_the_caller() {
+        let body = include_str!("../tests/fixtures/replay/poe1/itemoverview/standard/UniqueJewel.json");
+        let client = PoeNinjaClient::new().with_transport(Box::new(StaticTransport(body)));
+
+        let response = client.get_item_overview("Standard", "UniqueJewel").await.unwrap();
+        assert!(!response.lines.is_empty());
+        assert_eq!(client.take_skipped().len(), 1);
+        assert!(client.take_skipped().is_empty());
+    }
+
+    #[cfg(feature = "cache")]
+    #[tokio::test]
+    async fn test_write_failures_warn_only_when_asked() {
+        let dir = scratch_dir("unwritable");
+        std::fs::create_dir_all(&dir).unwrap();
+        // A file where the endpoint directory should be makes every write fail
+        let blocked = dir.join("blocked");
+        std::fs::write(&blocked, "").unwrap();
+        let key = PoeNinjaClient::new().endpoints.key("indexstate", None, None);
+
+        let strict = RecordTransport::new(Box::new(StaticTransport("body")), &blocked);
+        assert!(strict.fetch("", &key).await.is_err());
+
+        let warnings = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = warnings.clone();
+        let warn: WriteWarning = std::sync::Arc::new(move |_: &anyhow::Error| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        });
+        let lenient = RecordTransport::new(Box::new(StaticTransport("body")), &blocked).with_write_warning(warn.clone());
+        assert_eq!(lenient.fetch("", &key).await.unwrap(), "body");
+        let cache = CacheTransport::new(Box::new(StaticTransport("body")), &blocked, Duration::ZERO).with_write_warning(warn);
+        assert_eq!(cache.fetch("", &key).await.unwrap(), "body");
+        assert_eq!(warnings.load(std::sync::atomic::Ordering::SeqCst), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[tokio::test]
+    #[cfg_attr(not(feature = "network-tests"), ignore = "requires network access")]
     async fn test_currency_api_raw_response() {
         println!("\n=== Testing Currency API Raw Response ===");
         
         let client = PoeNinjaClient::new();
         let url = format!(
             "{}/currencyoverview?league={}&type={}",
-            client.base_url,
+            client.endpoints.base_url,
             urlencoding::encode("Standard"),
             "Currency"
         );
 
         println!("Making request to: {}", url);
         
-        let response = client
-            .client
-            .get(&url)
-            .send()
+        // The transport fails on non-success statuses
+        let key = client.endpoints.key("currencyoverview", Some("Standard"), Some("Currency"));
+        let raw_text = client
+            .transport
+            .fetch(&url, &key)
             .await
-            .expect("Failed to send request");
-
-        assert!(response.status().is_success(), "API request failed: {}", response.status());
-        
-        let raw_text = response.text().await.expect("Failed to get response text");
+            .expect("Failed to fetch raw response");
         println!("Raw API Response (first 500 chars):");
         println!("{}", &raw_text[..std::cmp::min(500, raw_text.len())]);
         println!("...(truncated)");
@@ -189,6 +961,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[cfg_attr(not(feature = "network-tests"), ignore = "requires network access")]
     async fn test_currency_struct_deserialization() {
         println!("\n=== Testing Currency Struct Deserialization ===");
         
@@ -239,29 +1012,27 @@ mod tests {
     }
 
     #[tokio::test]
+    #[cfg_attr(not(feature = "network-tests"), ignore = "requires network access")]
     async fn test_item_api_raw_response() {
         println!("\n=== Testing Item API Raw Response ===");
         
         let client = PoeNinjaClient::new();
         let url = format!(
             "{}/itemoverview?league={}&type={}",
-            client.base_url,
+            client.endpoints.base_url,
             urlencoding::encode("Standard"),
             "UniqueWeapon"
         );
 
         println!("Making request to: {}", url);
         
-        let response = client
-            .client
-            .get(&url)
-            .send()
+        // The transport fails on non-success statuses
+        let key = client.endpoints.key("itemoverview", Some("Standard"), Some("UniqueWeapon"));
+        let raw_text = client
+            .transport
+            .fetch(&url, &key)
             .await
-            .expect("Failed to send request");
-
-        assert!(response.status().is_success(), "API request failed: {}", response.status());
-        
-        let raw_text = response.text().await.expect("Failed to get response text");
+            .expect("Failed to fetch raw response");
         println!("Raw API Response (first 500 chars):");
         println!("{}", &raw_text[..std::cmp::min(500, raw_text.len())]);
         println!("...(truncated)");
@@ -279,6 +1050,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[cfg_attr(not(feature = "network-tests"), ignore = "requires network access")]
     async fn test_item_struct_deserialization() {
         println!("\n=== Testing Item Struct Deserialization ===");
         
@@ -331,6 +1103,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[cfg_attr(not(feature = "network-tests"), ignore = "requires network access")]
     async fn test_multiple_item_types() {
         println!("\n=== Testing Multiple Item Types ===");
         
@@ -356,6 +1129,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[cfg_attr(not(feature = "network-tests"), ignore = "requires network access")]
     async fn test_currency_types() {
         println!("\n=== Testing Currency Types ===");
         
@@ -383,6 +1157,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[cfg_attr(not(feature = "network-tests"), ignore = "requires network access")]
     async fn test_field_compatibility() {
         println!("\n=== Testing Field Compatibility ===");
         
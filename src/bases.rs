@@ -0,0 +1,256 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::types::*;
+
+/// Lower edges of the item level buckets above the first ("<82")
+pub const ILVL_EDGES: [i32; 5] = [82, 83, 84, 85, 86];
+
+/// Influences poe.ninja lists as BaseType variants, in column order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Influence {
+    /// No variant: an uninfluenced base
+    None,
+    Shaper,
+    Elder,
+    Crusader,
+    Redeemer,
+    Hunter,
+    Warlord,
+}
+
+impl Influence {
+    const ALL: [Influence; 7] = [
+        Influence::None,
+        Influence::Shaper,
+        Influence::Elder,
+        Influence::Crusader,
+        Influence::Redeemer,
+        Influence::Hunter,
+        Influence::Warlord,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Influence::None => "None",
+            Influence::Shaper => "Shaper",
+            Influence::Elder => "Elder",
+            Influence::Crusader => "Crusader",
+            Influence::Redeemer => "Redeemer",
+            Influence::Hunter => "Hunter",
+            Influence::Warlord => "Warlord",
+        }
+    }
+
+    /// The influence a BaseType `variant` names, `None` for no variant
+    pub fn from_variant(variant: Option<&str>) -> Option<Self> {
+        match variant {
+            None => Some(Influence::None),
+            Some(variant) => Self::ALL
+                .into_iter()
+                .find(|influence| influence.label().eq_ignore_ascii_case(variant.trim())),
+        }
+    }
+}
+
+/// Index into the item level buckets, 0 being "<82"
+pub fn ilvl_bucket(ilvl: i32) -> usize {
+    ILVL_EDGES.iter().filter(|edge| ilvl >= **edge).count()
+}
+
+pub fn bucket_label(bucket: usize) -> String {
+    match bucket {
+        0 => format!("<{}", ILVL_EDGES[0]),
+        bucket if bucket == ILVL_EDGES.len() => format!("{}+", ILVL_EDGES[bucket - 1]),
+        bucket => ILVL_EDGES[bucket - 1].to_string(),
+    }
+}
+
+/// A BaseType line with its item level (`levelRequired`) and influence (`variant`) decoded
+#[derive(Debug, Clone, PartialEq)]
+pub struct BaseLine {
+    pub base_type: String,
+    pub ilvl: i32,
+    pub influence: Influence,
+    pub chaos_value: f64,
+}
+
+/// BaseType lines decoded into bases, plus the lines whose variant is not a known influence
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DecodedBases {
+    pub lines: Vec<BaseLine>,
+    /// How many lines each unknown variant accounted for
+    pub unknown_variants: BTreeMap<String, usize>,
+}
+
+impl DecodedBases {
+    pub fn skipped(&self) -> usize {
+        self.unknown_variants.values().sum()
+    }
+}
+
+pub fn lines_from_items(items: &[ItemLine]) -> DecodedBases {
+    let mut decoded = DecodedBases::default();
+    for item in items {
+        match Influence::from_variant(item.variant.as_deref()) {
+            Some(influence) => decoded.lines.push(BaseLine {
+                base_type: item.name.clone(),
+                ilvl: item.level_required.unwrap_or(0),
+                influence,
+                chaos_value: item.chaos_value,
+            }),
+            None => {
+                let variant = item.variant.as_deref().unwrap_or_default().trim().to_string();
+                *decoded.unknown_variants.entry(variant).or_default() += 1;
+            }
+        }
+    }
+    decoded
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BaseFilters {
+    /// Case-insensitive part of the base type name
+    pub name: Option<String>,
+    pub min_ilvl: Option<i32>,
+    /// Empty keeps every influence
+    pub influences: Vec<Influence>,
+}
+
+impl BaseFilters {
+    pub fn allows(&self, line: &BaseLine) -> bool {
+        self.name
+            .as_ref()
+            .is_none_or(|name| line.base_type.to_lowercase().contains(&name.to_lowercase()))
+            && self.min_ilvl.is_none_or(|min| line.ilvl >= min)
+            && (self.influences.is_empty() || self.influences.contains(&line.influence))
+    }
+}
+
+/// One base type and item level bucket; cells hold the cheapest line per influence
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MatrixRow {
+    pub base_type: String,
+    pub ilvl: String,
+    pub cells: BTreeMap<Influence, f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Matrix {
+    /// Influences with at least one cell, in column order
+    pub influences: Vec<Influence>,
+    pub rows: Vec<MatrixRow>,
+}
+
+/// Group lines by base type name, then by item level bucket, with one column per influence
+pub fn matrix(lines: &[BaseLine]) -> Matrix {
+    let mut grouped: BTreeMap<(&str, usize), BTreeMap<Influence, f64>> = BTreeMap::new();
+    for line in lines {
+        let cells = grouped.entry((&line.base_type, ilvl_bucket(line.ilvl))).or_default();
+        let cell = cells.entry(line.influence).or_insert(line.chaos_value);
+        *cell = cell.min(line.chaos_value);
+    }
+
+    let mut influences: Vec<Influence> = grouped.values().flat_map(|cells| cells.keys().copied()).collect();
+    influences.sort();
+    influences.dedup();
+
+    Matrix {
+        influences,
+        rows: grouped
+            .into_iter()
+            .map(|((base_type, bucket), cells)| MatrixRow {
+                base_type: base_type.to_string(),
+                ilvl: bucket_label(bucket),
+                cells,
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(base_type: &str, ilvl: i32, influence: Influence, chaos_value: f64) -> BaseLine {
+        BaseLine {
+            base_type: base_type.to_string(),
+            ilvl,
+            influence,
+            chaos_value,
+        }
+    }
+
+    #[test]
+    fn test_buckets_and_influences() {
+        let labels: Vec<String> = [1, 81, 82, 85, 86, 100].iter().map(|ilvl| bucket_label(ilvl_bucket(*ilvl))).collect();
+        assert_eq!(labels, vec!["<82", "<82", "82", "85", "86+", "86+"]);
+
+        assert_eq!(Influence::from_variant(None), Some(Influence::None));
+        assert_eq!(Influence::from_variant(Some("crusader")), Some(Influence::Crusader));
+        assert_eq!(Influence::from_variant(Some("Synthesised")), None);
+    }
+
+    #[test]
+    fn test_unknown_variants_are_counted() {
+        let item = |variant: Option<&str>| ItemLine {
+            name: "Vaal Regalia".to_string(),
+            variant: variant.map(str::to_string),
+            level_required: Some(86),
+            chaos_value: 10.0,
+            ..Default::default()
+        };
+        let decoded = lines_from_items(&[
+            item(None),
+            item(Some("Shaper")),
+            item(Some("Synthesised")),
+            item(Some(" Synthesised ")),
+            item(Some("Eater")),
+        ]);
+
+        assert_eq!(decoded.lines.len(), 2);
+        assert_eq!(decoded.skipped(), 3);
+        assert_eq!(decoded.unknown_variants["Synthesised"], 2);
+        assert_eq!(decoded.unknown_variants["Eater"], 1);
+    }
+
+    #[test]
+    fn test_matrix_groups_by_base_and_bucket() {
+        let lines = vec![
+            line("Vaal Regalia", 86, Influence::Shaper, 120.0),
+            line("Vaal Regalia", 87, Influence::Shaper, 150.0),
+            line("Vaal Regalia", 86, Influence::None, 15.0),
+            line("Vaal Regalia", 84, Influence::Elder, 20.0),
+            line("Hubris Circlet", 75, Influence::None, 0.5),
+        ];
+        let result = matrix(&lines);
+
+        assert_eq!(result.influences, vec![Influence::None, Influence::Shaper, Influence::Elder]);
+        let rows: Vec<(&str, &str, usize)> = result
+            .rows
+            .iter()
+            .map(|row| (row.base_type.as_str(), row.ilvl.as_str(), row.cells.len()))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![("Hubris Circlet", "<82", 1), ("Vaal Regalia", "84", 1), ("Vaal Regalia", "86+", 2)]
+        );
+        assert_eq!(result.rows[2].cells[&Influence::Shaper], 120.0);
+    }
+
+    #[test]
+    fn test_filters() {
+        let filters = BaseFilters {
+            name: Some("regalia".to_string()),
+            min_ilvl: Some(85),
+            influences: vec![Influence::Shaper, Influence::Elder],
+        };
+        assert!(filters.allows(&line("Vaal Regalia", 86, Influence::Shaper, 1.0)));
+        assert!(!filters.allows(&line("Vaal Regalia", 84, Influence::Shaper, 1.0)));
+        assert!(!filters.allows(&line("Vaal Regalia", 86, Influence::Hunter, 1.0)));
+        assert!(!filters.allows(&line("Hubris Circlet", 86, Influence::Elder, 1.0)));
+        assert!(BaseFilters::default().allows(&line("Hubris Circlet", 1, Influence::None, 1.0)));
+    }
+}
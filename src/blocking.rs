@@ -0,0 +1,144 @@
+use anyhow::{Context, Result};
+use reqwest::blocking::Client;
+use std::time::Duration;
+
+use crate::api::{currency_hits, item_hits, search_types, sort_hits, Endpoints, Game, RequestKey, SearchHit};
+use crate::schema::SkippedLine;
+use crate::types::*;
+
+/// Synchronous counterpart of [`crate::api::PoeNinjaClient`], sharing its URLs and parsing.
+/// Don't call it from inside a tokio runtime.
+pub struct PoeNinjaClient {
+    client: Client,
+    endpoints: Endpoints,
+}
+
+impl PoeNinjaClient {
+    pub fn new() -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .user_agent("poe-ninja-cli/0.1.0")
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Self {
+            client,
+            endpoints: Endpoints::new(Game::Poe1),
+        }
+    }
+
+    /// Switch endpoints to the given game
+    pub fn with_game(mut self, game: Game) -> Self {
+        self.endpoints.set_game(game);
+        self
+    }
+
+    /// Point requests at a mirror or proxy of the game's API
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.endpoints.set_base_url(base_url.into());
+        self
+    }
+
+    /// Fail on any drift from the response model instead of skipping bad lines
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.endpoints.set_strict(strict);
+        self
+    }
+
+    pub fn game(&self) -> Game {
+        self.endpoints.game()
+    }
+
+    /// Overview lines that did not match the model and were left out since the last call
+    pub fn take_skipped(&self) -> Vec<SkippedLine> {
+        self.endpoints.take_skipped()
+    }
+
+    fn fetch(&self, (url, _key): (String, RequestKey)) -> Result<String> {
+        let response = self.client.get(&url).send().context("Failed to send request")?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "API request failed with status: {}",
+                response.status()
+            ));
+        }
+
+        response.text().context("Failed to read response body")
+    }
+
+    /// The unparsed overview body, for schema checks
+    pub fn get_raw_overview(&self, league: &str, overview_type: &str) -> Result<String> {
+        self.fetch(self.endpoints.overview(league, overview_type)?)
+    }
+
+    /// Get currency overview data
+    pub fn get_currency_overview(&self, league: &str, currency_type: &str) -> Result<CurrencyOverviewResponse> {
+        let body = self.fetch(self.endpoints.currency_overview(league, currency_type)?)?;
+        self.endpoints.parse_currency_overview(&body)
+    }
+
+    /// Get item overview data
+    pub fn get_item_overview(&self, league: &str, item_type: &str) -> Result<ItemOverviewResponse> {
+        let body = self.fetch(self.endpoints.item_overview(league, item_type)?)?;
+        self.endpoints.parse_item_overview(&body)
+    }
+
+    /// Get the daily price history of one currency (`CurrencyDetail::id`)
+    pub fn get_currency_history(
+        &self,
+        league: &str,
+        currency_type: &str,
+        currency_id: i32,
+    ) -> Result<CurrencyHistoryResponse> {
+        let body = self.fetch(self.endpoints.currency_history(league, currency_type, currency_id)?)?;
+        self.endpoints.parse_json(&body, "currency history")
+    }
+
+    /// Get the daily price history of one item (`ItemLine::id`)
+    pub fn get_item_history(&self, league: &str, item_type: &str, item_id: i32) -> Result<Vec<HistoryPoint>> {
+        let body = self.fetch(self.endpoints.item_history(league, item_type, item_id)?)?;
+        self.endpoints.parse_json(&body, "item history")
+    }
+
+    /// Get PoE2 currency exchange overview data
+    pub fn get_exchange_overview(&self, league: &str, overview_type: &str) -> Result<Poe2ExchangeOverviewResponse> {
+        let body = self.fetch(self.endpoints.exchange_overview(league, overview_type)?)?;
+        self.endpoints.parse_json(&body, "exchange")
+    }
+
+    /// Get build aggregates for a league, addressed by its index state url (e.g. "settlers")
+    pub fn get_build_overview(&self, league_url: &str) -> Result<BuildOverviewResponse> {
+        let body = self.fetch(self.endpoints.build_overview(league_url)?)?;
+        self.endpoints.parse_json(&body, "build")
+    }
+
+    /// Get the leagues poe.ninja currently tracks for the selected game
+    pub fn get_leagues(&self) -> Result<Vec<EconomyLeague>> {
+        let body = self.fetch(self.endpoints.leagues())?;
+        self.endpoints.parse_leagues(&body)
+    }
+
+    /// Search names containing `query` across `types` (every overview type when empty)
+    pub fn search(&self, league: &str, query: &str, types: &[&str]) -> Result<Vec<SearchHit>> {
+        let needle = query.to_lowercase();
+        let mut hits = Vec::new();
+        for overview_type in search_types(types)? {
+            if crate::get_currency_types().contains(&overview_type) {
+                let response = self.get_currency_overview(league, overview_type)?;
+                hits.extend(currency_hits(&response, overview_type, &needle));
+            } else {
+                let response = self.get_item_overview(league, overview_type)?;
+                hits.extend(item_hits(&response, overview_type, &needle));
+            }
+        }
+        sort_hits(&mut hits);
+        Ok(hits)
+    }
+}
+
+impl Default for PoeNinjaClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::source::PriceSource;
+use crate::pricing::{LoadedBook, PriceBook, PriceQuery, UNIQUE_CATEGORIES};
+use crate::types::*;
+
+/// How many sampled characters use something, and their share of the total
+#[derive(Debug, Clone, PartialEq)]
+pub struct Usage {
+    pub name: String,
+    pub characters: usize,
+    pub percent: f64,
+}
+
+/// Base class of an ascendancy; names that are not ascendancies map to themselves
+pub fn base_class(ascendancy: &str) -> &str {
+    match ascendancy {
+        "Juggernaut" | "Berserker" | "Chieftain" => "Marauder",
+        "Raider" | "Deadeye" | "Pathfinder" | "Warden" => "Ranger",
+        "Occultist" | "Elementalist" | "Necromancer" => "Witch",
+        "Slayer" | "Gladiator" | "Champion" => "Duelist",
+        "Inquisitor" | "Hierophant" | "Guardian" => "Templar",
+        "Assassin" | "Trickster" | "Saboteur" => "Shadow",
+        "Ascendant" | "Reliquarian" => "Scion",
+        other => other,
+    }
+}
+
+/// Resolve a league name to the url slug the builds endpoint expects,
+/// guessing from the name when the index state does not list it
+pub fn league_url(leagues: &[EconomyLeague], league: &str) -> String {
+    leagues
+        .iter()
+        .find(|known| known.name.eq_ignore_ascii_case(league))
+        .map(|known| known.url.clone())
+        .unwrap_or_else(|| {
+            league
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric())
+                .collect::<String>()
+                .to_lowercase()
+        })
+}
+
+fn sorted_usage(counts: impl IntoIterator<Item = (String, usize)>, total: usize) -> Vec<Usage> {
+    let mut usage: Vec<Usage> = counts
+        .into_iter()
+        .map(|(name, characters)| Usage {
+            percent: if total > 0 {
+                characters as f64 / total as f64 * 100.0
+            } else {
+                0.0
+            },
+            name,
+            characters,
+        })
+        .collect();
+
+    usage.sort_by(|a, b| b.characters.cmp(&a.characters).then_with(|| a.name.cmp(&b.name)));
+    usage
+}
+
+/// Share of sampled characters per ascendancy, most popular first
+pub fn ascendancy_shares(overview: &BuildOverviewResponse) -> Vec<Usage> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for index in &overview.classes {
+        if let Some(name) = overview.class_names.get(*index) {
+            *counts.entry(name.clone()).or_default() += 1;
+        }
+    }
+    sorted_usage(counts, overview.classes.len())
+}
+
+/// Share of sampled characters per base class, most popular first
+pub fn class_shares(overview: &BuildOverviewResponse) -> Vec<Usage> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for usage in ascendancy_shares(overview) {
+        *counts.entry(base_class(&usage.name).to_string()).or_default() += usage.characters;
+    }
+    sorted_usage(counts, overview.classes.len())
+}
+
+fn usage_of(names: &[&str], uses: &HashMap<String, Vec<usize>>, total: usize) -> Vec<Usage> {
+    let counts = uses.iter().filter_map(|(index, characters)| {
+        let name = names.get(index.parse::<usize>().ok()?)?;
+        Some((name.to_string(), characters.len()))
+    });
+    sorted_usage(counts, total)
+}
+
+pub fn unique_item_usage(overview: &BuildOverviewResponse) -> Vec<Usage> {
+    let names: Vec<&str> = overview.unique_items.iter().map(|item| item.name.as_str()).collect();
+    usage_of(&names, &overview.unique_item_use, overview.classes.len())
+}
+
+pub fn skill_usage(overview: &BuildOverviewResponse) -> Vec<Usage> {
+    let names: Vec<&str> = overview.active_skills.iter().map(|skill| skill.name.as_str()).collect();
+    usage_of(&names, &overview.active_skill_use, overview.classes.len())
+}
+
+pub fn keystone_usage(overview: &BuildOverviewResponse) -> Vec<Usage> {
+    let names: Vec<&str> = overview.keystones.iter().map(|keystone| keystone.name.as_str()).collect();
+    usage_of(&names, &overview.keystone_use, overview.classes.len())
+}
+
+/// A unique item's build usage next to its current price
+#[derive(Debug, Clone, PartialEq)]
+pub struct UniqueDemand {
+    pub usage: Usage,
+    pub category: Option<String>,
+    pub chaos_value: Option<f64>,
+}
+
+/// Load the unique overviews the used items need. Each item is searched
+/// through the unique categories until it resolves, and categories that fail
+/// to load are skipped.
+pub async fn load_price_book(source: &dyn PriceSource, league: &str, used: &[Usage]) -> LoadedBook {
+    let mut loaded = LoadedBook::default();
+    loaded.ensure(source, league, "Currency").await;
+
+    for usage in used {
+        let query = PriceQuery::named(&usage.name);
+        for category in UNIQUE_CATEGORIES {
+            if loaded.book.lookup_in(UNIQUE_CATEGORIES, &query).is_some() {
+                break;
+            }
+            loaded.ensure(source, league, category).await;
+        }
+    }
+
+    loaded
+}
+
+/// Pair each used unique with the price of its plainest variant
+pub fn demand_vs_price(usage: &[Usage], book: &PriceBook) -> Vec<UniqueDemand> {
+    usage
+        .iter()
+        .map(|usage| {
+            let price = book.lookup_in(UNIQUE_CATEGORIES, &PriceQuery::named(&usage.name));
+            UniqueDemand {
+                usage: usage.clone(),
+                category: price.as_ref().map(|price| price.category.clone()),
+                chaos_value: price.map(|price| price.chaos_value),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overview() -> BuildOverviewResponse {
+        serde_json::from_str(include_str!("../tests/fixtures/builds/build_overview.json")).unwrap()
+    }
+
+    #[test]
+    fn test_ascendancy_and_class_shares() {
+        let overview = overview();
+
+        let ascendancies = ascendancy_shares(&overview);
+        assert_eq!(ascendancies[0].name, "Necromancer");
+        assert_eq!(ascendancies[0].characters, 5);
+        assert_eq!(ascendancies[0].percent, 50.0);
+        assert_eq!(ascendancies.iter().map(|u| u.characters).sum::<usize>(), 10);
+
+        let classes = class_shares(&overview);
+        assert_eq!(classes[0].name, "Witch");
+        assert_eq!(classes.len(), 4);
+        assert_eq!(base_class("Warden"), "Ranger");
+    }
+
+    #[test]
+    fn test_item_skill_and_keystone_usage() {
+        let overview = overview();
+
+        let items = unique_item_usage(&overview);
+        assert_eq!(items[0].name, "Tabula Rasa");
+        assert_eq!(items[0].percent, 50.0);
+        assert_eq!(items[2].name, "Headhunter");
+
+        assert_eq!(skill_usage(&overview)[0].name, "Raise Spectre");
+        assert_eq!(keystone_usage(&overview)[0].name, "Mind Over Matter");
+    }
+
+    #[tokio::test]
+    async fn test_failed_categories_leave_uniques_unpriced() {
+        let used = unique_item_usage(&overview());
+        let source = crate::source::MockSource::new().with_items(
+            "Standard",
+            "UniqueArmour",
+            vec![ItemLine {
+                name: "Tabula Rasa".to_string(),
+                chaos_value: 10.0,
+                details_id: "tabula-rasa".to_string(),
+                ..Default::default()
+            }],
+        );
+
+        let loaded = load_price_book(&source, "Standard", &used[..1]).await;
+        let skipped: Vec<&str> = loaded.skipped.iter().map(|(category, _)| category.as_str()).collect();
+        assert_eq!(skipped, vec!["Currency", "UniqueWeapon"]);
+
+        let demand = demand_vs_price(&used[..1], &loaded.book);
+        assert_eq!(demand[0].category.as_deref(), Some("UniqueArmour"));
+        assert_eq!(demand[0].chaos_value, Some(10.0));
+    }
+
+    #[test]
+    fn test_league_url() {
+        let leagues = vec![EconomyLeague {
+            name: "Hardcore Settlers".to_string(),
+            url: "settlershc".to_string(),
+            display_name: "Hardcore Settlers".to_string(),
+            hardcore: true,
+            indexed: true,
+        }];
+
+        assert_eq!(league_url(&leagues, "hardcore settlers"), "settlershc");
+        assert_eq!(league_url(&leagues, "Solo Self-Found"), "soloselffound");
+    }
+}
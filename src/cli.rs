@@ -0,0 +1,423 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::api::Game;
+use crate::bases::Influence;
+use crate::config::{OutputFormat, Unit};
+use crate::forecast::Model;
+use crate::top::Confidence;
+
+/// Command-line arguments for the `poe-ninja-cli` binary
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+#[command(name = "poe-ninja")]
+pub struct Cli {
+    /// Which game's economy to query
+    #[arg(long, global = true, value_enum, default_value_t = Game::Poe1)]
+    pub game: Game,
+    /// Serve responses only from the local cache (or the --replay directory)
+    #[arg(long, global = true)]
+    pub offline: bool,
+    /// Also write raw responses to this directory
+    #[arg(long, global = true, value_name = "DIR", conflicts_with_all = ["offline", "replay"])]
+    pub record: Option<PathBuf>,
+    /// Serve raw responses recorded with --record from this directory
+    #[arg(long, global = true, value_name = "DIR")]
+    pub replay: Option<PathBuf>,
+    /// Read overviews from `<DIR>/<league>/<type>.json` instead of poe.ninja
+    #[arg(long, global = true, value_name = "DIR", conflicts_with_all = ["offline", "record", "replay"])]
+    pub data_dir: Option<PathBuf>,
+    /// Fail when a response drifts from the expected model instead of skipping bad lines
+    #[arg(long, global = true)]
+    pub strict: bool,
+    /// Use a named profile from the config file
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+    /// Output format (overrides the config file)
+    #[arg(long, global = true, value_enum)]
+    pub format: Option<OutputFormat>,
+    /// Currency unit for values (overrides the config file)
+    #[arg(long, global = true, value_enum)]
+    pub unit: Option<Unit>,
+    #[command(subcommand)]
+    pub command: Commands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Get currency prices and exchange rates
+    Currency {
+        /// League name (e.g., "Settlers", "Standard", "Hardcore"); defaults to the configured league
+        #[arg(short, long)]
+        league: Option<String>,
+        /// Currency type (Currency, Fragment)
+        #[arg(short, long, default_value = "Currency")]
+        currency_type: String,
+        /// Filter by currency name
+        #[arg(short, long)]
+        name: Option<String>,
+        /// Print bulk exchange links for the listed currencies
+        #[arg(long)]
+        trade_link: bool,
+    },
+    /// Get item prices
+    Item {
+        /// League name
+        #[arg(short, long)]
+        league: Option<String>,
+        /// Item type (Oil, Essence, UniqueWeapon, UniqueArmour, etc.)
+        #[arg(short, long)]
+        item_type: String,
+        /// Filter by item name
+        #[arg(short, long)]
+        name: Option<String>,
+        /// Minimum chaos value filter
+        #[arg(long)]
+        min_chaos: Option<f64>,
+        /// Maximum chaos value filter  
+        #[arg(long)]
+        max_chaos: Option<f64>,
+        /// Keep items with a modifier matching this regex, optionally with a condition
+        /// such as ">= 80"; `#` stands for a number or range (repeatable, all must match)
+        #[arg(long = "mod")]
+        mods: Vec<String>,
+        /// List each item's modifiers under it
+        #[arg(long)]
+        show_mods: bool,
+        /// Print trade site search links for the listed items
+        #[arg(long)]
+        trade_link: bool,
+    },
+    /// Build trade site queries for an item or currency (nothing is sent)
+    Trade {
+        /// League name
+        #[arg(short, long)]
+        league: Option<String>,
+        /// Item or currency type (currency types build bulk exchange queries)
+        #[arg(short, long)]
+        item_type: String,
+        /// Item or currency name
+        #[arg(short, long)]
+        name: String,
+        /// Trade id of the currency offered in bulk exchange queries
+        #[arg(long, default_value = "chaos")]
+        have: String,
+    },
+    /// Value a TOML, CSV or JSON file of holdings
+    Portfolio {
+        /// Holdings file (.toml, .csv or .json)
+        file: PathBuf,
+        /// League name
+        #[arg(short, long)]
+        league: Option<String>,
+    },
+    /// Value an exported stash tab (public stash API JSON)
+    StashValue {
+        /// Stash JSON file
+        file: PathBuf,
+        /// League name
+        #[arg(short, long)]
+        league: Option<String>,
+        /// Number of top contributors to list
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+    /// Compare divination card set costs with their reward values
+    Divcards {
+        /// League name
+        #[arg(short, long)]
+        league: Option<String>,
+        /// Filter by card name
+        #[arg(short, long)]
+        name: Option<String>,
+        /// Minimum profit in chaos for a turn-in to be listed
+        #[arg(long, default_value_t = 0.0)]
+        min_profit: f64,
+        /// List unprofitable turn-ins as well
+        #[arg(long)]
+        all: bool,
+    },
+    /// Compare gem variants to find leveling, quality and Vaal profits
+    Gems {
+        /// League name
+        #[arg(short, long)]
+        league: Option<String>,
+        /// Filter by gem name (includes awakened and transfigured gems)
+        #[arg(short, long)]
+        name: Option<String>,
+        /// Sort order
+        #[arg(long, default_value = "profit", value_parser = ["profit", "margin"])]
+        sort: String,
+        /// Only show one strategy
+        #[arg(long, value_parser = ["level", "quality", "vaal"])]
+        strategy: Option<String>,
+        /// TOML file with a custom Vaal Orb outcome table
+        #[arg(long)]
+        outcomes: Option<PathBuf>,
+        /// Minimum profit in chaos
+        #[arg(long)]
+        min_profit: Option<f64>,
+    },
+    /// Rewrite marked BaseType lists in a loot filter from live prices
+    Filter {
+        /// Filter file containing `# poe-ninja:` markers
+        input: PathBuf,
+        /// League name
+        #[arg(short, long)]
+        league: Option<String>,
+        /// Write the filter here instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// TOML file with named chaos thresholds referenced by `tier=`
+        #[arg(long)]
+        tiers: Option<PathBuf>,
+    },
+    /// Compare the same currency or item type across leagues
+    Compare {
+        /// League to compare (repeat for each league, at least two)
+        #[arg(short, long = "league", required = true)]
+        leagues: Vec<String>,
+        /// Item or currency type
+        #[arg(short, long)]
+        item_type: String,
+        /// Filter by name
+        #[arg(short, long)]
+        name: Option<String>,
+        /// Hide entries whose difference is below this percentage
+        #[arg(long)]
+        min_diff_percent: Option<f64>,
+    },
+    /// Summarize a category: value percentiles, low confidence share, price changes and top movers
+    Stats {
+        /// League name
+        #[arg(short, long)]
+        league: Option<String>,
+        /// Item or currency type
+        #[arg(short, long)]
+        item_type: String,
+        /// Filter by name
+        #[arg(short, long)]
+        name: Option<String>,
+        /// Minimum chaos value filter
+        #[arg(long)]
+        min_chaos: Option<f64>,
+        /// Maximum chaos value filter
+        #[arg(long)]
+        max_chaos: Option<f64>,
+        /// Number of gainers and losers to list
+        #[arg(long, default_value_t = 5)]
+        top: usize,
+    },
+    /// Compare two saved overviews or snapshots: new, removed and changed entries
+    Diff {
+        /// Earlier overview: a saved JSON response or a snapshot time (unix seconds, YYYY-MM-DD or YYYY-MM-DDTHH:MM)
+        before: String,
+        /// Later overview, given like the earlier one
+        after: String,
+        /// League of the snapshots when diffing by time
+        #[arg(short, long)]
+        league: Option<String>,
+        /// Item or currency type of the snapshots when diffing by time
+        #[arg(short, long)]
+        item_type: Option<String>,
+        /// Hide changed entries that moved less than this many chaos
+        #[arg(long)]
+        min_chaos_change: Option<f64>,
+        /// Hide changed entries that moved less than this percentage
+        #[arg(long)]
+        min_percent: Option<f64>,
+    },
+    /// Project an item's price over the next days from its sparkline or history
+    Forecast {
+        /// League name
+        #[arg(short, long)]
+        league: Option<String>,
+        /// Item or currency type
+        #[arg(short, long)]
+        item_type: String,
+        /// Item or currency name
+        #[arg(short, long)]
+        name: String,
+        /// Days to project
+        #[arg(long, default_value_t = 7, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        days: usize,
+        /// Model fitted to the daily values
+        #[arg(long, value_enum, default_value_t = Model::Linear)]
+        model: Model,
+        /// Fit the full daily history instead of the 7-day sparkline
+        #[arg(long)]
+        history: bool,
+        /// Projected change, in percent, needed to call a trend
+        #[arg(long, default_value_t = 5.0)]
+        threshold: f64,
+    },
+    /// Flag suspicious entries: sudden jumps, thin listings, pay/receive gaps and outlying variants
+    Anomalies {
+        /// League name
+        #[arg(short, long)]
+        league: Option<String>,
+        /// Item or currency type
+        #[arg(short, long)]
+        item_type: String,
+        /// Hide entries scoring below this (0 to 100)
+        #[arg(long, default_value_t = 0.0)]
+        min_score: f64,
+    },
+    /// List the most valuable entries league-wide, or per category
+    Top {
+        /// League name
+        #[arg(short, long)]
+        league: Option<String>,
+        /// Category to include (repeatable, comma separated; default every category)
+        #[arg(short, long = "item-type", value_delimiter = ',')]
+        item_types: Vec<String>,
+        /// Number of entries to list, or per category with --per-category
+        #[arg(long, default_value_t = 20)]
+        count: usize,
+        /// List the top entries of each category instead of league-wide
+        #[arg(long)]
+        per_category: bool,
+        /// Leave out entries below this confidence
+        #[arg(long, value_enum, default_value_t = Confidence::Low)]
+        min_confidence: Confidence,
+        /// Leave out entries whose name contains this (repeatable, comma separated)
+        #[arg(long, value_delimiter = ',')]
+        exclude: Vec<String>,
+        /// Leave out a category (repeatable, comma separated)
+        #[arg(long, value_delimiter = ',')]
+        exclude_category: Vec<String>,
+        /// Leave out corrupted items
+        #[arg(long)]
+        exclude_corrupted: bool,
+        /// Leave out entries worth more than this, e.g. mirror-tier items
+        #[arg(long)]
+        max_chaos: Option<f64>,
+        /// Milliseconds to wait between category requests
+        #[arg(long, default_value_t = 500)]
+        delay: u64,
+    },
+    /// Price crafting bases as a matrix of item level buckets against influence
+    Bases {
+        /// League name
+        #[arg(short, long)]
+        league: Option<String>,
+        /// Filter by base type name
+        #[arg(short, long)]
+        name: Option<String>,
+        /// Leave out bases below this item level
+        #[arg(long)]
+        min_ilvl: Option<i32>,
+        /// Only show this influence (repeatable, comma separated)
+        #[arg(long, value_enum, value_delimiter = ',')]
+        influence: Vec<Influence>,
+    },
+    /// Show ascendancy shares and the most used uniques, skills and keystones
+    Builds {
+        /// League name
+        #[arg(short, long)]
+        league: Option<String>,
+        /// Number of entries to list per section
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+    /// Serve prices as JSON over HTTP (/currency, /item, /search, /convert, /leagues)
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        bind: String,
+        /// Seconds before a cached overview is refreshed in the background
+        /// (defaults to the configured cache_ttl, else 300)
+        #[arg(long)]
+        ttl: Option<u64>,
+    },
+    /// Export tracked prices as Prometheus metrics on /metrics
+    Exporter {
+        /// TOML file with leagues, categories and cardinality rules
+        config: PathBuf,
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:9898")]
+        bind: String,
+        /// Seconds between fetches (overrides the config file)
+        #[arg(long)]
+        interval: Option<u64>,
+    },
+    /// Show or edit the watchlists saved in the config file
+    Watchlist {
+        #[command(subcommand)]
+        action: WatchlistAction,
+    },
+    /// Price queries read as JSON lines and print one JSON result per line
+    Batch {
+        /// Query file (stdin when omitted or "-")
+        input: Option<PathBuf>,
+        /// League for queries that do not name one
+        #[arg(short, long)]
+        league: Option<String>,
+    },
+    /// Report where live responses drift from the expected response model
+    SchemaCheck {
+        /// League name
+        #[arg(short, long)]
+        league: Option<String>,
+        /// Overview type to check (repeatable; all types when omitted)
+        #[arg(short, long = "item-type")]
+        item_types: Vec<String>,
+    },
+    /// Show, change or check the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// List leagues tracked by poe.ninja
+    Leagues,
+    /// List available item types
+    Types,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum WatchlistAction {
+    /// Price a watchlist and show the changes since it was last shown
+    Show {
+        /// Watchlist name
+        name: String,
+        /// League name
+        #[arg(short, long)]
+        league: Option<String>,
+    },
+    /// List saved watchlists
+    List,
+    /// Add an entry to a watchlist, creating the list if needed
+    Add {
+        /// Watchlist name
+        list: String,
+        /// Item or currency type
+        #[arg(short, long)]
+        item_type: String,
+        /// Item or currency name
+        #[arg(short, long)]
+        name: String,
+        /// Item variant (e.g. "Shaper", "1 passive")
+        #[arg(long)]
+        variant: Option<String>,
+        /// Number of links
+        #[arg(long)]
+        links: Option<i32>,
+    },
+    /// Remove every entry with this name from a watchlist
+    Remove {
+        /// Watchlist name
+        list: String,
+        /// Item or currency name
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigAction {
+    /// Print the config file and the settings in effect
+    Show,
+    /// Set a value, e.g. `league Settlers`, `profiles.hc.league "Hardcore Settlers"` or `aliases.hh Headhunter`
+    Set { key: String, value: String },
+    /// Check the config file for unknown keys and invalid values
+    Validate,
+}
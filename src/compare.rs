@@ -0,0 +1,177 @@
+use std::collections::BTreeMap;
+
+use crate::types::*;
+
+/// The fields of an overview line needed to compare it across leagues
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceRow {
+    pub details_id: String,
+    pub name: String,
+    pub chaos_value: f64,
+}
+
+pub fn rows_from_items(items: &[ItemLine]) -> Vec<PriceRow> {
+    items
+        .iter()
+        .map(|item| PriceRow {
+            details_id: item.details_id.clone(),
+            name: match &item.variant {
+                Some(variant) => format!("{} ({})", item.name, variant),
+                None => item.name.clone(),
+            },
+            chaos_value: item.chaos_value,
+        })
+        .collect()
+}
+
+pub fn rows_from_currencies(currencies: &[CurrencyLine]) -> Vec<PriceRow> {
+    currencies
+        .iter()
+        .map(|currency| PriceRow {
+            details_id: currency.details_id.clone(),
+            name: currency.currency_type_name.clone(),
+            chaos_value: currency.chaos_equivalent.unwrap_or(0.0),
+        })
+        .collect()
+}
+
+/// One `details_id` with its chaos value in each compared league
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComparedEntry {
+    pub details_id: String,
+    pub name: String,
+    /// Values in the same order as the leagues passed to `join_leagues`
+    pub values: Vec<Option<f64>>,
+}
+
+impl ComparedEntry {
+    pub fn in_all_leagues(&self) -> bool {
+        self.values.iter().all(|value| value.is_some())
+    }
+
+    fn present(&self) -> impl Iterator<Item = (usize, f64)> + '_ {
+        self.values
+            .iter()
+            .enumerate()
+            .filter_map(|(index, value)| value.map(|v| (index, v)))
+    }
+
+    /// Index of the league with the lowest value
+    pub fn cheapest(&self) -> Option<usize> {
+        self.present()
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(index, _)| index)
+    }
+
+    /// Index of the league with the highest value
+    pub fn priciest(&self) -> Option<usize> {
+        self.present()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(index, _)| index)
+    }
+
+    /// Highest minus lowest value, when at least two leagues have a price
+    pub fn difference(&self) -> Option<f64> {
+        let low = self.values[self.cheapest()?]?;
+        let high = self.values[self.priciest()?]?;
+        (self.present().count() >= 2).then_some(high - low)
+    }
+
+    /// `difference` relative to the lowest value
+    pub fn difference_percent(&self) -> Option<f64> {
+        let low = self.values[self.cheapest()?]?;
+        let difference = self.difference()?;
+        (low > 0.0).then(|| difference / low * 100.0)
+    }
+}
+
+/// Join per-league rows on `details_id`, largest relative difference first.
+/// Entries missing from some leagues sort after the shared ones.
+pub fn join_leagues(per_league: &[Vec<PriceRow>]) -> Vec<ComparedEntry> {
+    let mut joined: BTreeMap<String, ComparedEntry> = BTreeMap::new();
+
+    for (index, rows) in per_league.iter().enumerate() {
+        for row in rows {
+            let entry = joined
+                .entry(row.details_id.clone())
+                .or_insert_with(|| ComparedEntry {
+                    details_id: row.details_id.clone(),
+                    name: row.name.clone(),
+                    values: vec![None; per_league.len()],
+                });
+            entry.values[index] = Some(row.chaos_value);
+        }
+    }
+
+    let mut entries: Vec<ComparedEntry> = joined.into_values().collect();
+    entries.sort_by(|a, b| {
+        b.in_all_leagues()
+            .cmp(&a.in_all_leagues())
+            .then_with(|| {
+                let a = a.difference_percent().unwrap_or(0.0);
+                let b = b.difference_percent().unwrap_or(0.0);
+                b.total_cmp(&a)
+            })
+    });
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(details_id: &str, chaos_value: f64) -> PriceRow {
+        PriceRow {
+            details_id: details_id.to_string(),
+            name: details_id.to_string(),
+            chaos_value,
+        }
+    }
+
+    #[test]
+    fn test_join_two_leagues() {
+        let standard = vec![row("headhunter", 3000.0), row("kaoms-heart", 50.0), row("old-item", 5.0)];
+        let league = vec![row("headhunter", 6000.0), row("kaoms-heart", 60.0), row("new-item", 1.0)];
+
+        let entries = join_leagues(&[standard, league]);
+        assert_eq!(entries.len(), 4);
+
+        let headhunter = &entries[0];
+        assert_eq!(headhunter.details_id, "headhunter");
+        assert_eq!(headhunter.difference(), Some(3000.0));
+        assert_eq!(headhunter.difference_percent(), Some(100.0));
+        assert_eq!(headhunter.cheapest(), Some(0));
+        assert_eq!(headhunter.priciest(), Some(1));
+
+        assert_eq!(entries[1].details_id, "kaoms-heart");
+        assert!(entries[1].in_all_leagues());
+
+        let missing: Vec<_> = entries.iter().filter(|e| !e.in_all_leagues()).collect();
+        assert_eq!(missing.len(), 2);
+        assert_eq!(missing[0].difference(), None);
+        assert_eq!(missing[0].difference_percent(), None);
+    }
+
+    #[test]
+    fn test_join_three_leagues_uses_spread() {
+        let entries = join_leagues(&[
+            vec![row("divine-orb", 200.0)],
+            vec![row("divine-orb", 150.0)],
+            vec![row("divine-orb", 300.0)],
+        ]);
+
+        assert_eq!(entries[0].values, vec![Some(200.0), Some(150.0), Some(300.0)]);
+        assert_eq!(entries[0].difference(), Some(150.0));
+        assert_eq!(entries[0].difference_percent(), Some(100.0));
+        assert_eq!(entries[0].cheapest(), Some(1));
+        assert_eq!(entries[0].priciest(), Some(2));
+    }
+
+    #[test]
+    fn test_zero_value_has_no_percent() {
+        let entries = join_leagues(&[vec![row("scrap", 0.0)], vec![row("scrap", 1.0)]]);
+
+        assert_eq!(entries[0].difference(), Some(1.0));
+        assert_eq!(entries[0].difference_percent(), None);
+    }
+}
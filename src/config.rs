@@ -0,0 +1,398 @@
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::watchlist::WatchItem;
+
+/// How command results are printed
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Csv,
+}
+
+/// Which currency values are shown in
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Unit {
+    #[default]
+    Chaos,
+    Divine,
+}
+
+/// Settings that the config file, a profile, the environment or the CLI may set
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub league: Option<String>,
+    pub format: Option<OutputFormat>,
+    pub unit: Option<Unit>,
+    /// Seconds a cached response is served without asking poe.ninja again
+    pub cache_ttl: Option<u64>,
+    pub base_url: Option<String>,
+    /// Keep hourly snapshots of live overviews for `diff`
+    pub snapshots: Option<bool>,
+}
+
+pub const PROFILE_KEYS: [&str; 6] = ["league", "format", "unit", "cache_ttl", "base_url", "snapshots"];
+
+impl Profile {
+    /// Fill every unset field from `lower`
+    fn or(self, lower: &Profile) -> Profile {
+        Profile {
+            league: self.league.or_else(|| lower.league.clone()),
+            format: self.format.or(lower.format),
+            unit: self.unit.or(lower.unit),
+            cache_ttl: self.cache_ttl.or(lower.cache_ttl),
+            base_url: self.base_url.or_else(|| lower.base_url.clone()),
+            snapshots: self.snapshots.or(lower.snapshots),
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "league" => self.league = Some(value.to_string()),
+            "format" => self.format = Some(parse_enum(key, value)?),
+            "unit" => self.unit = Some(parse_enum(key, value)?),
+            "cache_ttl" => {
+                self.cache_ttl = Some(value.parse().with_context(|| format!("Invalid cache_ttl '{}'", value))?)
+            }
+            "base_url" => self.base_url = Some(value.to_string()),
+            "snapshots" => {
+                self.snapshots = Some(value.parse().with_context(|| format!("Invalid snapshots '{}', expected true or false", value))?)
+            }
+            _ => bail!("Unknown setting '{}' (expected one of: {})", key, PROFILE_KEYS.join(", ")),
+        }
+        Ok(())
+    }
+
+    fn validate(&self, scope: &str) -> Result<()> {
+        if let Some(url) = &self.base_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                bail!("{}base_url '{}' must start with http:// or https://", scope, url);
+            }
+        }
+        if matches!(&self.league, Some(league) if league.trim().is_empty()) {
+            bail!("{}league must not be empty", scope);
+        }
+        Ok(())
+    }
+
+    /// Read the `POE_NINJA_*` variables
+    pub fn from_env(env: impl Fn(&str) -> Option<String>) -> Result<Profile> {
+        let mut profile = Profile::default();
+        for key in PROFILE_KEYS {
+            if let Some(value) = env(&format!("POE_NINJA_{}", key.to_uppercase())) {
+                profile
+                    .set(key, &value)
+                    .with_context(|| format!("Invalid POE_NINJA_{}", key.to_uppercase()))?;
+            }
+        }
+        Ok(profile)
+    }
+}
+
+fn parse_enum<T: ValueEnum>(key: &str, value: &str) -> Result<T> {
+    T::from_str(value, true).map_err(|_| {
+        let allowed: Vec<String> = T::value_variants()
+            .iter()
+            .filter_map(|variant| variant.to_possible_value().map(|v| v.get_name().to_string()))
+            .collect();
+        anyhow::anyhow!("Invalid {} '{}' (expected one of: {})", key, value, allowed.join(", "))
+    })
+}
+
+/// The user's config file:
+///
+/// ```toml
+/// league = "Settlers"
+/// unit = "divine"
+///
+/// [profiles.hc]
+/// league = "Hardcore Settlers"
+///
+/// [aliases]
+/// div = "Divine Orb"
+/// hh = "Headhunter"
+///
+/// [[watchlists.morning]]
+/// category = "Currency"
+/// name = "Divine Orb"
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    #[serde(flatten)]
+    pub defaults: Profile,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub watchlists: BTreeMap<String, Vec<WatchItem>>,
+    /// Keys nothing else claimed, reported by `validate`
+    #[serde(flatten, skip_serializing)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+/// `$POE_NINJA_CONFIG`, else `$XDG_CONFIG_HOME/poe-ninja-cli/config.toml`,
+/// else `~/.config/poe-ninja-cli/config.toml`
+pub fn config_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("POE_NINJA_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("poe-ninja-cli").join("config.toml"))
+}
+
+impl Config {
+    pub fn parse(content: &str) -> Result<Self> {
+        let config: Self = toml::from_str(content).context("Failed to parse config")?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// A missing file is an empty config
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Self::parse(&content).with_context(|| format!("Invalid config '{}'", path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err).with_context(|| format!("Failed to read config '{}'", path.display())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).with_context(|| format!("Failed to create '{}'", parent.display()))?;
+        }
+        let content = toml::to_string_pretty(self).context("Failed to serialize config")?;
+        std::fs::write(path, content).with_context(|| format!("Failed to write config '{}'", path.display()))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if let Some(key) = self.unknown.keys().next() {
+            bail!(
+                "Unknown setting '{}' (expected one of: {}, profiles, aliases, watchlists)",
+                key,
+                PROFILE_KEYS.join(", ")
+            );
+        }
+        self.defaults.validate("")?;
+        for (name, profile) in &self.profiles {
+            profile.validate(&format!("profiles.{}.", name))?;
+        }
+        for (alias, target) in &self.aliases {
+            if target.trim().is_empty() {
+                bail!("aliases.{} must not be empty", alias);
+            }
+        }
+        for (list, items) in &self.watchlists {
+            if items.iter().any(|item| item.name.trim().is_empty() || item.category.trim().is_empty()) {
+                bail!("watchlists.{} has an entry without a category or name", list);
+            }
+        }
+        Ok(())
+    }
+
+    /// Set `league`, `profiles.<name>.<setting>` or `aliases.<name>`
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key.split_once('.') {
+            None => self.defaults.set(key, value)?,
+            Some(("aliases", alias)) => {
+                self.aliases.insert(alias.to_string(), value.to_string());
+            }
+            Some(("profiles", rest)) => {
+                let (profile, setting) = rest
+                    .rsplit_once('.')
+                    .with_context(|| format!("Expected profiles.<name>.<setting>, got '{}'", key))?;
+                self.profiles.entry(profile.to_string()).or_default().set(setting, value)?;
+            }
+            Some(_) => bail!("Unknown setting '{}'", key),
+        }
+        self.validate()
+    }
+}
+
+/// Effective settings after layering CLI > env > profile > config > defaults
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub league: String,
+    pub format: OutputFormat,
+    pub unit: Unit,
+    pub cache_ttl: Option<u64>,
+    pub base_url: Option<String>,
+    pub snapshots: bool,
+    pub aliases: BTreeMap<String, String>,
+}
+
+impl Settings {
+    /// `cli` holds the global flags; the profile comes from `--profile` or `POE_NINJA_PROFILE`
+    pub fn resolve(
+        config: &Config,
+        cli: &Profile,
+        profile: Option<&str>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self> {
+        let profile_name = profile.map(str::to_string).or_else(|| env("POE_NINJA_PROFILE"));
+        let profile = match &profile_name {
+            Some(name) => config.profiles.get(name).cloned().with_context(|| {
+                let known: Vec<&str> = config.profiles.keys().map(String::as_str).collect();
+                format!("Unknown profile '{}' (configured: {})", name, known.join(", "))
+            })?,
+            None => Profile::default(),
+        };
+
+        let merged = cli
+            .clone()
+            .or(&Profile::from_env(&env)?)
+            .or(&profile)
+            .or(&config.defaults);
+
+        Ok(Settings {
+            league: merged.league.unwrap_or_else(|| "Standard".to_string()),
+            format: merged.format.unwrap_or_default(),
+            unit: merged.unit.unwrap_or_default(),
+            cache_ttl: merged.cache_ttl,
+            base_url: merged.base_url,
+            snapshots: merged.snapshots.unwrap_or(false),
+            aliases: config.aliases.clone(),
+        })
+    }
+
+    /// The `--league` given to a command, else the configured league
+    pub fn league(&self, cli: Option<&str>) -> String {
+        cli.map(str::to_string).unwrap_or_else(|| self.league.clone())
+    }
+
+    /// Expand a user-defined alias (case-insensitive); other names pass through
+    pub fn expand_alias(&self, name: &str) -> String {
+        self.aliases
+            .iter()
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+            .map(|(_, target)| target.clone())
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    pub fn output(&self) -> Output {
+        Output {
+            format: self.format,
+            unit: self.unit,
+        }
+    }
+}
+
+/// How handlers print their tables
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Output {
+    pub format: OutputFormat,
+    pub unit: Unit,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+league = "Settlers"
+unit = "divine"
+cache_ttl = 600
+
+[profiles.hc]
+league = "Hardcore Settlers"
+format = "json"
+
+[aliases]
+div = "Divine Orb"
+
+[[watchlists.morning]]
+category = "UniqueArmour"
+name = "Tabula Rasa"
+links = 6
+"#;
+
+    fn env(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        move |key| vars.iter().find(|(k, _)| *k == key).map(|(_, v)| v.to_string())
+    }
+
+    #[test]
+    fn test_parse_and_round_trip() {
+        let config = Config::parse(CONFIG).unwrap();
+        assert_eq!(config.defaults.league.as_deref(), Some("Settlers"));
+        assert_eq!(config.defaults.cache_ttl, Some(600));
+        assert_eq!(config.profiles["hc"].format, Some(OutputFormat::Json));
+        assert_eq!(config.aliases["div"], "Divine Orb");
+        assert_eq!(config.watchlists["morning"][0].links, Some(6));
+
+        let reparsed = Config::parse(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        assert_eq!(reparsed, config);
+
+        assert!(Config::parse("leauge = \"Settlers\"\n").is_err());
+        assert!(Config::parse("[profiles.hc]\nunit = \"exalted\"\n").is_err());
+        assert!(Config::parse("base_url = \"poe.ninja\"\n").is_err());
+    }
+
+    #[test]
+    fn test_precedence() {
+        let config = Config::parse(CONFIG).unwrap();
+
+        let plain = Settings::resolve(&config, &Profile::default(), None, env(&[])).unwrap();
+        assert_eq!(plain.league, "Settlers");
+        assert_eq!(plain.unit, Unit::Divine);
+        assert_eq!(plain.format, OutputFormat::Table);
+        assert!(!plain.snapshots);
+
+        let hc = Settings::resolve(&config, &Profile::default(), Some("hc"), env(&[])).unwrap();
+        assert_eq!(hc.league, "Hardcore Settlers");
+        assert_eq!(hc.format, OutputFormat::Json);
+        assert_eq!(hc.cache_ttl, Some(600));
+
+        let from_env = Settings::resolve(
+            &config,
+            &Profile::default(),
+            None,
+            env(&[("POE_NINJA_PROFILE", "hc"), ("POE_NINJA_FORMAT", "csv")]),
+        )
+        .unwrap();
+        assert_eq!(from_env.league, "Hardcore Settlers");
+        assert_eq!(from_env.format, OutputFormat::Csv);
+
+        let snapshots = Settings::resolve(&config, &Profile::default(), None, env(&[("POE_NINJA_SNAPSHOTS", "true")])).unwrap();
+        assert!(snapshots.snapshots);
+
+        let cli = Profile {
+            format: Some(OutputFormat::Table),
+            ..Profile::default()
+        };
+        let from_cli = Settings::resolve(&config, &cli, Some("hc"), env(&[("POE_NINJA_FORMAT", "csv")])).unwrap();
+        assert_eq!(from_cli.format, OutputFormat::Table);
+        assert_eq!(from_cli.league(Some("Standard")), "Standard");
+
+        assert!(Settings::resolve(&config, &Profile::default(), Some("ssf"), env(&[])).is_err());
+        assert!(Settings::resolve(&config, &Profile::default(), None, env(&[("POE_NINJA_UNIT", "mirror")])).is_err());
+    }
+
+    #[test]
+    fn test_set_and_aliases() {
+        let mut config = Config::default();
+        config.set("league", "Settlers").unwrap();
+        config.set("profiles.ssf.league", "SSF Settlers").unwrap();
+        config.set("profiles.ssf.unit", "Divine").unwrap();
+        config.set("aliases.hh", "Headhunter").unwrap();
+
+        assert_eq!(config.profiles["ssf"].unit, Some(Unit::Divine));
+        assert!(config.set("colour", "red").is_err());
+        assert!(config.set("cache_ttl", "soon").is_err());
+        assert!(config.set("snapshots", "hourly").is_err());
+        assert!(config.set("profiles.ssf", "x").is_err());
+
+        let settings = Settings::resolve(&config, &Profile::default(), None, env(&[])).unwrap();
+        assert_eq!(settings.expand_alias("HH"), "Headhunter");
+        assert_eq!(settings.expand_alias("Mageblood"), "Mageblood");
+    }
+}
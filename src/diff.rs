@@ -0,0 +1,274 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::schema;
+use crate::types::*;
+
+/// One line of a saved overview, ranked by chaos value (1 is the most valuable)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotRow {
+    pub details_id: String,
+    pub name: String,
+    pub chaos_value: f64,
+    pub count: i32,
+    pub rank: usize,
+}
+
+fn ranked(mut rows: Vec<SnapshotRow>) -> Vec<SnapshotRow> {
+    rows.sort_by(|a, b| b.chaos_value.total_cmp(&a.chaos_value));
+    for (index, row) in rows.iter_mut().enumerate() {
+        row.rank = index + 1;
+    }
+    rows
+}
+
+pub fn rows_from_items(items: &[ItemLine]) -> Vec<SnapshotRow> {
+    ranked(
+        items
+            .iter()
+            .map(|item| SnapshotRow {
+                details_id: item.details_id.clone(),
+                name: match &item.variant {
+                    Some(variant) => format!("{} ({})", item.name, variant),
+                    None => item.name.clone(),
+                },
+                chaos_value: item.chaos_value,
+                count: item.count,
+                rank: 0,
+            })
+            .collect(),
+    )
+}
+
+/// Currencies count the receive side, the direction of `chaosEquivalent`
+pub fn rows_from_currencies(currencies: &[CurrencyLine]) -> Vec<SnapshotRow> {
+    ranked(
+        currencies
+            .iter()
+            .map(|currency| SnapshotRow {
+                details_id: currency.details_id.clone(),
+                name: currency.currency_type_name.clone(),
+                chaos_value: currency.chaos_equivalent.unwrap_or(0.0),
+                count: currency.receive.as_ref().map(|receive| receive.count).unwrap_or(0),
+                rank: 0,
+            })
+            .collect(),
+    )
+}
+
+/// Rows of a saved currency or item overview body, told apart by `currencyTypeName`.
+/// Lines that don't match the model are skipped like in any other lenient parse.
+pub fn rows_from_body(body: &str) -> Result<Vec<SnapshotRow>> {
+    let value: serde_json::Value = serde_json::from_str(body).context("Snapshot is not JSON")?;
+    let is_currency = value["lines"]
+        .as_array()
+        .and_then(|lines| lines.first())
+        .is_some_and(|line| line.get("currencyTypeName").is_some());
+
+    if is_currency {
+        let (response, _) = schema::parse_currency_overview(body).context("Failed to parse currency snapshot")?;
+        Ok(rows_from_currencies(&response.lines))
+    } else {
+        let (response, _) = schema::parse_item_overview(body).context("Failed to parse item snapshot")?;
+        Ok(rows_from_items(&response.lines))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffStatus {
+    New,
+    Removed,
+    Changed,
+}
+
+/// A value seen in one snapshot
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Side {
+    pub chaos_value: f64,
+    pub count: i32,
+    pub rank: usize,
+}
+
+impl From<&SnapshotRow> for Side {
+    fn from(row: &SnapshotRow) -> Self {
+        Self {
+            chaos_value: row.chaos_value,
+            count: row.count,
+            rank: row.rank,
+        }
+    }
+}
+
+/// One `details_id` in either snapshot
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiffEntry {
+    pub details_id: String,
+    pub name: String,
+    pub status: DiffStatus,
+    pub before: Option<Side>,
+    pub after: Option<Side>,
+}
+
+impl DiffEntry {
+    fn both(&self) -> Option<(&Side, &Side)> {
+        Some((self.before.as_ref()?, self.after.as_ref()?))
+    }
+
+    pub fn value_change(&self) -> Option<f64> {
+        self.both().map(|(before, after)| after.chaos_value - before.chaos_value)
+    }
+
+    /// `value_change` relative to the earlier value
+    pub fn percent_change(&self) -> Option<f64> {
+        let (before, _) = self.both()?;
+        (before.chaos_value > 0.0).then(|| self.value_change().unwrap_or(0.0) / before.chaos_value * 100.0)
+    }
+
+    pub fn count_change(&self) -> Option<i32> {
+        self.both().map(|(before, after)| after.count - before.count)
+    }
+
+    /// Places gained; positive means the entry moved up the value ranking
+    pub fn rank_change(&self) -> Option<i64> {
+        self.both().map(|(before, after)| before.rank as i64 - after.rank as i64)
+    }
+
+    /// New and removed entries always pass; changed ones need to move past both thresholds
+    /// and at all when no threshold is set
+    pub fn passes(&self, thresholds: &Thresholds) -> bool {
+        if self.status != DiffStatus::Changed {
+            return true;
+        }
+        let change = self.value_change().unwrap_or(0.0).abs();
+        let percent = self.percent_change().map(f64::abs);
+        let moved = change > 0.0 || self.count_change() != Some(0) || self.rank_change() != Some(0);
+
+        moved
+            && thresholds.min_chaos.is_none_or(|min| change >= min)
+            && thresholds.min_percent.is_none_or(|min| percent.is_none_or(|percent| percent >= min))
+    }
+}
+
+/// Hide changed entries that moved less than this
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Thresholds {
+    pub min_chaos: Option<f64>,
+    pub min_percent: Option<f64>,
+}
+
+/// Join two snapshots on `details_id`: new entries first, then removed ones,
+/// then changes by the size of the chaos change
+pub fn diff(before: &[SnapshotRow], after: &[SnapshotRow]) -> Vec<DiffEntry> {
+    let mut joined: BTreeMap<&str, DiffEntry> = BTreeMap::new();
+    for row in before {
+        joined.insert(
+            &row.details_id,
+            DiffEntry {
+                details_id: row.details_id.clone(),
+                name: row.name.clone(),
+                status: DiffStatus::Removed,
+                before: Some(row.into()),
+                after: None,
+            },
+        );
+    }
+    for row in after {
+        let entry = joined.entry(&row.details_id).or_insert_with(|| DiffEntry {
+            details_id: row.details_id.clone(),
+            name: row.name.clone(),
+            status: DiffStatus::New,
+            before: None,
+            after: None,
+        });
+        if entry.before.is_some() {
+            entry.status = DiffStatus::Changed;
+        }
+        entry.after = Some(row.into());
+    }
+
+    let mut entries: Vec<DiffEntry> = joined.into_values().collect();
+    entries.sort_by(|a, b| {
+        a.status.cmp(&b.status).then_with(|| {
+            let a = a.value_change().unwrap_or(0.0).abs();
+            let b = b.value_change().unwrap_or(0.0).abs();
+            b.total_cmp(&a)
+        })
+    });
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(details_id: &str, chaos_value: f64, count: i32) -> SnapshotRow {
+        SnapshotRow {
+            details_id: details_id.to_string(),
+            name: details_id.to_string(),
+            chaos_value,
+            count,
+            rank: 0,
+        }
+    }
+
+    #[test]
+    fn test_diff_joins_on_details_id() {
+        let before = ranked(vec![row("a", 100.0, 10), row("b", 50.0, 5), row("c", 10.0, 1), row("gone", 1.0, 1)]);
+        let after = ranked(vec![row("a", 100.0, 10), row("b", 40.0, 8), row("c", 200.0, 3), row("fresh", 5.0, 2)]);
+
+        let entries = diff(&before, &after);
+        let order: Vec<(&str, DiffStatus)> = entries.iter().map(|e| (e.details_id.as_str(), e.status)).collect();
+        assert_eq!(
+            order,
+            vec![
+                ("fresh", DiffStatus::New),
+                ("gone", DiffStatus::Removed),
+                ("c", DiffStatus::Changed),
+                ("b", DiffStatus::Changed),
+                ("a", DiffStatus::Changed),
+            ]
+        );
+
+        let c = &entries[2];
+        assert_eq!(c.value_change(), Some(190.0));
+        assert_eq!(c.percent_change(), Some(1900.0));
+        assert_eq!(c.count_change(), Some(2));
+        assert_eq!(c.rank_change(), Some(2));
+
+        let b = &entries[3];
+        assert_eq!(b.percent_change(), Some(-20.0));
+        assert_eq!(b.rank_change(), Some(-1));
+    }
+
+    #[test]
+    fn test_thresholds_hide_noise() {
+        let before = ranked(vec![row("a", 100.0, 10), row("b", 50.0, 5), row("c", 10.0, 1)]);
+        let after = ranked(vec![row("a", 100.0, 10), row("b", 49.0, 5), row("c", 20.0, 1), row("d", 1.0, 1)]);
+        let entries = diff(&before, &after);
+
+        let shown = |thresholds: Thresholds| -> Vec<String> {
+            entries
+                .iter()
+                .filter(|entry| entry.passes(&thresholds))
+                .map(|entry| entry.details_id.clone())
+                .collect()
+        };
+        assert_eq!(shown(Thresholds::default()), vec!["d", "c", "b"]);
+        assert_eq!(shown(Thresholds { min_chaos: Some(5.0), min_percent: None }), vec!["d", "c"]);
+        assert_eq!(shown(Thresholds { min_chaos: None, min_percent: Some(50.0) }), vec!["d", "c"]);
+    }
+
+    #[test]
+    fn test_rows_from_saved_bodies() {
+        let items = rows_from_body(include_str!("../tests/fixtures/replay/poe1/itemoverview/standard/UniqueWeapon.json")).unwrap();
+        assert_eq!(items[0].name, "Starforge");
+        assert_eq!(items[0].rank, 1);
+
+        let currencies =
+            rows_from_body(include_str!("../tests/fixtures/replay/poe1/currencyoverview/standard/Currency.json")).unwrap();
+        assert_eq!(currencies[0].name, "Divine Orb");
+        assert_eq!(currencies[1].rank, 2);
+    }
+}
@@ -0,0 +1,384 @@
+use crate::source::PriceSource;
+use crate::pricing::{LoadedBook, PriceBook, PriceQuery, CURRENCY_LIKE_CATEGORIES, UNIQUE_CATEGORIES};
+use crate::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RewardKind {
+    Unique,
+    Currency,
+    Gem,
+    DivinationCard,
+    Other,
+}
+
+/// A divination card reward parsed from its modifier text
+#[derive(Debug, Clone, PartialEq)]
+pub struct DivReward {
+    pub kind: RewardKind,
+    pub name: String,
+    pub quantity: u32,
+    pub links: Option<i32>,
+    pub gem_level: Option<i32>,
+    pub gem_quality: Option<i32>,
+    pub corrupted: bool,
+    pub text: String,
+}
+
+impl DivReward {
+    /// poe.ninja categories the reward could be priced in
+    pub fn categories(&self) -> &'static [&'static str] {
+        match self.kind {
+            RewardKind::Unique => UNIQUE_CATEGORIES,
+            RewardKind::Currency => CURRENCY_LIKE_CATEGORIES,
+            RewardKind::Gem => &["SkillGem"],
+            RewardKind::DivinationCard => &["DivinationCard"],
+            RewardKind::Other => &[],
+        }
+    }
+
+    pub fn query(&self) -> PriceQuery<'_> {
+        let mut query = PriceQuery::named(&self.name);
+        query.links = self.links;
+        if self.kind == RewardKind::Gem {
+            query.gem_level = self.gem_level;
+            query.gem_quality = self.gem_quality;
+            query.corrupted = Some(self.corrupted);
+        }
+        query
+    }
+}
+
+/// Split poe.ninja reward markup like `<size:31>{<uniqueitem>{Headhunter}}`
+/// into (tag, text) pairs, keeping only the innermost segments
+fn markup_segments(text: &str) -> Vec<(String, String)> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let Some(tag_end) = rest.find('>') else { break };
+        let tag = rest[..tag_end].split(':').next().unwrap_or("").to_string();
+        rest = &rest[tag_end + 1..];
+
+        let Some(body) = rest.strip_prefix('{') else { continue };
+        if body.starts_with('<') {
+            // A wrapper such as <size:31>{...}; its contents are tagged again
+            rest = body;
+            continue;
+        }
+
+        let Some(body_end) = body.find('}') else { break };
+        segments.push((tag, body[..body_end].trim().to_string()));
+        rest = &body[body_end + 1..];
+    }
+
+    segments
+}
+
+fn leading_number(text: &str) -> Option<i32> {
+    let digits: String = text
+        .chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+// "Six-Link Kaom's Heart" -> (Some(6), "Kaom's Heart")
+fn strip_links(name: &str) -> (Option<i32>, String) {
+    const LINK_WORDS: &[(&str, i32)] = &[("Six-Link", 6), ("6-Link", 6), ("Five-Link", 5), ("5-Link", 5)];
+    for (word, links) in LINK_WORDS {
+        if let Some(rest) = name.strip_prefix(word) {
+            return (Some(*links), rest.trim().to_string());
+        }
+    }
+    (None, name.to_string())
+}
+
+/// Parse the reward of a divination card from its explicit modifiers
+pub fn parse_reward(modifiers: &[Modifier]) -> Option<DivReward> {
+    let text = modifiers
+        .iter()
+        .map(|modifier| modifier.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let segments = markup_segments(&text);
+
+    let (tag, body) = segments.iter().find(|(tag, _)| {
+        matches!(
+            tag.as_str(),
+            "uniqueitem" | "currencyitem" | "gemitem" | "divination" | "whiteitem"
+                | "magicitem" | "rareitem"
+        )
+    })?;
+
+    let corrupted = segments.iter().any(|(tag, _)| tag == "corrupted");
+    let gem_quality = segments
+        .iter()
+        .position(|(_, body)| body.starts_with("Quality"))
+        .and_then(|index| segments.get(index + 1))
+        .and_then(|(_, value)| leading_number(value));
+
+    let mut reward = DivReward {
+        kind: RewardKind::Other,
+        name: body.clone(),
+        quantity: 1,
+        links: None,
+        gem_level: None,
+        gem_quality,
+        corrupted,
+        text,
+    };
+
+    match tag.as_str() {
+        "uniqueitem" => {
+            let (links, name) = strip_links(body);
+            reward.kind = RewardKind::Unique;
+            reward.links = links;
+            reward.name = name;
+        }
+        "currencyitem" => {
+            reward.kind = RewardKind::Currency;
+            if let Some((count, name)) = body.split_once("x ") {
+                if let Ok(count) = count.trim().parse() {
+                    reward.quantity = count;
+                    reward.name = name.trim().to_string();
+                }
+            }
+        }
+        "gemitem" => {
+            reward.kind = RewardKind::Gem;
+            if let Some(rest) = body.strip_prefix("Level ") {
+                if let Some((level, name)) = rest.split_once(' ') {
+                    reward.gem_level = level.parse().ok();
+                    reward.name = name.trim().to_string();
+                }
+            }
+        }
+        "divination" => {
+            reward.kind = RewardKind::DivinationCard;
+            if let Some((count, name)) = body.split_once("x ") {
+                if let Ok(count) = count.trim().parse() {
+                    reward.quantity = count;
+                    reward.name = name.trim().to_string();
+                }
+            }
+        }
+        _ => {}
+    }
+
+    Some(reward)
+}
+
+/// Cost of a full set of cards against the value of the reward
+#[derive(Debug, Clone)]
+pub struct CardValue {
+    pub card: String,
+    pub stack_size: u32,
+    pub card_chaos: f64,
+    pub set_cost: f64,
+    pub reward: Option<DivReward>,
+    pub reward_value: Option<f64>,
+}
+
+impl CardValue {
+    pub fn profit(&self) -> Option<f64> {
+        self.reward_value.map(|value| value - self.set_cost)
+    }
+
+    pub fn margin(&self) -> Option<f64> {
+        self.profit()
+            .filter(|_| self.set_cost > 0.0)
+            .map(|profit| profit / self.set_cost * 100.0)
+    }
+}
+
+/// Load the card overview plus the categories their rewards need. Each reward is
+/// searched through its categories until it resolves, and categories that fail
+/// to load are skipped.
+pub async fn load_price_book(source: &dyn PriceSource, league: &str, cards: &[ItemLine]) -> LoadedBook {
+    let mut loaded = LoadedBook::default();
+    loaded.ensure(source, league, "Currency").await;
+    loaded.book.insert_items("DivinationCard", cards.to_vec());
+
+    for reward in cards.iter().filter_map(|card| parse_reward(&card.explicit_modifiers)) {
+        let query = reward.query();
+        for category in reward.categories() {
+            if loaded.book.lookup_in(reward.categories(), &query).is_some() {
+                break;
+            }
+            loaded.ensure(source, league, category).await;
+        }
+    }
+
+    loaded
+}
+
+/// Evaluate every card; returns (resolved cards by profit, unresolved cards)
+pub fn evaluate_cards(cards: &[ItemLine], book: &PriceBook) -> (Vec<CardValue>, Vec<CardValue>) {
+    let mut resolved = Vec::new();
+    let mut unresolved = Vec::new();
+
+    for card in cards {
+        let stack_size = card.stack_size.unwrap_or(1).max(1) as u32;
+        let reward = parse_reward(&card.explicit_modifiers);
+        let reward_value = reward.as_ref().and_then(|reward| {
+            book.lookup_in(reward.categories(), &reward.query())
+                .map(|price| price.chaos_value * reward.quantity as f64)
+        });
+
+        let value = CardValue {
+            card: card.name.clone(),
+            stack_size,
+            card_chaos: card.chaos_value,
+            set_cost: card.chaos_value * stack_size as f64,
+            reward,
+            reward_value,
+        };
+
+        if value.reward_value.is_some() {
+            resolved.push(value);
+        } else {
+            unresolved.push(value);
+        }
+    }
+
+    // Resolved sets always have a reward value, so every profit is known
+    resolved.sort_by(|a, b| b.profit().unwrap_or(0.0).total_cmp(&a.profit().unwrap_or(0.0)));
+    (resolved, unresolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modifiers(texts: &[&str]) -> Vec<Modifier> {
+        texts
+            .iter()
+            .map(|text| Modifier { text: text.to_string(), optional: false })
+            .collect()
+    }
+
+    fn line(name: &str, chaos: f64, stack_size: Option<i32>, reward: &[&str]) -> ItemLine {
+        ItemLine {
+            id: 1,
+            name: name.to_string(),
+            stack_size,
+            explicit_modifiers: modifiers(reward),
+            chaos_value: chaos,
+            count: 1,
+            details_id: crate::pricing::slugify(name),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_unique_reward_with_size_wrapper() {
+        let reward = parse_reward(&modifiers(&["<size:31>{<uniqueitem>{Headhunter}}"])).unwrap();
+
+        assert_eq!(reward.kind, RewardKind::Unique);
+        assert_eq!(reward.name, "Headhunter");
+        assert_eq!(reward.quantity, 1);
+        assert!(!reward.corrupted);
+    }
+
+    #[test]
+    fn test_parse_currency_stack() {
+        let reward = parse_reward(&modifiers(&["<currencyitem>{10x Divine Orb}"])).unwrap();
+
+        assert_eq!(reward.kind, RewardKind::Currency);
+        assert_eq!(reward.name, "Divine Orb");
+        assert_eq!(reward.quantity, 10);
+    }
+
+    #[test]
+    fn test_parse_corrupted_gem() {
+        let reward = parse_reward(&modifiers(&[
+            "<gemitem>{Level 4 Enlighten Support}",
+            "<default>{Quality:} <augmented>{+20%}",
+            "<corrupted>{Corrupted}",
+        ]))
+        .unwrap();
+
+        assert_eq!(reward.kind, RewardKind::Gem);
+        assert_eq!(reward.name, "Enlighten Support");
+        assert_eq!(reward.gem_level, Some(4));
+        assert_eq!(reward.gem_quality, Some(20));
+        assert!(reward.corrupted);
+    }
+
+    #[test]
+    fn test_parse_linked_unique_and_generic_item() {
+        let linked = parse_reward(&modifiers(&["<uniqueitem>{Six-Link Kaom's Heart}"])).unwrap();
+        assert_eq!(linked.links, Some(6));
+        assert_eq!(linked.name, "Kaom's Heart");
+
+        let generic = parse_reward(&modifiers(&["<whiteitem>{Six-Link Body Armour}"])).unwrap();
+        assert_eq!(generic.kind, RewardKind::Other);
+        assert!(generic.categories().is_empty());
+
+        assert!(parse_reward(&modifiers(&["plain text"])).is_none());
+    }
+
+    #[test]
+    fn test_evaluate_cards() {
+        let cards = vec![
+            line("The Doctor", 400.0, Some(8), &["<size:31>{<uniqueitem>{Headhunter}}"]),
+            line("Abandoned Wealth", 20.0, Some(5), &["<currencyitem>{3x Exalted Orb}"]),
+            line("The Void", 10.0, Some(1), &["<size:26>{<divination>{Reward Unknown}}"]),
+        ];
+
+        let mut book = PriceBook::new();
+        book.insert_items("UniqueAccessory", vec![line("Headhunter", 4000.0, None, &[])]);
+        book.insert_currencies(
+            "Currency",
+            vec![CurrencyLine {
+                currency_type_name: "Exalted Orb".to_string(),
+                chaos_equivalent: Some(15.0),
+                details_id: "exalted-orb".to_string(),
+                ..Default::default()
+            }],
+        );
+        book.insert_items("DivinationCard", cards.clone());
+
+        let (resolved, unresolved) = evaluate_cards(&cards, &book);
+
+        assert_eq!(resolved.len(), 2);
+        assert_eq!(resolved[0].card, "The Doctor");
+        assert_eq!(resolved[0].set_cost, 3200.0);
+        assert_eq!(resolved[0].profit(), Some(800.0));
+        assert_eq!(resolved[0].margin(), Some(25.0));
+        assert_eq!(resolved[1].profit(), Some(-55.0));
+        assert_eq!(unresolved.len(), 1);
+        assert_eq!(unresolved[0].card, "The Void");
+    }
+    #[tokio::test]
+    async fn test_failed_categories_leave_cards_unresolved() {
+        let cards = vec![
+            line("The Doctor", 400.0, Some(8), &["<size:31>{<uniqueitem>{Headhunter}}"]),
+            line("Abandoned Wealth", 20.0, Some(5), &["<currencyitem>{3x Exalted Orb}"]),
+        ];
+        let source = crate::source::MockSource::new().with_currencies(
+            "Standard",
+            "Currency",
+            CurrencyOverviewResponse {
+                lines: vec![CurrencyLine {
+                    currency_type_name: "Exalted Orb".to_string(),
+                    chaos_equivalent: Some(15.0),
+                    details_id: "exalted-orb".to_string(),
+                    ..Default::default()
+                }],
+                currency_details: vec![],
+            },
+        );
+
+        let loaded = load_price_book(&source, "Standard", &cards).await;
+        let skipped: Vec<&str> = loaded.skipped.iter().map(|(category, _)| category.as_str()).collect();
+        assert_eq!(skipped, UNIQUE_CATEGORIES);
+
+        let (resolved, unresolved) = evaluate_cards(&cards, &loaded.book);
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].card, "Abandoned Wealth");
+        assert_eq!(unresolved[0].card, "The Doctor");
+    }
+}
@@ -0,0 +1,360 @@
+use anyhow::{Context, Result};
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::pricing;
+use crate::source::PriceSource;
+use crate::types::*;
+
+/// Which lines of a category get exported
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct CategoryRule {
+    /// Only export these names (case-insensitive)
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// Only export the N most valuable lines
+    pub top: Option<usize>,
+}
+
+/// Exporter settings, loaded from TOML:
+///
+/// ```toml
+/// leagues = ["Settlers"]
+/// interval = 300
+///
+/// [categories.Currency]
+/// allow = ["Divine Orb", "Exalted Orb"]
+///
+/// [categories.UniqueWeapon]
+/// top = 25
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExporterConfig {
+    pub leagues: Vec<String>,
+    /// Seconds between fetches
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    pub categories: BTreeMap<String, CategoryRule>,
+}
+
+fn default_interval() -> u64 {
+    300
+}
+
+impl ExporterConfig {
+    pub fn parse(content: &str) -> Result<Self> {
+        let config: Self = toml::from_str(content).context("Failed to parse exporter config")?;
+        if config.leagues.is_empty() || config.categories.is_empty() {
+            anyhow::bail!("Exporter config needs at least one league and one category");
+        }
+        Ok(config)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read exporter config '{}'", path.display()))?;
+        Self::parse(&content)
+    }
+}
+
+/// One exported line with the values it has
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub league: String,
+    pub category: String,
+    pub name: String,
+    pub variant: String,
+    pub chaos_value: f64,
+    pub divine_value: Option<f64>,
+    pub count: Option<i32>,
+    pub listing_count: Option<i32>,
+    pub total_change: Option<f64>,
+}
+
+/// Keep allowed lines, then the `top` most valuable
+pub fn apply_rule(mut samples: Vec<Sample>, rule: &CategoryRule) -> Vec<Sample> {
+    if !rule.allow.is_empty() {
+        samples.retain(|sample| rule.allow.iter().any(|name| name.eq_ignore_ascii_case(&sample.name)));
+    }
+    samples.sort_by(|a, b| b.chaos_value.total_cmp(&a.chaos_value));
+    if let Some(top) = rule.top {
+        samples.truncate(top);
+    }
+    samples
+}
+
+pub fn samples_from_currencies(
+    league: &str,
+    category: &str,
+    lines: &[CurrencyLine],
+    divine_rate: Option<f64>,
+) -> Vec<Sample> {
+    lines
+        .iter()
+        .map(|line| {
+            let chaos_value = line.chaos_equivalent.unwrap_or(0.0);
+            Sample {
+                league: league.to_string(),
+                category: category.to_string(),
+                name: line.currency_type_name.clone(),
+                variant: String::new(),
+                chaos_value,
+                divine_value: divine_rate.map(|rate| pricing::chaos_to_divine(chaos_value, rate)),
+                count: line.receive.as_ref().map(|receive| receive.count),
+                listing_count: line.receive.as_ref().and_then(|receive| receive.listing_count),
+                total_change: line.receive_spark_line.total_change,
+            }
+        })
+        .collect()
+}
+
+pub fn samples_from_items(league: &str, category: &str, lines: &[ItemLine]) -> Vec<Sample> {
+    lines
+        .iter()
+        .map(|line| Sample {
+            league: league.to_string(),
+            category: category.to_string(),
+            name: line.name.clone(),
+            variant: line.variant.clone().unwrap_or_default(),
+            chaos_value: line.chaos_value,
+            divine_value: line.divine_value,
+            count: Some(line.count),
+            listing_count: line.listing_count,
+            total_change: line.sparkline.total_change,
+        })
+        .collect()
+}
+
+/// Metric name, help text and the sample value it reports
+type Gauge = (&'static str, &'static str, fn(&Sample) -> Option<f64>);
+
+const GAUGES: [Gauge; 5] = [
+    ("poe_ninja_chaos_value", "Value in chaos orbs", |s| Some(s.chaos_value)),
+    ("poe_ninja_divine_value", "Value in divine orbs", |s| s.divine_value),
+    ("poe_ninja_count", "Number of price samples", |s| s.count.map(f64::from)),
+    ("poe_ninja_listing_count", "Number of trade listings", |s| s.listing_count.map(f64::from)),
+    ("poe_ninja_total_change", "Sparkline change over the last 7 days in percent", |s| s.total_change),
+];
+
+/// Latest samples plus the counters reported on every scrape
+#[derive(Default)]
+pub struct ExporterState {
+    samples: RwLock<Vec<Sample>>,
+    fetches: AtomicU64,
+    fetch_errors: RwLock<BTreeMap<(String, String), u64>>,
+    scrapes: AtomicU64,
+}
+
+impl ExporterState {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Fetch every configured category once. Categories that fail keep their
+    /// previous samples so one upstream error does not blank a dashboard.
+    pub async fn refresh(&self, source: &dyn PriceSource, config: &ExporterConfig) {
+        let mut fresh: Vec<Sample> = Vec::new();
+        let mut failed: Vec<(String, String)> = Vec::new();
+
+        for league in &config.leagues {
+            let divine_rate = source
+                .get_currency_overview(league, "Currency")
+                .await
+                .ok()
+                .and_then(|response| pricing::divine_rate(&response.lines));
+
+            for (category, rule) in &config.categories {
+                self.fetches.fetch_add(1, Ordering::Relaxed);
+                let samples = if crate::get_currency_types().contains(&category.as_str()) {
+                    source
+                        .get_currency_overview(league, category)
+                        .await
+                        .map(|response| samples_from_currencies(league, category, &response.lines, divine_rate))
+                } else {
+                    source
+                        .get_item_overview(league, category)
+                        .await
+                        .map(|response| samples_from_items(league, category, &response.lines))
+                };
+
+                match samples {
+                    Ok(samples) => fresh.extend(apply_rule(samples, rule)),
+                    Err(_) => failed.push((league.clone(), category.clone())),
+                }
+            }
+        }
+
+        let mut samples = self.samples.write().unwrap();
+        let kept: Vec<Sample> = samples
+            .drain(..)
+            .filter(|sample| failed.contains(&(sample.league.clone(), sample.category.clone())))
+            .collect();
+        fresh.extend(kept);
+        *samples = fresh;
+
+        let mut errors = self.fetch_errors.write().unwrap();
+        for key in failed {
+            *errors.entry(key).or_default() += 1;
+        }
+    }
+
+    /// Prometheus text exposition of the current state
+    pub fn render(&self) -> String {
+        let samples = self.samples.read().unwrap();
+        let mut out = String::new();
+
+        for (metric, help, value) in GAUGES {
+            let _ = writeln!(out, "# HELP {} {}", metric, help);
+            let _ = writeln!(out, "# TYPE {} gauge", metric);
+            for sample in samples.iter() {
+                if let Some(value) = value(sample) {
+                    let _ = writeln!(
+                        out,
+                        "{}{{league=\"{}\",category=\"{}\",name=\"{}\",variant=\"{}\"}} {}",
+                        metric,
+                        escape_label(&sample.league),
+                        escape_label(&sample.category),
+                        escape_label(&sample.name),
+                        escape_label(&sample.variant),
+                        value
+                    );
+                }
+            }
+        }
+
+        let _ = writeln!(out, "# HELP poe_ninja_fetches_total Overview fetches attempted");
+        let _ = writeln!(out, "# TYPE poe_ninja_fetches_total counter");
+        let _ = writeln!(out, "poe_ninja_fetches_total {}", self.fetches.load(Ordering::Relaxed));
+
+        let _ = writeln!(out, "# HELP poe_ninja_fetch_errors_total Overview fetches that failed");
+        let _ = writeln!(out, "# TYPE poe_ninja_fetch_errors_total counter");
+        for ((league, category), count) in self.fetch_errors.read().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "poe_ninja_fetch_errors_total{{league=\"{}\",category=\"{}\"}} {}",
+                escape_label(league),
+                escape_label(category),
+                count
+            );
+        }
+
+        let _ = writeln!(out, "# HELP poe_ninja_scrapes_total Times /metrics was served");
+        let _ = writeln!(out, "# TYPE poe_ninja_scrapes_total counter");
+        let _ = writeln!(out, "poe_ninja_scrapes_total {}", self.scrapes.load(Ordering::Relaxed));
+
+        out
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+async fn metrics(State(state): State<Arc<ExporterState>>) -> impl IntoResponse {
+    state.scrapes.fetch_add(1, Ordering::Relaxed);
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.render(),
+    )
+}
+
+pub fn router(state: Arc<ExporterState>) -> Router {
+    Router::new().route("/metrics", get(metrics)).with_state(state)
+}
+
+/// Refresh on the configured interval and serve `/metrics` on the listener
+pub async fn run(
+    listener: tokio::net::TcpListener,
+    source: Arc<dyn PriceSource>,
+    config: ExporterConfig,
+) -> Result<()> {
+    let state = ExporterState::new();
+
+    let refresher = state.clone();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(config.interval.max(1)));
+        loop {
+            ticker.tick().await;
+            refresher.refresh(source.as_ref(), &config).await;
+        }
+    });
+
+    axum::serve(listener, router(state)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(name: &str, chaos_value: f64) -> Sample {
+        Sample {
+            league: "Settlers".to_string(),
+            category: "UniqueWeapon".to_string(),
+            name: name.to_string(),
+            variant: String::new(),
+            chaos_value,
+            divine_value: None,
+            count: Some(3),
+            listing_count: None,
+            total_change: Some(-1.5),
+        }
+    }
+
+    #[test]
+    fn test_config_parsing() {
+        let config = ExporterConfig::parse(
+            "leagues = [\"Settlers\"]\n\n[categories.Currency]\nallow = [\"Divine Orb\"]\n\n[categories.Oil]\ntop = 5\n",
+        )
+        .unwrap();
+
+        assert_eq!(config.interval, 300);
+        assert_eq!(config.categories["Oil"].top, Some(5));
+        assert_eq!(config.categories["Currency"].allow, vec!["Divine Orb"]);
+        assert!(ExporterConfig::parse("leagues = []\n[categories.Oil]\n").is_err());
+    }
+
+    #[test]
+    fn test_apply_rule() {
+        let samples = vec![sample("Cheap", 1.0), sample("Pricey", 100.0), sample("Mid", 10.0)];
+
+        let top = apply_rule(samples.clone(), &CategoryRule { allow: vec![], top: Some(2) });
+        assert_eq!(top.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), vec!["Pricey", "Mid"]);
+
+        let allowed = apply_rule(samples, &CategoryRule { allow: vec!["cheap".to_string()], top: None });
+        assert_eq!(allowed.len(), 1);
+    }
+
+    #[test]
+    fn test_render_format() {
+        let state = ExporterState::new();
+        *state.samples.write().unwrap() = vec![sample("Lioneye's \"Glare\"", 2.5)];
+        state
+            .fetch_errors
+            .write()
+            .unwrap()
+            .insert(("Settlers".to_string(), "Oil".to_string()), 2);
+
+        let text = state.render();
+        assert!(text.contains("# TYPE poe_ninja_chaos_value gauge"));
+        assert!(text.contains(
+            "poe_ninja_chaos_value{league=\"Settlers\",category=\"UniqueWeapon\",name=\"Lioneye's \\\"Glare\\\"\",variant=\"\"} 2.5"
+        ));
+        assert!(text.contains("poe_ninja_total_change{league=\"Settlers\""));
+        assert!(!text.contains("poe_ninja_divine_value{"));
+        assert!(text.contains("poe_ninja_fetch_errors_total{league=\"Settlers\",category=\"Oil\"} 2"));
+    }
+}
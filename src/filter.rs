@@ -0,0 +1,397 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::pricing::PriceBook;
+
+/// Comment tag that marks a block for rewriting, e.g.
+/// `Show # poe-ninja: category=UniqueArmour,UniqueWeapon min=5 by=max`
+pub const MARKER: &str = "poe-ninja:";
+
+/// Prefix used to comment out marked blocks that matched nothing, so the
+/// output can be fed back in and regenerated
+pub const DISABLED_PREFIX: &str = "#~ ";
+
+pub const SUPPORTED_CATEGORIES: &[&str] = &[
+    "UniqueWeapon", "UniqueArmour", "UniqueAccessory", "DivinationCard",
+    "Scarab", "Fossil", "Essence", "Currency",
+];
+
+/// How to value a base type shared by several uniques
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Aggregate {
+    Max,
+    Min,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+pub struct Thresholds {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+/// Named tiers shared by several markers, loaded from TOML:
+///
+/// ```toml
+/// [tiers.t1]
+/// min = 50.0
+///
+/// [tiers.t2]
+/// min = 5.0
+/// max = 50.0
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TierConfig {
+    #[serde(default)]
+    pub tiers: HashMap<String, Thresholds>,
+}
+
+impl TierConfig {
+    pub fn parse(content: &str) -> Result<Self> {
+        toml::from_str(content).context("Failed to parse tier config")
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read tier config '{}'", path.display()))?;
+        Self::parse(&content)
+    }
+}
+
+/// Settings parsed from one marker comment
+#[derive(Debug, Clone, PartialEq)]
+pub struct TierSpec {
+    pub categories: Vec<String>,
+    pub thresholds: Thresholds,
+    pub aggregate: Aggregate,
+}
+
+/// Parse the marker on a block header line, if it has one
+pub fn parse_marker(line: &str, config: &TierConfig) -> Option<Result<TierSpec>> {
+    let (_, comment) = line.split_once('#')?;
+    let settings = comment.trim().strip_prefix(MARKER)?;
+    Some(parse_settings(settings, config))
+}
+
+fn parse_settings(settings: &str, config: &TierConfig) -> Result<TierSpec> {
+    let mut categories = Vec::new();
+    let mut thresholds = Thresholds::default();
+    let mut aggregate = Aggregate::Max;
+
+    // Named tiers apply first so inline min/max can override them
+    let pairs: Vec<(&str, &str)> = settings
+        .split_whitespace()
+        .map(|pair| {
+            pair.split_once('=')
+                .ok_or_else(|| anyhow!("Expected key=value in marker, found '{}'", pair))
+        })
+        .collect::<Result<_>>()?;
+
+    if let Some((_, tier)) = pairs.iter().find(|(key, _)| *key == "tier") {
+        thresholds = *config
+            .tiers
+            .get(*tier)
+            .ok_or_else(|| anyhow!("Unknown tier '{}'", tier))?;
+    }
+
+    for (key, value) in pairs {
+        match key {
+            "category" => {
+                for category in value.split(',') {
+                    if !SUPPORTED_CATEGORIES.contains(&category) {
+                        bail!("Unsupported filter category '{}'", category);
+                    }
+                    categories.push(category.to_string());
+                }
+            }
+            "min" => thresholds.min = Some(value.parse().context("Invalid min threshold")?),
+            "max" => thresholds.max = Some(value.parse().context("Invalid max threshold")?),
+            "by" => {
+                aggregate = match value {
+                    "max" => Aggregate::Max,
+                    "min" => Aggregate::Min,
+                    other => bail!("Unknown aggregate '{}', expected max or min", other),
+                }
+            }
+            "tier" => {}
+            other => bail!("Unknown marker setting '{}'", other),
+        }
+    }
+
+    if categories.is_empty() {
+        bail!("Marker needs at least one category");
+    }
+
+    Ok(TierSpec {
+        categories,
+        thresholds,
+        aggregate,
+    })
+}
+
+/// Chaos value per filter BaseType for the spec's categories. Uniques are
+/// keyed by base type; everything else by its own name.
+pub fn base_values(book: &PriceBook, spec: &TierSpec) -> BTreeMap<String, f64> {
+    let mut values: BTreeMap<String, f64> = BTreeMap::new();
+    let mut add = |base: &str, value: f64| {
+        values
+            .entry(base.to_string())
+            .and_modify(|current| {
+                *current = match spec.aggregate {
+                    Aggregate::Max => current.max(value),
+                    Aggregate::Min => current.min(value),
+                }
+            })
+            .or_insert(value);
+    };
+
+    for category in &spec.categories {
+        if let Some(currencies) = book.currencies(category) {
+            for currency in currencies {
+                add(&currency.currency_type_name, currency.chaos_equivalent.unwrap_or(0.0));
+            }
+        }
+        if let Some(items) = book.items(category) {
+            for item in items {
+                // Five and six link variants would skew shared bases
+                if item.links.is_some() {
+                    continue;
+                }
+                if category.starts_with("Unique") {
+                    if let Some(base) = &item.base_type {
+                        add(base, item.chaos_value);
+                    }
+                } else {
+                    add(&item.name, item.chaos_value);
+                }
+            }
+        }
+    }
+
+    values
+}
+
+/// Base types whose value falls inside the spec's thresholds, sorted by name
+pub fn select_bases(values: &BTreeMap<String, f64>, thresholds: &Thresholds) -> Vec<String> {
+    values
+        .iter()
+        .filter(|(_, value)| thresholds.min.is_none_or(|min| **value >= min))
+        .filter(|(_, value)| thresholds.max.is_none_or(|max| **value < max))
+        .map(|(base, _)| base.clone())
+        .collect()
+}
+
+fn is_block_header(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    ["Show", "Hide", "Minimal"]
+        .iter()
+        .any(|keyword| trimmed.starts_with(keyword))
+}
+
+fn is_disabled_marked_header(line: &str) -> bool {
+    line.strip_prefix(DISABLED_PREFIX).is_some_and(|header| {
+        is_block_header(header)
+            && header
+                .split_once('#')
+                .is_some_and(|(_, comment)| comment.trim().starts_with(MARKER))
+    })
+}
+
+/// Lines of the filter with any previously disabled blocks restored.
+/// Only blocks whose header carries a marker are un-commented, so `#~`
+/// lines written by hand elsewhere in the filter are left alone.
+fn restore_disabled(filter: &str) -> Vec<String> {
+    let mut restoring = false;
+    filter
+        .lines()
+        .map(|line| {
+            if is_disabled_marked_header(line) {
+                restoring = true;
+            } else if !line.trim().is_empty() {
+                // A disabled block runs until the next header or uncommented line
+                restoring &= line
+                    .strip_prefix(DISABLED_PREFIX)
+                    .is_some_and(|rest| !is_block_header(rest));
+            }
+            match line.strip_prefix(DISABLED_PREFIX) {
+                Some(restored) if restoring => restored.to_string(),
+                _ => line.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Split the filter into (preamble, blocks); each block starts at a header line
+fn split_blocks(lines: Vec<String>) -> (Vec<String>, Vec<Vec<String>>) {
+    let mut preamble = Vec::new();
+    let mut blocks: Vec<Vec<String>> = Vec::new();
+
+    for line in lines {
+        if is_block_header(&line) {
+            blocks.push(vec![line]);
+        } else if let Some(block) = blocks.last_mut() {
+            block.push(line);
+        } else {
+            preamble.push(line);
+        }
+    }
+
+    (preamble, blocks)
+}
+
+/// Every category referenced by a marker in the filter
+pub fn required_categories(filter: &str, config: &TierConfig) -> Result<Vec<String>> {
+    let mut categories = Vec::new();
+    for (index, line) in restore_disabled(filter).iter().enumerate() {
+        if !is_block_header(line) {
+            continue;
+        }
+        if let Some(spec) = parse_marker(line, config) {
+            let spec = spec.with_context(|| format!("Invalid marker on line {}", index + 1))?;
+            for category in spec.categories {
+                if !categories.contains(&category) {
+                    categories.push(category);
+                }
+            }
+        }
+    }
+    Ok(categories)
+}
+
+fn rewrite_block(block: &mut Vec<String>, bases: &[String]) {
+    let quoted = bases
+        .iter()
+        .map(|base| format!("\"{}\"", base))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let existing = block
+        .iter()
+        .position(|line| line.trim_start().starts_with("BaseType"));
+
+    match existing {
+        Some(index) => {
+            let line = &block[index];
+            let indent = &line[..line.len() - line.trim_start().len()];
+            block[index] = format!("{}BaseType == {}", indent, quoted);
+        }
+        None => {
+            let indent = block
+                .get(1)
+                .map(|line| line[..line.len() - line.trim_start().len()].to_string())
+                .filter(|indent| !indent.is_empty())
+                .unwrap_or_else(|| "    ".to_string());
+            block.insert(1, format!("{}BaseType == {}", indent, quoted));
+        }
+    }
+}
+
+/// What `rewrite_with_summary` did to one marked block
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockSummary {
+    pub header: String,
+    /// Bases written to the block; zero means it was commented out
+    pub bases: usize,
+}
+
+/// Rewrite the BaseType list of every marked block from live prices.
+/// Blocks that end up with no bases are commented out so the filter stays valid.
+pub fn rewrite(filter: &str, book: &PriceBook, config: &TierConfig) -> Result<String> {
+    rewrite_with_summary(filter, book, config).map(|(text, _)| text)
+}
+
+/// `rewrite`, plus a summary of every marked block
+pub fn rewrite_with_summary(filter: &str, book: &PriceBook, config: &TierConfig) -> Result<(String, Vec<BlockSummary>)> {
+    let (preamble, mut blocks) = split_blocks(restore_disabled(filter));
+    let mut summary = Vec::new();
+
+    for block in &mut blocks {
+        let Some(spec) = parse_marker(&block[0], config) else { continue };
+        let spec = spec.with_context(|| format!("Invalid marker: {}", block[0].trim()))?;
+
+        let bases = select_bases(&base_values(book, &spec), &spec.thresholds);
+        summary.push(BlockSummary {
+            header: block[0].trim().to_string(),
+            bases: bases.len(),
+        });
+        if bases.is_empty() {
+            for line in block.iter_mut().filter(|line| !line.trim().is_empty()) {
+                *line = format!("{}{}", DISABLED_PREFIX, line);
+            }
+        } else {
+            rewrite_block(block, &bases);
+        }
+    }
+
+    let mut output = preamble;
+    output.extend(blocks.into_iter().flatten());
+    let mut text = output.join("\n");
+    if filter.ends_with('\n') {
+        text.push('\n');
+    }
+    Ok((text, summary))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_marker() {
+        let config = TierConfig::parse("[tiers.t1]\nmin = 50.0\nmax = 500.0\n").unwrap();
+
+        let spec = parse_marker(
+            "Show # poe-ninja: category=UniqueArmour,UniqueWeapon tier=t1 max=100 by=min",
+            &config,
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(spec.categories, vec!["UniqueArmour", "UniqueWeapon"]);
+        assert_eq!(spec.thresholds.min, Some(50.0));
+        assert_eq!(spec.thresholds.max, Some(100.0));
+        assert_eq!(spec.aggregate, Aggregate::Min);
+
+        assert!(parse_marker("Show # just a comment", &config).is_none());
+        assert!(parse_marker("Show", &config).is_none());
+    }
+
+    #[test]
+    fn test_invalid_markers() {
+        let config = TierConfig::default();
+
+        for line in [
+            "Show # poe-ninja: min=5",
+            "Show # poe-ninja: category=Beast",
+            "Show # poe-ninja: category=Scarab tier=missing",
+            "Show # poe-ninja: category=Scarab by=avg",
+            "Show # poe-ninja: category=Scarab oops",
+        ] {
+            assert!(parse_marker(line, &config).unwrap().is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_select_bases_thresholds() {
+        let values: BTreeMap<String, f64> = [("A", 1.0), ("B", 5.0), ("C", 50.0)]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+
+        let mid = Thresholds { min: Some(5.0), max: Some(50.0) };
+        assert_eq!(select_bases(&values, &mid), vec!["B"]);
+        assert_eq!(select_bases(&values, &Thresholds::default()).len(), 3);
+    }
+
+    #[test]
+    fn test_required_categories() {
+        let filter = "Show # poe-ninja: category=Scarab min=1\n\
+                      #~ Show # poe-ninja: category=Fossil,Scarab min=1\n\
+                      Hide\n";
+
+        let categories = required_categories(filter, &TierConfig::default()).unwrap();
+        assert_eq!(categories, vec!["Scarab", "Fossil"]);
+    }
+}
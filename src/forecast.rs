@@ -0,0 +1,297 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::stats::has_data;
+use crate::types::*;
+
+/// Two-sided 95% band
+const Z_95: f64 = 1.96;
+
+/// Smoothing factor of the level (EMA and Holt)
+const ALPHA: f64 = 0.5;
+/// Smoothing factor of the trend (EMA drift and Holt)
+const BETA: f64 = 0.3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Model {
+    /// Least squares line through the whole series
+    #[default]
+    Linear,
+    /// Exponentially weighted level plus an exponentially weighted daily drift
+    Ema,
+    /// Holt's linear smoothing of level and trend
+    Holt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Trend {
+    Up,
+    Down,
+    Flat,
+}
+
+/// Projected value `day` days after the last point, with its 95% band
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProjectedDay {
+    pub day: usize,
+    pub value: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Forecast {
+    pub model: Model,
+    pub points: usize,
+    pub last: f64,
+    /// Chaos per day at the end of the series
+    pub slope: f64,
+    pub projection: Vec<ProjectedDay>,
+    /// Change from `last` to the final projected day, in percent
+    pub change_percent: f64,
+    pub trend: Trend,
+}
+
+/// Daily chaos values from a sparkline, whose points are percent changes since its first day.
+/// Missing days between known points are interpolated so the days stay evenly spaced;
+/// missing days at either end are left out. Empty when the last known point is -100%,
+/// which leaves nothing to scale `current` by.
+pub fn series_from_sparkline(current: f64, sparkline: &SparkLine) -> Vec<f64> {
+    let known: Vec<(usize, f64)> = sparkline
+        .data
+        .iter()
+        .flatten()
+        .enumerate()
+        .filter_map(|(day, change)| change.map(|change| (day, change)))
+        .collect();
+    let Some(&(_, last)) = known.last() else {
+        return Vec::new();
+    };
+    let scale = 1.0 + last / 100.0;
+    if scale <= 0.0 {
+        return Vec::new();
+    }
+    let base = current / scale;
+
+    let mut changes = vec![known[0].1];
+    for pair in known.windows(2) {
+        let ((from_day, from), (to_day, to)) = (pair[0], pair[1]);
+        let span = (to_day - from_day) as f64;
+        changes.extend((1..=to_day - from_day).map(|step| from + (to - from) * step as f64 / span));
+    }
+    changes.iter().map(|change| base * (1.0 + change / 100.0)).collect()
+}
+
+/// The regular sparkline, or the low confidence one when poe.ninja left it empty
+pub fn pick_sparkline<'a>(sparkline: &'a SparkLine, low_confidence: &'a SparkLine) -> &'a SparkLine {
+    if has_data(sparkline) {
+        sparkline
+    } else {
+        low_confidence
+    }
+}
+
+/// Daily chaos values from a history, oldest first
+pub fn series_from_history(history: &[HistoryPoint]) -> Vec<f64> {
+    let mut points: Vec<&HistoryPoint> = history.iter().collect();
+    points.sort_by_key(|point| std::cmp::Reverse(point.days_ago));
+    points.iter().map(|point| point.value).collect()
+}
+
+/// Ends of the fit: the value at the last point and the per-day slope there,
+/// plus the half-width of the band a given number of days ahead
+struct Fit {
+    level: f64,
+    slope: f64,
+    spread: Box<dyn Fn(usize) -> f64>,
+}
+
+fn linear(series: &[f64]) -> Fit {
+    let n = series.len() as f64;
+    let mean_x = (n - 1.0) / 2.0;
+    let mean_y = series.iter().sum::<f64>() / n;
+    let sxx: f64 = (0..series.len()).map(|x| (x as f64 - mean_x).powi(2)).sum();
+    let sxy: f64 = series
+        .iter()
+        .enumerate()
+        .map(|(x, y)| (x as f64 - mean_x) * (y - mean_y))
+        .sum();
+    let slope = sxy / sxx;
+    let intercept = mean_y - slope * mean_x;
+
+    let sse: f64 = series
+        .iter()
+        .enumerate()
+        .map(|(x, y)| (y - (intercept + slope * x as f64)).powi(2))
+        .sum();
+    let sd = (sse / (n - 2.0)).sqrt();
+    let last_x = n - 1.0;
+
+    Fit {
+        level: intercept + slope * last_x,
+        slope,
+        spread: Box::new(move |day| {
+            let x = last_x + day as f64;
+            Z_95 * sd * (1.0 + 1.0 / n + (x - mean_x).powi(2) / sxx).sqrt()
+        }),
+    }
+}
+
+/// Smooth level and trend; `drift_from_values` picks the EMA drift over raw
+/// daily differences instead of Holt's differences of the smoothed level
+fn smoothed(series: &[f64], drift_from_values: bool) -> Fit {
+    let mut level = series[0];
+    let mut slope = series[1] - series[0];
+    let mut errors = Vec::with_capacity(series.len() - 1);
+
+    for window in series.windows(2) {
+        let (previous, value) = (window[0], window[1]);
+        errors.push(value - (level + slope));
+        let previous_level = level;
+        level = ALPHA * value + (1.0 - ALPHA) * (level + slope);
+        let step = if drift_from_values { value - previous } else { level - previous_level };
+        slope = BETA * step + (1.0 - BETA) * slope;
+    }
+
+    let sd = (errors.iter().map(|error| error.powi(2)).sum::<f64>() / errors.len() as f64).sqrt();
+    Fit {
+        level,
+        slope,
+        spread: Box::new(move |day| Z_95 * sd * (day as f64).sqrt()),
+    }
+}
+
+/// Fit `model` to a daily series and project it `days` ahead. The item is trending
+/// when the projected change reaches `threshold` percent and the band excludes today's value.
+pub fn forecast(series: &[f64], model: Model, days: usize, threshold: f64) -> Result<Forecast> {
+    if series.len() < 3 {
+        anyhow::bail!("Need at least 3 daily values to forecast, got {}", series.len());
+    }
+    if days == 0 {
+        anyhow::bail!("Need at least one day to project");
+    }
+
+    let fit = match model {
+        Model::Linear => linear(series),
+        Model::Ema => smoothed(series, true),
+        Model::Holt => smoothed(series, false),
+    };
+    let last = series[series.len() - 1];
+
+    let projection: Vec<ProjectedDay> = (1..=days)
+        .map(|day| {
+            let value = fit.level + fit.slope * day as f64;
+            let spread = (fit.spread)(day);
+            ProjectedDay {
+                day,
+                value,
+                low: (value - spread).max(0.0),
+                high: value + spread,
+            }
+        })
+        .collect();
+
+    let end = projection.last().expect("at least one projected day");
+    let change_percent = if last > 0.0 { (end.value - last) / last * 100.0 } else { 0.0 };
+    let trend = if change_percent >= threshold && end.low > last {
+        Trend::Up
+    } else if change_percent <= -threshold && end.high < last {
+        Trend::Down
+    } else {
+        Trend::Flat
+    };
+
+    Ok(Forecast {
+        model,
+        points: series.len(),
+        last,
+        slope: fit.slope,
+        change_percent,
+        trend,
+        projection,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    /// 100, 102, 104, ... with a deterministic wobble of +-0.5
+    fn rising(days: usize) -> Vec<f64> {
+        (0..days)
+            .map(|day| 100.0 + 2.0 * day as f64 + if day % 2 == 0 { 0.5 } else { -0.5 })
+            .collect()
+    }
+
+    #[test]
+    fn test_linear_recovers_exact_line() {
+        let series: Vec<f64> = (0..10).map(|day| 50.0 - 1.5 * day as f64).collect();
+        let result = forecast(&series, Model::Linear, 4, 5.0).unwrap();
+
+        assert!(close(result.slope, -1.5));
+        assert!(close(result.projection[3].value, 50.0 - 1.5 * 13.0));
+        assert!(close(result.projection[3].low, result.projection[3].high));
+        assert_eq!(result.trend, Trend::Down);
+    }
+
+    #[test]
+    fn test_models_follow_a_noisy_rise() {
+        let series = rising(14);
+        for model in [Model::Linear, Model::Ema, Model::Holt] {
+            let result = forecast(&series, model, 7, 5.0).unwrap();
+            assert!(result.slope > 1.5 && result.slope < 2.5, "{:?}: {}", model, result.slope);
+            assert_eq!(result.trend, Trend::Up, "{:?}", model);
+
+            let bands: Vec<f64> = result.projection.iter().map(|day| day.high - day.low).collect();
+            assert!(bands.windows(2).all(|pair| pair[1] >= pair[0]), "{:?}: {:?}", model, bands);
+        }
+    }
+
+    #[test]
+    fn test_noise_alone_is_flat() {
+        let series: Vec<f64> = (0..14).map(|day| if day % 2 == 0 { 101.0 } else { 99.0 }).collect();
+        for model in [Model::Linear, Model::Ema, Model::Holt] {
+            assert_eq!(forecast(&series, model, 7, 5.0).unwrap().trend, Trend::Flat, "{:?}", model);
+        }
+        assert!(forecast(&series[..2], Model::Linear, 7, 5.0).is_err());
+        assert!(forecast(&series, Model::Linear, 0, 5.0).is_err());
+    }
+
+    #[test]
+    fn test_series_sources() {
+        let sparkline = SparkLine {
+            data: Some(vec![Some(0.0), None, Some(10.0), Some(25.0)]),
+            total_change: Some(25.0),
+        };
+        let series = series_from_sparkline(250.0, &sparkline);
+        assert_eq!(series.len(), 4);
+        assert!(close(series[0], 200.0) && close(series[1], 210.0));
+        assert!(close(series[2], 220.0) && close(series[3], 250.0));
+
+        let edges = SparkLine {
+            data: Some(vec![None, Some(0.0), Some(50.0), None]),
+            total_change: None,
+        };
+        assert_eq!(series_from_sparkline(150.0, &edges).len(), 2);
+
+        let wiped = SparkLine {
+            data: Some(vec![Some(0.0), Some(-50.0), Some(-100.0)]),
+            total_change: Some(-100.0),
+        };
+        assert!(series_from_sparkline(0.0, &wiped).is_empty());
+
+        let history = vec![
+            HistoryPoint { count: 1, value: 3.0, days_ago: 0 },
+            HistoryPoint { count: 1, value: 1.0, days_ago: 2 },
+            HistoryPoint { count: 1, value: 2.0, days_ago: 1 },
+        ];
+        assert_eq!(series_from_history(&history), vec![1.0, 2.0, 3.0]);
+    }
+}
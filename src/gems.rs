@@ -0,0 +1,345 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::types::*;
+
+/// One priced level/quality/corruption combination of a gem
+#[derive(Debug, Clone, PartialEq)]
+pub struct GemVariant {
+    pub level: i32,
+    pub quality: i32,
+    pub corrupted: bool,
+    pub chaos_value: f64,
+}
+
+/// All priced variants of one gem
+#[derive(Debug, Clone)]
+pub struct GemGroup {
+    pub name: String,
+    pub variants: Vec<GemVariant>,
+}
+
+impl GemGroup {
+    pub fn price(&self, level: i32, quality: i32, corrupted: bool) -> Option<f64> {
+        self.variants
+            .iter()
+            .find(|v| v.level == level && v.quality == quality && v.corrupted == corrupted)
+            .map(|v| v.chaos_value)
+    }
+
+    /// Highest level reachable without corruption
+    pub fn max_level(&self) -> i32 {
+        max_gem_level(&self.name)
+    }
+}
+
+pub fn max_gem_level(name: &str) -> i32 {
+    if name.starts_with("Awakened ") {
+        5
+    } else if matches!(name, "Enlighten Support" | "Empower Support" | "Enhance Support") {
+        3
+    } else {
+        20
+    }
+}
+
+/// Group SkillGem lines by gem name
+pub fn group_gems(lines: &[ItemLine]) -> Vec<GemGroup> {
+    let mut groups: BTreeMap<String, Vec<GemVariant>> = BTreeMap::new();
+
+    for line in lines {
+        let Some(level) = line.gem_level else { continue };
+        groups.entry(line.name.clone()).or_default().push(GemVariant {
+            level,
+            quality: line.gem_quality.unwrap_or(0),
+            corrupted: line.corrupted.unwrap_or(false),
+            chaos_value: line.chaos_value,
+        });
+    }
+
+    groups
+        .into_iter()
+        .map(|(name, variants)| GemGroup { name, variants })
+        .collect()
+}
+
+/// One possible result of using a Vaal Orb on a gem
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CorruptionOutcome {
+    #[serde(default)]
+    pub level: i32,
+    #[serde(default)]
+    pub quality: i32,
+    pub probability: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct OutcomeTable {
+    #[serde(rename = "outcome")]
+    pub outcomes: Vec<CorruptionOutcome>,
+}
+
+impl Default for OutcomeTable {
+    /// Half of all corruptions leave level and quality alone; the rest
+    /// split evenly between +/-1 level and +/-3 quality
+    fn default() -> Self {
+        let outcome = |level, quality, probability| CorruptionOutcome { level, quality, probability };
+        Self {
+            outcomes: vec![
+                outcome(0, 0, 0.5),
+                outcome(1, 0, 0.125),
+                outcome(-1, 0, 0.125),
+                outcome(0, 3, 0.125),
+                outcome(0, -3, 0.125),
+            ],
+        }
+    }
+}
+
+impl OutcomeTable {
+    pub fn parse(content: &str) -> Result<Self> {
+        let table: Self = toml::from_str(content).context("Failed to parse outcome table")?;
+        let total: f64 = table.outcomes.iter().map(|o| o.probability).sum();
+        if (total - 1.0).abs() > 1e-6 {
+            anyhow::bail!("Outcome probabilities sum to {:.3}, expected 1.0", total);
+        }
+        Ok(table)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read outcome table '{}'", path.display()))?;
+        Self::parse(&content)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    Level,
+    Quality,
+    Vaal,
+}
+
+impl std::fmt::Display for Strategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Strategy::Level => write!(f, "Level"),
+            Strategy::Quality => write!(f, "Quality"),
+            Strategy::Vaal => write!(f, "Vaal"),
+        }
+    }
+}
+
+/// Cost and resulting value of one way of upgrading a gem
+#[derive(Debug, Clone, PartialEq)]
+pub struct GemOpportunity {
+    pub name: String,
+    pub strategy: Strategy,
+    pub from: String,
+    pub to: String,
+    pub cost: f64,
+    pub value: f64,
+}
+
+impl GemOpportunity {
+    pub fn profit(&self) -> f64 {
+        self.value - self.cost
+    }
+
+    pub fn margin(&self) -> f64 {
+        if self.cost > 0.0 {
+            self.profit() / self.cost * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+fn label(level: i32, quality: i32, corrupted: bool) -> String {
+    format!("{}/{}{}", level, quality, if corrupted { "c" } else { "" })
+}
+
+/// Expected value of corrupting a max level 20% quality gem. Outcomes
+/// poe.ninja has no price for fall back to the plain corrupted variant.
+pub fn vaal_expected_value(group: &GemGroup, table: &OutcomeTable) -> Option<f64> {
+    let max = group.max_level();
+    let fallback = group.price(max, 20, true)?;
+
+    Some(
+        table
+            .outcomes
+            .iter()
+            .map(|outcome| {
+                let value = group
+                    .price(max + outcome.level, 20 + outcome.quality, true)
+                    .unwrap_or(fallback);
+                value * outcome.probability
+            })
+            .sum(),
+    )
+}
+
+/// Every upgrade path of a gem that poe.ninja has prices for
+pub fn opportunities(group: &GemGroup, table: &OutcomeTable) -> Vec<GemOpportunity> {
+    let max = group.max_level();
+    let mut found = Vec::new();
+
+    if let (Some(cost), Some(value)) = (group.price(1, 0, false), group.price(max, 0, false)) {
+        found.push(GemOpportunity {
+            name: group.name.clone(),
+            strategy: Strategy::Level,
+            from: label(1, 0, false),
+            to: label(max, 0, false),
+            cost,
+            value,
+        });
+    }
+
+    if let (Some(cost), Some(value)) = (group.price(max, 0, false), group.price(max, 20, false)) {
+        found.push(GemOpportunity {
+            name: group.name.clone(),
+            strategy: Strategy::Quality,
+            from: label(max, 0, false),
+            to: label(max, 20, false),
+            cost,
+            value,
+        });
+    }
+
+    if let (Some(cost), Some(value)) = (group.price(max, 20, false), vaal_expected_value(group, table)) {
+        found.push(GemOpportunity {
+            name: group.name.clone(),
+            strategy: Strategy::Vaal,
+            from: label(max, 20, false),
+            to: format!("EV {}", label(max, 20, true)),
+            cost,
+            value,
+        });
+    }
+
+    found
+}
+
+pub fn sort_by_profit(mut found: Vec<GemOpportunity>) -> Vec<GemOpportunity> {
+    found.sort_by(|a, b| b.profit().total_cmp(&a.profit()));
+    found
+}
+
+pub fn sort_by_margin(mut found: Vec<GemOpportunity>) -> Vec<GemOpportunity> {
+    found.sort_by(|a, b| b.margin().total_cmp(&a.margin()));
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gem(name: &str, level: i32, quality: Option<i32>, corrupted: bool, chaos: f64) -> ItemLine {
+        ItemLine {
+            id: 1,
+            name: name.to_string(),
+            corrupted: Some(corrupted),
+            gem_level: Some(level),
+            gem_quality: quality,
+            chaos_value: chaos,
+            count: 1,
+            details_id: crate::pricing::slugify(name),
+            ..Default::default()
+        }
+    }
+
+    fn sample_lines() -> Vec<ItemLine> {
+        vec![
+            gem("Cleave of Rage", 1, None, false, 2.0),
+            gem("Cleave of Rage", 20, None, false, 12.0),
+            gem("Cleave of Rage", 20, Some(20), false, 20.0),
+            gem("Cleave of Rage", 20, Some(20), true, 10.0),
+            gem("Cleave of Rage", 21, Some(20), true, 100.0),
+            gem("Awakened Added Fire Damage Support", 1, None, false, 5.0),
+            gem("Awakened Added Fire Damage Support", 5, None, false, 45.0),
+            gem("No Level Gem", 0, None, false, 1.0),
+        ]
+    }
+
+    #[test]
+    fn test_group_gems_and_max_level() {
+        let groups = group_gems(&sample_lines());
+
+        assert_eq!(groups.len(), 3);
+        let awakened = &groups[0];
+        assert_eq!(awakened.name, "Awakened Added Fire Damage Support");
+        assert_eq!(awakened.max_level(), 5);
+        assert_eq!(groups[1].max_level(), 20);
+        assert_eq!(groups[1].price(21, 20, true), Some(100.0));
+        assert_eq!(max_gem_level("Enlighten Support"), 3);
+    }
+
+    #[test]
+    fn test_opportunities() {
+        let groups = group_gems(&sample_lines());
+        let cleave = groups.iter().find(|g| g.name == "Cleave of Rage").unwrap();
+
+        let found = opportunities(cleave, &OutcomeTable::default());
+        assert_eq!(found.len(), 3);
+
+        assert_eq!(found[0].strategy, Strategy::Level);
+        assert_eq!(found[0].profit(), 10.0);
+        assert_eq!(found[0].margin(), 500.0);
+
+        assert_eq!(found[1].strategy, Strategy::Quality);
+        assert_eq!(found[1].profit(), 8.0);
+
+        // 12.5% chance of 21/20c at 100c, everything else falls back to 10c
+        assert_eq!(found[2].strategy, Strategy::Vaal);
+        assert!((found[2].value - (0.125 * 100.0 + 0.875 * 10.0)).abs() < 1e-9);
+        assert_eq!(found[2].to, "EV 20/20c");
+    }
+
+    #[test]
+    fn test_awakened_gem_levels_to_five() {
+        let groups = group_gems(&sample_lines());
+        let found = opportunities(&groups[0], &OutcomeTable::default());
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].to, "5/0");
+        assert_eq!(found[0].profit(), 40.0);
+    }
+
+    #[test]
+    fn test_sorting() {
+        let groups = group_gems(&sample_lines());
+        let found: Vec<_> = groups
+            .iter()
+            .flat_map(|g| opportunities(g, &OutcomeTable::default()))
+            .collect();
+
+        let by_profit = sort_by_profit(found.clone());
+        assert_eq!(by_profit[0].name, "Awakened Added Fire Damage Support");
+
+        let by_margin = sort_by_margin(found);
+        assert_eq!(by_margin[0].strategy, Strategy::Level);
+        assert_eq!(by_margin[0].name, "Awakened Added Fire Damage Support");
+    }
+
+    #[test]
+    fn test_outcome_table_parsing() {
+        let content = r#"
+            [[outcome]]
+            probability = 0.75
+
+            [[outcome]]
+            level = 1
+            quality = 3
+            probability = 0.25
+        "#;
+
+        let table = OutcomeTable::parse(content).unwrap();
+        assert_eq!(table.outcomes.len(), 2);
+        assert_eq!(table.outcomes[1].level, 1);
+
+        let bad = "[[outcome]]\nprobability = 0.5\n";
+        assert!(OutcomeTable::parse(bad).is_err());
+    }
+}
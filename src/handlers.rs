@@ -0,0 +1,2584 @@
+use anyhow::{anyhow, Context, Result};
+use colored::*;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use serde::Serialize;
+use tabled::settings::{location::ByColumnName, Disable, Span, Style};
+use tabled::{builder::Builder, Table, Tabled};
+
+use crate::api::{search_types, warn_or_fail, RequestKey, WriteWarning};
+use crate::config::{Config, Output, OutputFormat, Profile, Settings, Unit};
+use crate::snapshots::SnapshotStore;
+use crate::source::PriceSource;
+use crate::{
+    anomalies, bases, builds, compare, diff, divcards, exporter, filter, forecast, gems, mods, portfolio, pricing, schema, server,
+    snapshots, stash, stats, top, trade, watchlist,
+};
+use crate::{
+    filter_currencies_by_name, filter_exchange_lines, filter_items_by_criteria, find_currency_by_name,
+    find_item_by_name, get_currency_types, get_item_types, get_leagues_for, get_poe2_exchange_types,
+    sort_currencies_by_value, sort_items_by_value, ConfigAction, Game, WatchlistAction,
+};
+
+pub async fn handle_currency(
+    source: &dyn PriceSource,
+    league: &str,
+    currency_type: &str,
+    name_filter: Option<&str>,
+    trade_link: bool,
+    output: &Output,
+) -> Result<()> {
+    write_currency(&mut std::io::stdout(), source, league, currency_type, name_filter, trade_link, output).await
+}
+
+/// `handle_currency`, writing to `out`
+pub async fn write_currency(
+    out: &mut (impl Write + Send),
+    source: &dyn PriceSource,
+    league: &str,
+    currency_type: &str,
+    name_filter: Option<&str>,
+    trade_link: bool,
+    output: &Output,
+) -> Result<()> {
+    if output.format == OutputFormat::Table {
+        writeln!(
+            out,
+            "{} {}",
+            "Fetching currency data for".bright_blue(),
+            format!("{} - {}", league, currency_type).bright_yellow()
+        )?;
+    }
+
+    let response = source
+        .get_currency_overview(league, currency_type)
+        .await
+        .context("Failed to fetch currency data")?;
+
+    let divine_rate = pricing::divine_rate(&response.lines);
+    let filtered_currencies = filter_currencies_by_name(response.lines, name_filter);
+    let sorted_currencies = sort_currencies_by_value(filtered_currencies);
+
+    let links: Vec<Option<trade::TradeLink>> = sorted_currencies
+        .iter()
+        .map(|currency| {
+            trade::find_currency_detail(&response.currency_details, &currency.currency_type_name)
+                .filter(|_| trade_link)
+                .and_then(|detail| trade::currency_trade_link(league, detail, "chaos"))
+        })
+        .collect();
+
+    let currencies: Vec<CurrencyDisplay> = sorted_currencies
+        .into_iter()
+        .zip(&links)
+        .map(|(currency, link)| CurrencyDisplay {
+            trade_url: link.as_ref().map(|link| link.url.clone()),
+            name: currency.currency_type_name,
+            chaos_value: currency.chaos_equivalent.unwrap_or(0.0),
+            divine_value: divine_rate
+                .map(|rate| pricing::chaos_to_divine(currency.chaos_equivalent.unwrap_or(0.0), rate))
+                .unwrap_or(0.0),
+            pay_value: currency.pay.as_ref().map(|p| p.value).unwrap_or(0.0),
+            receive_value: currency.receive.as_ref().map(|r| r.value).unwrap_or(0.0),
+            pay_count: currency.pay.as_ref().map(|p| p.count).unwrap_or(0),
+            receive_count: currency.receive.as_ref().map(|r| r.count).unwrap_or(0),
+        })
+        .collect();
+
+    if currencies.is_empty() && output.format == OutputFormat::Table {
+        writeln!(out, "{}", "No currencies found with the given filters.".red())?;
+        return Ok(());
+    }
+
+    let names: Vec<String> = currencies.iter().map(|currency| currency.name.clone()).collect();
+    write_rows(out, currencies, output, &hidden_columns(output, trade_link))?;
+    if output.format == OutputFormat::Table {
+        write_trade_links(out, names.into_iter().zip(links))?;
+    }
+
+    Ok(())
+}
+
+/// Value columns hidden for a unit, by table header
+fn value_columns(unit: Unit) -> &'static [&'static str] {
+    match unit {
+        Unit::Chaos => &["Divine Value"],
+        Unit::Divine => &["Chaos Value"],
+    }
+}
+
+/// `value_columns` plus the trade URL column: JSON and CSV carry it when links
+/// were asked for, tables list the links below the rows instead
+fn hidden_columns(output: &Output, trade_link: bool) -> Vec<&'static str> {
+    let mut hidden = value_columns(output.unit).to_vec();
+    if !trade_link || output.format == OutputFormat::Table {
+        hidden.push("Trade URL");
+    }
+    hidden
+}
+
+/// Print rows as a table, JSON array or CSV without the `hidden` columns.
+/// JSON keys are the serialized field names, which are the snake_case headers.
+fn print_rows<T: Tabled + Serialize>(rows: Vec<T>, output: &Output, hidden: &[&str]) -> Result<()> {
+    write_rows(&mut std::io::stdout(), rows, output, hidden)
+}
+
+fn write_rows<T: Tabled + Serialize>(out: &mut impl Write, rows: Vec<T>, output: &Output, hidden: &[&str]) -> Result<()> {
+    match output.format {
+        OutputFormat::Table => {
+            let mut table = Table::new(rows);
+            table.with(Style::modern());
+            for column in hidden {
+                table.with(Disable::column(ByColumnName::new(*column)));
+            }
+            writeln!(out, "{}", table)?;
+        }
+        OutputFormat::Json => writeln!(out, "{}", serde_json::to_string_pretty(&json_rows(&rows, hidden)?)?)?,
+        OutputFormat::Csv => {
+            let headers = T::headers();
+            let shown: Vec<usize> = (0..headers.len())
+                .filter(|index| !hidden.contains(&headers[*index].as_ref()))
+                .collect();
+            let mut writer = csv::Writer::from_writer(out);
+            writer.write_record(shown.iter().map(|index| headers[*index].as_ref()))?;
+            for row in &rows {
+                let fields = row.fields();
+                writer.write_record(shown.iter().map(|index| fields[*index].as_ref()))?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Rows as JSON objects without the `hidden` columns
+fn json_rows<T: Serialize>(rows: &[T], hidden: &[&str]) -> Result<Vec<serde_json::Value>> {
+    let keys: Vec<String> = hidden.iter().map(|column| column.to_lowercase().replace(' ', "_")).collect();
+    let mut values: Vec<serde_json::Value> = serde_json::from_value(serde_json::to_value(rows)?)?;
+    for value in values.iter_mut().filter_map(|value| value.as_object_mut()) {
+        value.retain(|key, _| !keys.contains(key));
+    }
+    Ok(values)
+}
+
+/// `write_rows` plus each row's modifiers: listed under the row in a table,
+/// as a `modifiers` array in JSON and as one ` | ` separated column in CSV
+fn write_rows_with_mods<T: Tabled + Serialize>(
+    out: &mut impl Write,
+    rows: Vec<T>,
+    modifiers: &[Vec<mods::ModLine>],
+    output: &Output,
+    hidden: &[&str],
+) -> Result<()> {
+    let headers = T::headers();
+    let shown: Vec<usize> = (0..headers.len())
+        .filter(|index| !hidden.contains(&headers[*index].as_ref()))
+        .collect();
+
+    match output.format {
+        OutputFormat::Table => {
+            let mut builder = Builder::default();
+            builder.push_record(shown.iter().map(|index| headers[*index].to_string()));
+            let mut mod_rows = Vec::new();
+            for (row, lines) in rows.iter().zip(modifiers) {
+                let fields = row.fields();
+                builder.push_record(shown.iter().map(|index| fields[*index].to_string()));
+                if !lines.is_empty() {
+                    mod_rows.push(builder.count_records());
+                    let mut record = vec![lines.iter().map(|line| format!("  {}", line.label())).collect::<Vec<_>>().join("\n")];
+                    record.resize(shown.len(), String::new());
+                    builder.push_record(record);
+                }
+            }
+            let mut table = builder.build();
+            table.with(Style::modern());
+            for row in mod_rows {
+                table.modify((row, 0), Span::column(shown.len()));
+            }
+            writeln!(out, "{}", table)?;
+        }
+        OutputFormat::Json => {
+            let mut values = json_rows(&rows, hidden)?;
+            for (value, lines) in values.iter_mut().zip(modifiers) {
+                if let Some(object) = value.as_object_mut() {
+                    object.insert("modifiers".to_string(), serde_json::to_value(lines)?);
+                }
+            }
+            writeln!(out, "{}", serde_json::to_string_pretty(&values)?)?;
+        }
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            let mut header: Vec<&str> = shown.iter().map(|index| headers[*index].as_ref()).collect();
+            header.push("Modifiers");
+            writer.write_record(header)?;
+            for (row, lines) in rows.iter().zip(modifiers) {
+                let fields = row.fields();
+                let mut record: Vec<String> = shown.iter().map(|index| fields[*index].to_string()).collect();
+                record.push(lines.iter().map(mods::ModLine::label).collect::<Vec<_>>().join(" | "));
+                writer.write_record(record)?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+pub fn require_poe1_trade_links(trade_link: bool) -> Result<()> {
+    if trade_link {
+        anyhow::bail!("Trade links are only available for Path of Exile 1");
+    }
+    Ok(())
+}
+
+/// PoE2 currency and item prices, both served by the exchange overview
+pub async fn handle_exchange(
+    source: &dyn PriceSource,
+    league: &str,
+    overview_type: &str,
+    name_filter: Option<&str>,
+    min_chaos: Option<f64>,
+    max_chaos: Option<f64>,
+    output: &Output,
+) -> Result<()> {
+    if output.format == OutputFormat::Table {
+        println!(
+            "{} {}",
+            "Fetching PoE2 exchange data for".bright_blue(),
+            format!("{} - {}", league, overview_type).bright_yellow()
+        );
+    }
+
+    let response = source
+        .get_exchange_overview(league, overview_type)
+        .await
+        .context("Failed to fetch exchange data")?;
+
+    let rows: Vec<ExchangeDisplay> = filter_exchange_lines(&response, name_filter, min_chaos, max_chaos)
+        .into_iter()
+        .map(|line| ExchangeDisplay {
+            name: response
+                .item_info(&line.id)
+                .map(|info| info.name.clone())
+                .unwrap_or_else(|| line.id.clone()),
+            divine_value: format!("{:.4}", line.primary_value),
+            exalted_value: response
+                .value_in(line, "exalted")
+                .map(|value| format!("{:.2}", value))
+                .unwrap_or_else(|| "-".to_string()),
+            chaos_value: response
+                .value_in(line, "chaos")
+                .map(|value| format!("{:.2}", value))
+                .unwrap_or_else(|| "-".to_string()),
+            volume: format!("{:.0}", line.volume_primary_value.unwrap_or(0.0)),
+            change: line
+                .sparkline
+                .as_ref()
+                .and_then(|sparkline| sparkline.total_change)
+                .map(|change| format!("{:+.1}%", change))
+                .unwrap_or_else(|| "-".to_string()),
+        })
+        .collect();
+
+    if rows.is_empty() && output.format == OutputFormat::Table {
+        println!("{}", "No items found with the given filters.".red());
+        return Ok(());
+    }
+
+    print_rows(rows, output, value_columns(output.unit))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_item(
+    source: &dyn PriceSource,
+    league: &str,
+    item_type: &str,
+    name_filter: Option<&str>,
+    min_chaos: Option<f64>,
+    max_chaos: Option<f64>,
+    mod_filters: &[mods::ModFilter],
+    show_mods: bool,
+    trade_link: bool,
+    output: &Output,
+) -> Result<()> {
+    write_item(
+        &mut std::io::stdout(),
+        source,
+        league,
+        item_type,
+        name_filter,
+        min_chaos,
+        max_chaos,
+        mod_filters,
+        show_mods,
+        trade_link,
+        output,
+    )
+    .await
+}
+
+/// `handle_item`, writing to `out`
+#[allow(clippy::too_many_arguments)]
+pub async fn write_item(
+    out: &mut (impl Write + Send),
+    source: &dyn PriceSource,
+    league: &str,
+    item_type: &str,
+    name_filter: Option<&str>,
+    min_chaos: Option<f64>,
+    max_chaos: Option<f64>,
+    mod_filters: &[mods::ModFilter],
+    show_mods: bool,
+    trade_link: bool,
+    output: &Output,
+) -> Result<()> {
+    if output.format == OutputFormat::Table {
+        writeln!(
+            out,
+            "{} {}",
+            "Fetching item data for".bright_blue(),
+            format!("{} - {}", league, item_type).bright_yellow()
+        )?;
+    }
+
+    let response = source
+        .get_item_overview(league, item_type)
+        .await
+        .context("Failed to fetch item data")?;
+
+    let filtered_items = filter_items_by_criteria(response.lines, name_filter, min_chaos, max_chaos);
+    let filtered_items = mods::filter_items_by_mods(filtered_items, mod_filters);
+    let sorted_items = sort_items_by_value(filtered_items);
+    let modifiers: Vec<Vec<mods::ModLine>> = sorted_items.iter().map(mods::mod_lines).collect();
+
+    let links: Vec<Option<trade::TradeLink>> = sorted_items
+        .iter()
+        .map(|item| trade_link.then(|| trade::item_trade_link(league, item)))
+        .collect();
+
+    let items: Vec<ItemDisplay> = sorted_items
+        .into_iter()
+        .zip(&links)
+        .map(|(item, link)| ItemDisplay {
+            trade_url: link.as_ref().map(|link| link.url.clone()),
+            name: item.name,
+            base_type: item.base_type.unwrap_or_else(|| "N/A".to_string()),
+            chaos_value: item.chaos_value,
+            divine_value: item.divine_value.unwrap_or(0.0),
+            count: item.count,
+            listing_count: item.listing_count.unwrap_or(0),
+            level_required: item.level_required.unwrap_or(0),
+        })
+        .collect();
+
+    if items.is_empty() && output.format == OutputFormat::Table {
+        writeln!(out, "{}", "No items found with the given filters.".red())?;
+        return Ok(());
+    }
+
+    let names: Vec<String> = items.iter().map(|item| item.name.clone()).collect();
+    let hidden = hidden_columns(output, trade_link);
+    if show_mods {
+        write_rows_with_mods(out, items, &modifiers, output, &hidden)?;
+    } else {
+        write_rows(out, items, output, &hidden)?;
+    }
+    if output.format == OutputFormat::Table {
+        write_trade_links(out, names.into_iter().zip(links))?;
+    }
+
+    Ok(())
+}
+
+pub async fn handle_trade(
+    source: &dyn PriceSource,
+    league: &str,
+    item_type: &str,
+    name: &str,
+    have: &str,
+    output: &Output,
+) -> Result<()> {
+    if output.format == OutputFormat::Table {
+        println!(
+            "{} {}",
+            "Building trade query for".bright_blue(),
+            format!("{} - {}", name, item_type).bright_yellow()
+        );
+    }
+
+    let (name, link) = if get_currency_types().contains(&item_type) {
+        let response = source
+            .get_currency_overview(league, item_type)
+            .await
+            .context("Failed to fetch currency data")?;
+
+        let detail = trade::find_currency_detail(&response.currency_details, name)
+            .with_context(|| format!("No currency named '{}' in {}", name, item_type))?;
+
+        let link = trade::currency_trade_link(league, detail, have)
+            .with_context(|| format!("'{}' has no trade id on poe.ninja", detail.name))?;
+        (detail.name.clone(), link)
+    } else {
+        let response = source
+            .get_item_overview(league, item_type)
+            .await
+            .context("Failed to fetch item data")?;
+
+        let item = find_item_by_name(&response.lines, name)
+            .with_context(|| format!("No item named '{}' in {}", name, item_type))?;
+
+        (item.name.clone(), trade::item_trade_link(league, item))
+    };
+
+    match output.format {
+        OutputFormat::Json => {
+            let report = TradeReport {
+                name: &name,
+                category: item_type,
+                query: &link.query,
+                url: &link.url,
+            };
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        OutputFormat::Csv => {
+            let row = TradeDisplay {
+                name,
+                category: item_type.to_string(),
+                url: link.url,
+            };
+            print_rows(vec![row], output, &[])?;
+        }
+        OutputFormat::Table => {
+            println!();
+            println!("{}", "Query:".bright_green());
+            println!("{}", serde_json::to_string_pretty(&link.query)?);
+            println!();
+            println!("{}", "URL:".bright_green());
+            println!("{}", link.url);
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct TradeReport<'a> {
+    name: &'a str,
+    category: &'a str,
+    query: &'a serde_json::Value,
+    url: &'a str,
+}
+
+/// Total columns hidden for a unit, by table header
+fn total_columns(unit: Unit) -> &'static [&'static str] {
+    match unit {
+        Unit::Chaos => &["Total Divine"],
+        Unit::Divine => &["Total Chaos"],
+    }
+}
+
+pub async fn handle_portfolio(source: &dyn PriceSource, league: &str, file: &Path, output: &Output) -> Result<()> {
+    write_portfolio(&mut std::io::stdout(), source, league, file, output).await
+}
+
+/// `handle_portfolio`, writing to `out`
+pub async fn write_portfolio(
+    out: &mut (impl Write + Send),
+    source: &dyn PriceSource,
+    league: &str,
+    file: &Path,
+    output: &Output,
+) -> Result<()> {
+    let holdings = portfolio::load_holdings(file)?;
+
+    if output.format == OutputFormat::Table {
+        writeln!(
+            out,
+            "{} {}",
+            "Valuing holdings for".bright_blue(),
+            format!("{} - {} entries", league, holdings.len()).bright_yellow()
+        )?;
+    }
+
+    let loaded = portfolio::load_price_book(source, league, &holdings).await;
+    for (category, err) in &loaded.skipped {
+        eprintln!("{} {}: {:#}", "Skipped".yellow(), category, err);
+    }
+    let valuation = portfolio::value_holdings(&loaded.book, &holdings);
+
+    let rows: Vec<PortfolioDisplay> = valuation
+        .lines
+        .iter()
+        .map(|line| PortfolioDisplay {
+            name: line.price.name.clone(),
+            category: line.price.category.clone(),
+            quantity: line.holding.quantity,
+            unit_chaos: line.price.chaos_value,
+            total_chaos: line.total_chaos,
+            total_divine: valuation
+                .divine_rate
+                .map(|rate| pricing::chaos_to_divine(line.total_chaos, rate))
+                .unwrap_or(0.0),
+        })
+        .collect();
+    let hidden = total_columns(output.unit);
+
+    match output.format {
+        OutputFormat::Json => {
+            let report = PortfolioReport {
+                league,
+                holdings: json_rows(&rows, hidden)?,
+                total_chaos: valuation.total_chaos,
+                total_divine: valuation.total_divine(),
+                unpriced: valuation
+                    .unpriced
+                    .iter()
+                    .map(|holding| UnpricedHolding {
+                        name: &holding.name,
+                        quantity: holding.quantity,
+                    })
+                    .collect(),
+                skipped: loaded.skipped.iter().map(|(category, _)| category.as_str()).collect(),
+            };
+            writeln!(out, "{}", serde_json::to_string_pretty(&report)?)?;
+            return Ok(());
+        }
+        OutputFormat::Csv => return write_rows(out, rows, output, hidden),
+        OutputFormat::Table => {}
+    }
+
+    if rows.is_empty() {
+        writeln!(out, "{}", "None of the holdings could be priced.".red())?;
+    } else {
+        write_rows(out, rows, output, hidden)?;
+    }
+
+    writeln!(out)?;
+    let total = match valuation.total_divine() {
+        Some(divines) => format!("{:.1} chaos ({:.2} divine)", valuation.total_chaos, divines),
+        None => format!("{:.1} chaos", valuation.total_chaos),
+    };
+    writeln!(out, "{} {}", "Total:".bright_green(), total.bright_yellow())?;
+
+    if !valuation.unpriced.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "{}", "Could not price:".red())?;
+        for holding in &valuation.unpriced {
+            writeln!(out, "  • {} x{}", holding.name.bright_yellow(), holding.quantity)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct PortfolioReport<'a> {
+    league: &'a str,
+    holdings: Vec<serde_json::Value>,
+    total_chaos: f64,
+    total_divine: Option<f64>,
+    unpriced: Vec<UnpricedHolding<'a>>,
+    skipped: Vec<&'a str>,
+}
+
+#[derive(Serialize)]
+struct UnpricedHolding<'a> {
+    name: &'a str,
+    quantity: u32,
+}
+
+pub async fn handle_stash_value(
+    source: &dyn PriceSource,
+    league: &str,
+    file: &Path,
+    top: usize,
+    output: &Output,
+) -> Result<()> {
+    let items = stash::load_stash(file)?;
+
+    if output.format == OutputFormat::Table {
+        println!(
+            "{} {}",
+            "Valuing stash for".bright_blue(),
+            format!("{} - {} items", league, items.len()).bright_yellow()
+        );
+    }
+
+    let loaded = stash::load_price_book(source, league, &items).await;
+    for (category, err) in &loaded.skipped {
+        eprintln!("{} {}: {:#}", "Skipped".yellow(), category, err);
+    }
+    let valuation = stash::value_stash(&loaded.book, &items);
+    let to_divine = |chaos: f64| {
+        valuation
+            .divine_rate
+            .map(|rate| pricing::chaos_to_divine(chaos, rate))
+            .unwrap_or(0.0)
+    };
+
+    let rows: Vec<StashDisplay> = valuation
+        .items
+        .iter()
+        .map(|valued| StashDisplay {
+            name: valued.price.name.clone(),
+            category: valued.price.category.clone(),
+            quantity: valued.quantity,
+            unit_chaos: valued.price.chaos_value,
+            total_chaos: valued.total_chaos,
+            total_divine: to_divine(valued.total_chaos),
+        })
+        .collect();
+    let hidden = total_columns(output.unit);
+    let contributors = valuation.top_contributors(top);
+
+    match output.format {
+        OutputFormat::Json => {
+            let report = StashReport {
+                league,
+                items: json_rows(&rows, hidden)?,
+                total_chaos: valuation.total_chaos,
+                total_divine: valuation
+                    .divine_rate
+                    .map(|rate| pricing::chaos_to_divine(valuation.total_chaos, rate)),
+                top_contributors: contributors
+                    .iter()
+                    .map(|(valued, share)| Contributor {
+                        name: &valued.price.name,
+                        quantity: valued.quantity,
+                        total_chaos: valued.total_chaos,
+                        share: *share,
+                    })
+                    .collect(),
+                unpriced: valuation.unpriced.len(),
+                skipped: loaded.skipped.iter().map(|(category, _)| category.as_str()).collect(),
+            };
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(());
+        }
+        OutputFormat::Csv => return print_rows(rows, output, hidden),
+        OutputFormat::Table => {}
+    }
+
+    if rows.is_empty() {
+        println!("{}", "None of the stash items could be priced.".red());
+        return Ok(());
+    }
+
+    print_rows(rows, output, hidden)?;
+
+    println!();
+    let total = match valuation.divine_rate {
+        Some(rate) => format!(
+            "{:.1} chaos ({:.2} divine)",
+            valuation.total_chaos,
+            pricing::chaos_to_divine(valuation.total_chaos, rate)
+        ),
+        None => format!("{:.1} chaos", valuation.total_chaos),
+    };
+    println!("{} {}", "Total:".bright_green(), total.bright_yellow());
+
+    println!();
+    println!("{}", "Top Contributors:".bright_green());
+    for (valued, share) in contributors {
+        println!(
+            "  • {} x{}: {:.1} chaos ({:.1}%)",
+            valued.price.name.bright_yellow(),
+            valued.quantity,
+            valued.total_chaos,
+            share
+        );
+    }
+
+    if !valuation.unpriced.is_empty() {
+        println!();
+        println!(
+            "{}",
+            format!("{} items could not be priced.", valuation.unpriced.len()).dimmed()
+        );
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct StashReport<'a> {
+    league: &'a str,
+    items: Vec<serde_json::Value>,
+    total_chaos: f64,
+    total_divine: Option<f64>,
+    top_contributors: Vec<Contributor<'a>>,
+    unpriced: usize,
+    skipped: Vec<&'a str>,
+}
+
+#[derive(Serialize)]
+struct Contributor<'a> {
+    name: &'a str,
+    quantity: u32,
+    total_chaos: f64,
+    share: f64,
+}
+
+pub async fn handle_divcards(
+    source: &dyn PriceSource,
+    league: &str,
+    name_filter: Option<&str>,
+    min_profit: f64,
+    all: bool,
+    output: &Output,
+) -> Result<()> {
+    if output.format == OutputFormat::Table {
+        println!(
+            "{} {}",
+            "Fetching divination cards for".bright_blue(),
+            league.bright_yellow()
+        );
+    }
+
+    let response = source
+        .get_item_overview(league, "DivinationCard")
+        .await
+        .context("Failed to fetch divination card data")?;
+
+    let cards = filter_items_by_criteria(response.lines, name_filter, None, None);
+    let loaded = divcards::load_price_book(source, league, &cards).await;
+    let (resolved, unresolved) = divcards::evaluate_cards(&cards, &loaded.book);
+
+    let rows: Vec<DivCardDisplay> = resolved
+        .iter()
+        .filter(|value| all || value.profit().is_some_and(|p| p > 0.0 && p >= min_profit))
+        .map(|value| DivCardDisplay {
+            card: value.card.clone(),
+            reward: value
+                .reward
+                .as_ref()
+                .map(|reward| match reward.quantity {
+                    1 => reward.name.clone(),
+                    n => format!("{}x {}", n, reward.name),
+                })
+                .unwrap_or_default(),
+            stack_size: value.stack_size,
+            set_cost: value.set_cost,
+            reward_value: value.reward_value.unwrap_or(0.0),
+            profit: value.profit().unwrap_or(0.0),
+            margin: format!("{:.1}%", value.margin().unwrap_or(0.0)),
+        })
+        .collect();
+    let unresolved: Vec<UnresolvedCard> = unresolved
+        .iter()
+        .map(|value| UnresolvedCard {
+            card: value.card.clone(),
+            reward: value
+                .reward
+                .as_ref()
+                .map(|reward| reward.text.replace('\n', " "))
+                .unwrap_or_else(|| "no reward text".to_string()),
+        })
+        .collect();
+
+    if output.format != OutputFormat::Table {
+        for (category, err) in &loaded.skipped {
+            eprintln!("{} {}: {:#}", "Skipped".yellow(), category, err);
+        }
+    }
+
+    match output.format {
+        OutputFormat::Json => {
+            let report = DivcardsReport {
+                league,
+                cards: &rows,
+                unresolved: &unresolved,
+                skipped: loaded.skipped.iter().map(|(category, _)| category.as_str()).collect(),
+            };
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(());
+        }
+        OutputFormat::Csv => return print_rows(rows, output, &[]),
+        OutputFormat::Table => {}
+    }
+
+    if rows.is_empty() {
+        println!("{}", "No profitable turn-ins found.".red());
+    } else {
+        print_rows(rows, output, &[])?;
+    }
+
+    if !unresolved.is_empty() || !loaded.skipped.is_empty() {
+        println!();
+        println!("{}", "Unresolved rewards:".red());
+        for value in &unresolved {
+            println!("  • {}: {}", value.card.bright_yellow(), value.reward.dimmed());
+        }
+        for (category, err) in &loaded.skipped {
+            println!("  • {} {}: {:#}", "Skipped".yellow(), category, err);
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct DivcardsReport<'a> {
+    league: &'a str,
+    cards: &'a [DivCardDisplay],
+    unresolved: &'a [UnresolvedCard],
+    skipped: Vec<&'a str>,
+}
+
+#[derive(Serialize)]
+struct UnresolvedCard {
+    card: String,
+    reward: String,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_gems(
+    source: &dyn PriceSource,
+    league: &str,
+    name_filter: Option<&str>,
+    sort: &str,
+    strategy: Option<&str>,
+    outcomes: Option<&Path>,
+    min_profit: Option<f64>,
+    output: &Output,
+) -> Result<()> {
+    let table = match outcomes {
+        Some(path) => gems::OutcomeTable::load(path)?,
+        None => gems::OutcomeTable::default(),
+    };
+
+    if output.format == OutputFormat::Table {
+        println!(
+            "{} {}",
+            "Fetching gem data for".bright_blue(),
+            league.bright_yellow()
+        );
+    }
+
+    let response = source
+        .get_item_overview(league, "SkillGem")
+        .await
+        .context("Failed to fetch gem data")?;
+
+    let lines = filter_items_by_criteria(response.lines, name_filter, None, None);
+    let found: Vec<gems::GemOpportunity> = gems::group_gems(&lines)
+        .iter()
+        .flat_map(|group| gems::opportunities(group, &table))
+        .filter(|found| {
+            strategy.is_none_or(|s| found.strategy.to_string().eq_ignore_ascii_case(s))
+        })
+        .filter(|found| min_profit.is_none_or(|min| found.profit() >= min))
+        .collect();
+
+    let sorted = match sort {
+        "margin" => gems::sort_by_margin(found),
+        _ => gems::sort_by_profit(found),
+    };
+
+    let rows: Vec<GemDisplay> = sorted
+        .into_iter()
+        .map(|found| GemDisplay {
+            profit: found.profit(),
+            margin: format!("{:.1}%", found.margin()),
+            name: found.name,
+            strategy: found.strategy.to_string(),
+            from: found.from,
+            to: found.to,
+            cost: found.cost,
+            value: found.value,
+        })
+        .collect();
+
+    if rows.is_empty() && output.format == OutputFormat::Table {
+        println!("{}", "No gem opportunities found with the given filters.".red());
+        return Ok(());
+    }
+
+    print_rows(rows, output, &[])
+}
+
+/// Rewrite a filter; with JSON or CSV output a summary of the marked blocks
+/// follows the filter (on stderr when the filter itself goes to stdout)
+pub async fn handle_filter(
+    source: &dyn PriceSource,
+    league: &str,
+    input: &Path,
+    destination: Option<&Path>,
+    tiers: Option<&Path>,
+    output: &Output,
+) -> Result<()> {
+    let template = std::fs::read_to_string(input)
+        .with_context(|| format!("Failed to read filter '{}'", input.display()))?;
+    let config = match tiers {
+        Some(path) => filter::TierConfig::load(path)?,
+        None => filter::TierConfig::default(),
+    };
+
+    // The filter itself may go to stdout, so progress goes to stderr
+    let categories = filter::required_categories(&template, &config)?;
+    if output.format == OutputFormat::Table {
+        eprintln!(
+            "{} {}",
+            "Fetching filter prices for".bright_blue(),
+            format!("{} - {}", league, categories.join(", ")).bright_yellow()
+        );
+    }
+
+    let mut book = pricing::PriceBook::new();
+    for category in &categories {
+        book.ensure_category(source, league, category).await?;
+    }
+
+    let (rewritten, summary) = filter::rewrite_with_summary(&template, &book, &config)?;
+
+    match destination {
+        Some(path) => {
+            std::fs::write(path, rewritten)
+                .with_context(|| format!("Failed to write filter '{}'", path.display()))?;
+            if output.format == OutputFormat::Table {
+                eprintln!("{} {}", "Wrote".bright_green(), path.display());
+            }
+        }
+        None => print!("{}", rewritten),
+    }
+
+    if output.format != OutputFormat::Table {
+        let rows: Vec<FilterBlockDisplay> = summary
+            .into_iter()
+            .map(|block| FilterBlockDisplay {
+                header: block.header,
+                bases: block.bases,
+            })
+            .collect();
+        match destination {
+            Some(_) => print_rows(rows, output, &[])?,
+            None => write_rows(&mut std::io::stderr(), rows, output, &[])?,
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn handle_compare(
+    source: &dyn PriceSource,
+    leagues: &[String],
+    item_type: &str,
+    name_filter: Option<&str>,
+    min_diff_percent: Option<f64>,
+    output: &Output,
+) -> Result<()> {
+    write_compare(&mut std::io::stdout(), source, leagues, item_type, name_filter, min_diff_percent, output).await
+}
+
+/// `handle_compare`, writing to `out`
+pub async fn write_compare(
+    out: &mut (impl Write + Send),
+    source: &dyn PriceSource,
+    leagues: &[String],
+    item_type: &str,
+    name_filter: Option<&str>,
+    min_diff_percent: Option<f64>,
+    output: &Output,
+) -> Result<()> {
+    if leagues.len() < 2 {
+        anyhow::bail!("Pass at least two leagues to compare, e.g. -l Standard -l Settlers");
+    }
+
+    if output.format == OutputFormat::Table {
+        writeln!(
+            out,
+            "{} {}",
+            "Comparing".bright_blue(),
+            format!("{} across {}", item_type, leagues.join(", ")).bright_yellow()
+        )?;
+    }
+
+    let per_league: Vec<Vec<compare::PriceRow>> = if get_currency_types().contains(&item_type) {
+        let responses = futures::future::try_join_all(
+            leagues
+                .iter()
+                .map(|league| source.get_currency_overview(league, item_type)),
+        )
+        .await
+        .context("Failed to fetch currency data")?;
+
+        responses
+            .into_iter()
+            .map(|response| {
+                let lines = filter_currencies_by_name(response.lines, name_filter);
+                compare::rows_from_currencies(&lines)
+            })
+            .collect()
+    } else {
+        let responses = futures::future::try_join_all(
+            leagues
+                .iter()
+                .map(|league| source.get_item_overview(league, item_type)),
+        )
+        .await
+        .context("Failed to fetch item data")?;
+
+        responses
+            .into_iter()
+            .map(|response| {
+                let lines = filter_items_by_criteria(response.lines, name_filter, None, None);
+                compare::rows_from_items(&lines)
+            })
+            .collect()
+    };
+
+    let entries = compare::join_leagues(&per_league);
+    let (shared, partial): (Vec<_>, Vec<_>) =
+        entries.into_iter().partition(|entry| entry.in_all_leagues());
+
+    let shared: Vec<_> = shared
+        .into_iter()
+        .filter(|entry| {
+            min_diff_percent.is_none_or(|min| entry.difference_percent().unwrap_or(0.0) >= min)
+        })
+        .collect();
+    let listed_in = |entry: &compare::ComparedEntry| -> Vec<String> {
+        entry
+            .values
+            .iter()
+            .zip(leagues)
+            .filter(|(value, _)| value.is_some())
+            .map(|(_, league)| league.clone())
+            .collect()
+    };
+
+    if output.format == OutputFormat::Json {
+        let report = CompareReport {
+            category: item_type,
+            leagues,
+            entries: shared
+                .iter()
+                .map(|entry| CompareEntry {
+                    name: &entry.name,
+                    details_id: &entry.details_id,
+                    values: &entry.values,
+                    difference: entry.difference(),
+                    difference_percent: entry.difference_percent(),
+                    cheapest_in: entry.cheapest().map(|index| leagues[index].as_str()),
+                })
+                .collect(),
+            partial: partial
+                .iter()
+                .map(|entry| PartialEntry {
+                    name: &entry.name,
+                    leagues: listed_in(entry),
+                })
+                .collect(),
+        };
+        writeln!(out, "{}", serde_json::to_string_pretty(&report)?)?;
+        return Ok(());
+    }
+
+    let mut header = vec!["Name".to_string()];
+    header.extend(leagues.iter().cloned());
+    header.extend(["Diff".to_string(), "Diff %".to_string(), "Cheapest In".to_string()]);
+    let records = shared.iter().map(|entry| {
+        let mut record = vec![entry.name.clone()];
+        record.extend(entry.values.iter().map(|value| format!("{:.1}", value.unwrap_or(0.0))));
+        record.push(format!("{:.1}", entry.difference().unwrap_or(0.0)));
+        record.push(
+            entry
+                .difference_percent()
+                .map(|pct| format!("{:.1}%", pct))
+                .unwrap_or_else(|| "N/A".to_string()),
+        );
+        record.push(
+            entry
+                .cheapest()
+                .map(|index| leagues[index].clone())
+                .unwrap_or_default(),
+        );
+        record
+    });
+
+    if output.format == OutputFormat::Csv {
+        let mut writer = csv::Writer::from_writer(out);
+        writer.write_record(&header)?;
+        for record in records {
+            writer.write_record(record)?;
+        }
+        writer.flush()?;
+        return Ok(());
+    }
+
+    if shared.is_empty() {
+        writeln!(out, "{}", "No entries found in every league with the given filters.".red())?;
+    } else {
+        let mut builder = Builder::default();
+        builder.push_record(header);
+        for record in records {
+            builder.push_record(record);
+        }
+
+        let mut table = builder.build();
+        table.with(Style::modern());
+        writeln!(out, "{}", table)?;
+    }
+
+    if !partial.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "{}", "Not listed in every league:".bright_green())?;
+        for entry in &partial {
+            writeln!(out, "  • {} ({})", entry.name.bright_yellow(), listed_in(entry).join(", "))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct CompareReport<'a> {
+    category: &'a str,
+    leagues: &'a [String],
+    entries: Vec<CompareEntry<'a>>,
+    partial: Vec<PartialEntry<'a>>,
+}
+
+#[derive(Serialize)]
+struct CompareEntry<'a> {
+    name: &'a str,
+    details_id: &'a str,
+    /// Chaos value per league, in the order of `leagues`
+    values: &'a [Option<f64>],
+    difference: Option<f64>,
+    difference_percent: Option<f64>,
+    cheapest_in: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct PartialEntry<'a> {
+    name: &'a str,
+    leagues: Vec<String>,
+}
+
+/// Market summary of one category, as tables, one JSON object or long-form CSV
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_stats(
+    source: &dyn PriceSource,
+    league: &str,
+    item_type: &str,
+    name_filter: Option<&str>,
+    min_chaos: Option<f64>,
+    max_chaos: Option<f64>,
+    top: usize,
+    output: &Output,
+) -> Result<()> {
+    if output.format == OutputFormat::Table {
+        println!(
+            "{} {}",
+            "Summarizing".bright_blue(),
+            format!("{} - {}", league, item_type).bright_yellow()
+        );
+    }
+
+    let lines = if get_currency_types().contains(&item_type) {
+        let response = source
+            .get_currency_overview(league, item_type)
+            .await
+            .context("Failed to fetch currency data")?;
+        let mut lines = stats::lines_from_currencies(&filter_currencies_by_name(response.lines, name_filter));
+        lines.retain(|line| {
+            min_chaos.is_none_or(|min| line.chaos_value >= min) && max_chaos.is_none_or(|max| line.chaos_value <= max)
+        });
+        lines
+    } else {
+        let response = source
+            .get_item_overview(league, item_type)
+            .await
+            .context("Failed to fetch item data")?;
+        stats::lines_from_items(&filter_items_by_criteria(response.lines, name_filter, min_chaos, max_chaos))
+    };
+
+    let summary = stats::summarize(&lines, top);
+
+    match output.format {
+        OutputFormat::Json => {
+            let report = StatsReport {
+                league,
+                category: item_type,
+                stats: &summary,
+            };
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        OutputFormat::Csv => print_rows(stat_rows(&summary), output, &[])?,
+        OutputFormat::Table => {
+            if lines.is_empty() {
+                println!("{}", "No entries found with the given filters.".red());
+                return Ok(());
+            }
+            print_rows(summary_rows(&summary), output, &[])?;
+
+            println!();
+            println!("{}", "Change over 7 days:".bright_green());
+            let buckets: Vec<BucketDisplay> = summary
+                .change_distribution
+                .iter()
+                .map(|bucket| BucketDisplay {
+                    range: bucket.range.clone(),
+                    count: bucket.count,
+                })
+                .collect();
+            print_rows(buckets, output, &[])?;
+
+            for (title, movers) in [("Top gainers:", &summary.gainers), ("Top losers:", &summary.losers)] {
+                if movers.is_empty() {
+                    continue;
+                }
+                println!();
+                println!("{}", title.bright_green());
+                let rows: Vec<MoverDisplay> = movers
+                    .iter()
+                    .map(|mover| MoverDisplay {
+                        name: mover.name.clone(),
+                        chaos_value: mover.chaos_value,
+                        change: format!("{:+.1}%", mover.total_change),
+                    })
+                    .collect();
+                print_rows(rows, output, &[])?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct StatsReport<'a> {
+    league: &'a str,
+    category: &'a str,
+    #[serde(flatten)]
+    stats: &'a stats::MarketStats,
+}
+
+fn summary_rows(summary: &stats::MarketStats) -> Vec<MetricDisplay> {
+    let mut rows = vec![
+        MetricDisplay::new("Entries", summary.count.to_string()),
+        MetricDisplay::new("Total chaos", format!("{:.1}", summary.total_chaos)),
+        MetricDisplay::new("Mean chaos", format!("{:.2}", summary.mean_chaos)),
+        MetricDisplay::new("Median chaos", format!("{:.2}", summary.median_chaos)),
+    ];
+    rows.extend(summary.percentiles.iter().map(|percentile| {
+        MetricDisplay::new(&format!("P{} chaos", percentile.percentile), format!("{:.2}", percentile.chaos_value))
+    }));
+    rows.push(MetricDisplay::new(
+        "Low confidence",
+        format!("{} ({:.1}%)", summary.low_confidence, summary.low_confidence_share * 100.0),
+    ));
+    rows.push(MetricDisplay::new(
+        "Median change",
+        summary
+            .median_change
+            .map(|change| format!("{:+.1}%", change))
+            .unwrap_or_else(|| "-".to_string()),
+    ));
+    rows
+}
+
+/// Every section of the summary as `section,name,value` rows
+fn stat_rows(summary: &stats::MarketStats) -> Vec<StatRow> {
+    let mut rows: Vec<StatRow> = summary_rows(summary)
+        .into_iter()
+        .map(|row| StatRow::new("summary", &row.metric, row.value))
+        .collect();
+    rows.extend(
+        summary
+            .change_distribution
+            .iter()
+            .map(|bucket| StatRow::new("change", &bucket.range, bucket.count.to_string())),
+    );
+    rows.extend(
+        summary
+            .gainers
+            .iter()
+            .map(|mover| StatRow::new("gainer", &mover.name, format!("{:.1}", mover.total_change))),
+    );
+    rows.extend(
+        summary
+            .losers
+            .iter()
+            .map(|mover| StatRow::new("loser", &mover.name, format!("{:.1}", mover.total_change))),
+    );
+    rows
+}
+
+/// Diff two saved overviews or snapshots; `key` names the overview for snapshot times
+pub fn handle_diff(
+    before: &str,
+    after: &str,
+    key: Option<&RequestKey>,
+    thresholds: &diff::Thresholds,
+    output: &Output,
+) -> Result<()> {
+    let store = SnapshotStore::open_default().ok();
+    let (before_label, before_path) = snapshots::resolve(store.as_ref(), key, before)?;
+    let (after_label, after_path) = snapshots::resolve(store.as_ref(), key, after)?;
+
+    let read = |path: &Path| -> Result<Vec<diff::SnapshotRow>> {
+        let body =
+            std::fs::read_to_string(path).with_context(|| format!("Failed to read '{}'", path.display()))?;
+        diff::rows_from_body(&body).with_context(|| format!("Failed to load '{}'", path.display()))
+    };
+    let entries: Vec<diff::DiffEntry> = diff::diff(&read(&before_path)?, &read(&after_path)?)
+        .into_iter()
+        .filter(|entry| entry.passes(thresholds))
+        .collect();
+
+    if output.format == OutputFormat::Table {
+        println!(
+            "{} {}",
+            "Comparing".bright_blue(),
+            format!("{} with {}", before_label, after_label).bright_yellow()
+        );
+        if entries.is_empty() {
+            println!("{}", "No differences above the thresholds.".red());
+            return Ok(());
+        }
+    }
+
+    let rows: Vec<DiffDisplay> = entries
+        .iter()
+        .map(|entry| DiffDisplay {
+            status: format!("{:?}", entry.status).to_lowercase(),
+            name: entry.name.clone(),
+            details_id: entry.details_id.clone(),
+            before: entry.before.as_ref().map(|side| side.chaos_value),
+            after: entry.after.as_ref().map(|side| side.chaos_value),
+            change: entry.value_change(),
+            change_percent: entry.percent_change(),
+            count_change: entry.count_change(),
+            rank_change: entry.rank_change(),
+        })
+        .collect();
+
+    let hidden: &[&str] = match output.format {
+        OutputFormat::Table => &["Details Id"],
+        _ => &[],
+    };
+    print_rows(rows, output, hidden)
+}
+
+/// Fit a model to one entry's sparkline (or `history`) and print the projection
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_forecast(
+    source: &dyn PriceSource,
+    league: &str,
+    item_type: &str,
+    name: &str,
+    days: usize,
+    model: forecast::Model,
+    history: bool,
+    threshold: f64,
+    output: &Output,
+) -> Result<()> {
+    let (found, id, current, sparkline) = if get_currency_types().contains(&item_type) {
+        let response = source
+            .get_currency_overview(league, item_type)
+            .await
+            .context("Failed to fetch currency data")?;
+        let line = find_currency_by_name(&response.lines, name)
+            .with_context(|| format!("No currency named '{}' in {}", name, item_type))?;
+        let id = trade::find_currency_detail(&response.currency_details, &line.currency_type_name).map(|detail| detail.id);
+        let sparkline =
+            forecast::pick_sparkline(&line.receive_spark_line, &line.low_confidence_receive_spark_line).clone();
+        (line.currency_type_name.clone(), id, line.chaos_equivalent.unwrap_or(0.0), sparkline)
+    } else {
+        let response = source
+            .get_item_overview(league, item_type)
+            .await
+            .context("Failed to fetch item data")?;
+        let item = find_item_by_name(&response.lines, name)
+            .with_context(|| format!("No item named '{}' in {}", name, item_type))?;
+        let sparkline = forecast::pick_sparkline(&item.sparkline, &item.low_confidence_sparkline).clone();
+        (item.name.clone(), Some(item.id), item.chaos_value, sparkline)
+    };
+
+    let series = if history {
+        let id = id.with_context(|| format!("'{}' has no history id on poe.ninja", found))?;
+        let points = source
+            .get_history(league, item_type, id)
+            .await
+            .context("Failed to fetch price history")?;
+        forecast::series_from_history(&points)
+    } else {
+        forecast::series_from_sparkline(current, &sparkline)
+    };
+    let result = forecast::forecast(&series, model, days, threshold)
+        .with_context(|| format!("Cannot forecast '{}'", found))?;
+
+    if output.format == OutputFormat::Json {
+        let report = ForecastReport {
+            league,
+            category: item_type,
+            name: &found,
+            forecast: &result,
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    if output.format == OutputFormat::Table {
+        let verdict = match result.trend {
+            forecast::Trend::Up => "trending up".bright_green(),
+            forecast::Trend::Down => "trending down".bright_red(),
+            forecast::Trend::Flat => "no significant trend".normal(),
+        };
+        println!(
+            "{} {} ({} fit of {} daily values): {:.1} chaos now, {:+.1}% in {} days, {}",
+            "Forecast for".bright_blue(),
+            found.bright_yellow(),
+            format!("{:?}", model).to_lowercase(),
+            result.points,
+            result.last,
+            result.change_percent,
+            days,
+            verdict
+        );
+    }
+
+    let rows: Vec<ForecastDisplay> = result
+        .projection
+        .iter()
+        .map(|day| ForecastDisplay {
+            day: day.day,
+            value: day.value,
+            low: day.low,
+            high: day.high,
+        })
+        .collect();
+    print_rows(rows, output, &[])
+}
+
+#[derive(Serialize)]
+struct ForecastReport<'a> {
+    league: &'a str,
+    category: &'a str,
+    name: &'a str,
+    #[serde(flatten)]
+    forecast: &'a forecast::Forecast,
+}
+
+/// Score suspicious entries of one category and list the reasons behind each score
+pub async fn handle_anomalies(
+    source: &dyn PriceSource,
+    league: &str,
+    item_type: &str,
+    min_score: f64,
+    output: &Output,
+) -> Result<()> {
+    if output.format == OutputFormat::Table {
+        println!(
+            "{} {}",
+            "Scanning".bright_blue(),
+            format!("{} - {}", league, item_type).bright_yellow()
+        );
+    }
+
+    let heuristics = anomalies::Heuristics::default();
+    let mut flagged = if get_currency_types().contains(&item_type) {
+        let response = source
+            .get_currency_overview(league, item_type)
+            .await
+            .context("Failed to fetch currency data")?;
+        anomalies::scan_currencies(&response.lines, &heuristics)
+    } else {
+        let response = source
+            .get_item_overview(league, item_type)
+            .await
+            .context("Failed to fetch item data")?;
+        anomalies::scan_items(&response.lines, &heuristics)
+    };
+    flagged.retain(|entry| entry.score >= min_score);
+
+    match output.format {
+        OutputFormat::Json => {
+            let report = AnomaliesReport {
+                league,
+                category: item_type,
+                anomalies: &flagged,
+            };
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            Ok(())
+        }
+        _ => {
+            if output.format == OutputFormat::Table && flagged.is_empty() {
+                println!("{}", "No anomalies found.".green());
+                return Ok(());
+            }
+            let rows: Vec<AnomalyDisplay> = flagged
+                .iter()
+                .map(|entry| AnomalyDisplay {
+                    name: entry.name.clone(),
+                    chaos_value: entry.chaos_value,
+                    score: entry.score,
+                    reasons: entry
+                        .reasons
+                        .iter()
+                        .map(|reason| reason.detail.clone())
+                        .collect::<Vec<_>>()
+                        .join("; "),
+                })
+                .collect();
+            print_rows(rows, output, &[])
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AnomaliesReport<'a> {
+    league: &'a str,
+    category: &'a str,
+    anomalies: &'a [anomalies::Flagged],
+}
+
+/// Rank entries across categories, fetched one at a time with `delay` between requests
+/// so a full sweep stays under poe.ninja's rate limit. Categories that fail are skipped.
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_top(
+    source: &dyn PriceSource,
+    league: &str,
+    item_types: &[String],
+    count: usize,
+    per_category: bool,
+    exclusions: &top::Exclusions,
+    delay: std::time::Duration,
+    output: &Output,
+) -> Result<()> {
+    let requested: Vec<&str> = item_types.iter().map(String::as_str).collect();
+    let categories: Vec<&str> = search_types(&requested)?
+        .into_iter()
+        .filter(|category| !exclusions.categories.iter().any(|excluded| excluded.eq_ignore_ascii_case(category)))
+        .collect();
+
+    if output.format == OutputFormat::Table {
+        println!(
+            "{} {}",
+            "Ranking".bright_blue(),
+            format!("{} - {} categories", league, categories.len()).bright_yellow()
+        );
+    }
+
+    let mut entries: Vec<top::Ranked> = Vec::new();
+    let mut divine_rate = None;
+    let mut failed = 0;
+    for (index, category) in categories.iter().enumerate() {
+        if index > 0 && !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+        let fetched = if get_currency_types().contains(category) {
+            source.get_currency_overview(league, category).await.map(|response| {
+                if *category == "Currency" {
+                    divine_rate = pricing::divine_rate(&response.lines);
+                }
+                top::entries_from_currencies(category, &response.lines)
+            })
+        } else {
+            source
+                .get_item_overview(league, category)
+                .await
+                .map(|response| top::entries_from_items(category, &response.lines))
+        };
+        match fetched {
+            Ok(fetched) => entries.extend(fetched),
+            Err(err) => {
+                failed += 1;
+                eprintln!("{} {}: {:#}", "Skipped".yellow(), category, err);
+            }
+        }
+    }
+    if failed > 0 && failed == categories.len() {
+        anyhow::bail!("Failed to fetch any of the {} categories", failed);
+    }
+
+    let ranked = top::top(entries, exclusions, count, per_category);
+    if ranked.is_empty() && output.format == OutputFormat::Table {
+        println!("{}", "No entries left after the exclusions.".red());
+        return Ok(());
+    }
+
+    let mut rank = 0;
+    let mut previous: Option<&str> = None;
+    let rows: Vec<TopDisplay> = ranked
+        .iter()
+        .map(|entry| {
+            if per_category && previous != Some(entry.category.as_str()) {
+                rank = 0;
+            }
+            previous = Some(&entry.category);
+            rank += 1;
+            TopDisplay {
+                rank,
+                category: entry.category.clone(),
+                name: entry.name.clone(),
+                chaos_value: entry.chaos_value,
+                divine_value: entry
+                    .divine_value
+                    .or_else(|| divine_rate.map(|rate| pricing::chaos_to_divine(entry.chaos_value, rate)))
+                    .unwrap_or(0.0),
+                listings: entry.listings,
+                confidence: format!("{:?}", entry.confidence).to_lowercase(),
+            }
+        })
+        .collect();
+    print_rows(rows, output, value_columns(output.unit))
+}
+
+/// BaseType prices by base, item level bucket and influence
+///
+/// Lines with an unknown variant go to `warn`; without one they fail the command.
+pub async fn handle_bases(
+    source: &dyn PriceSource,
+    league: &str,
+    filters: &bases::BaseFilters,
+    output: &Output,
+    warn: Option<&WriteWarning>,
+) -> Result<()> {
+    if output.format == OutputFormat::Table {
+        println!(
+            "{} {}",
+            "Pricing bases for".bright_blue(),
+            league.bright_yellow()
+        );
+    }
+
+    let response = source
+        .get_item_overview(league, "BaseType")
+        .await
+        .context("Failed to fetch base type data")?;
+    let decoded = bases::lines_from_items(&response.lines);
+    if decoded.skipped() > 0 {
+        let variants: Vec<String> = decoded
+            .unknown_variants
+            .iter()
+            .map(|(variant, count)| format!("{} ({})", variant, count))
+            .collect();
+        let skipped = anyhow!(
+            "skipped {} BaseType lines with unknown variants: {}",
+            decoded.skipped(),
+            variants.join(", ")
+        );
+        warn_or_fail(Err(skipped), warn)?;
+    }
+    let lines: Vec<bases::BaseLine> = decoded.lines.into_iter().filter(|line| filters.allows(line)).collect();
+    let matrix = bases::matrix(&lines);
+
+    if output.format == OutputFormat::Json {
+        let report = BasesReport {
+            league,
+            matrix: &matrix,
+            unknown_variants: &decoded.unknown_variants,
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    if matrix.rows.is_empty() && output.format == OutputFormat::Table {
+        println!("{}", "No bases found with the given filters.".red());
+        return Ok(());
+    }
+
+    let mut header = vec!["Base Type".to_string(), "iLvl".to_string()];
+    header.extend(matrix.influences.iter().map(|influence| influence.label().to_string()));
+    let records = matrix.rows.iter().map(|row| {
+        let mut record = vec![row.base_type.clone(), row.ilvl.clone()];
+        record.extend(matrix.influences.iter().map(|influence| match row.cells.get(influence) {
+            Some(chaos_value) => format!("{:.1}", chaos_value),
+            None => "-".to_string(),
+        }));
+        record
+    });
+
+    if output.format == OutputFormat::Csv {
+        let mut writer = csv::Writer::from_writer(std::io::stdout());
+        writer.write_record(&header)?;
+        for record in records {
+            writer.write_record(record)?;
+        }
+        writer.flush()?;
+    } else {
+        let mut builder = Builder::default();
+        builder.push_record(header);
+        for record in records {
+            builder.push_record(record);
+        }
+        let mut table = builder.build();
+        table.with(Style::modern());
+        println!("{}", table);
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct BasesReport<'a> {
+    league: &'a str,
+    #[serde(flatten)]
+    matrix: &'a bases::Matrix,
+    unknown_variants: &'a std::collections::BTreeMap<String, usize>,
+}
+
+/// Build statistics as tables, one JSON object or long-form `section,name,value` CSV
+pub async fn handle_builds(source: &dyn PriceSource, league: &str, top: usize, output: &Output) -> Result<()> {
+    if output.format == OutputFormat::Table {
+        println!(
+            "{} {}",
+            "Fetching build statistics for".bright_blue(),
+            league.bright_yellow()
+        );
+    }
+
+    let leagues = source.get_leagues().await.unwrap_or_default();
+    let overview = source
+        .get_build_overview(&builds::league_url(&leagues, league))
+        .await
+        .context("Failed to fetch build data")?;
+
+    let usage_rows = |usage: Vec<builds::Usage>| -> Vec<UsageDisplay> {
+        usage
+            .into_iter()
+            .take(top)
+            .map(|usage| UsageDisplay {
+                name: usage.name,
+                characters: usage.characters,
+                share: format!("{:.1}%", usage.percent),
+            })
+            .collect()
+    };
+    let sections = [
+        ("class", "Classes:", usage_rows(builds::class_shares(&overview))),
+        ("ascendancy", "Ascendancies:", usage_rows(builds::ascendancy_shares(&overview))),
+        ("skill", "Skills:", usage_rows(builds::skill_usage(&overview))),
+        ("keystone", "Keystones:", usage_rows(builds::keystone_usage(&overview))),
+    ];
+
+    let used: Vec<builds::Usage> = builds::unique_item_usage(&overview).into_iter().take(top).collect();
+    let loaded = builds::load_price_book(source, league, &used).await;
+    for (category, err) in &loaded.skipped {
+        eprintln!("{} {}: {:#}", "Skipped".yellow(), category, err);
+    }
+    let divine_rate = loaded.book.divine_rate();
+
+    let uniques: Vec<UniqueDemandDisplay> = builds::demand_vs_price(&used, &loaded.book)
+        .into_iter()
+        .map(|demand| UniqueDemandDisplay {
+            name: demand.usage.name,
+            share: format!("{:.1}%", demand.usage.percent),
+            category: demand.category.unwrap_or_else(|| "-".to_string()),
+            chaos_value: demand
+                .chaos_value
+                .map(|value| format!("{:.1}", value))
+                .unwrap_or_else(|| "-".to_string()),
+            divine_value: demand
+                .chaos_value
+                .zip(divine_rate)
+                .map(|(value, rate)| format!("{:.2}", pricing::chaos_to_divine(value, rate)))
+                .unwrap_or_else(|| "-".to_string()),
+        })
+        .collect();
+    let hidden = value_columns(output.unit);
+
+    match output.format {
+        OutputFormat::Json => {
+            let [classes, ascendancies, skills, keystones] = &sections;
+            let report = BuildsReport {
+                league,
+                characters: overview.classes.len(),
+                classes: &classes.2,
+                ascendancies: &ascendancies.2,
+                skills: &skills.2,
+                keystones: &keystones.2,
+                uniques: json_rows(&uniques, hidden)?,
+            };
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        OutputFormat::Csv => {
+            let mut rows: Vec<StatRow> = vec![StatRow::new("summary", "Characters", overview.classes.len().to_string())];
+            for (section, _, usage) in &sections {
+                rows.extend(usage.iter().map(|row| StatRow::new(section, &row.name, row.share.clone())));
+            }
+            rows.extend(uniques.iter().map(|row| {
+                let value = match output.unit {
+                    Unit::Chaos => &row.chaos_value,
+                    Unit::Divine => &row.divine_value,
+                };
+                StatRow::new("unique", &row.name, value.clone())
+            }));
+            print_rows(rows, output, &[])?;
+        }
+        OutputFormat::Table => {
+            println!("{} {}", "Characters sampled:".bright_green(), overview.classes.len());
+            for (_, title, usage) in sections {
+                println!();
+                println!("{}", title.bright_blue());
+                print_rows(usage, output, &[])?;
+            }
+
+            println!();
+            println!("{}", "Uniques (demand vs price):".bright_blue());
+            print_rows(uniques, output, hidden)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct BuildsReport<'a> {
+    league: &'a str,
+    characters: usize,
+    classes: &'a [UsageDisplay],
+    ascendancies: &'a [UsageDisplay],
+    skills: &'a [UsageDisplay],
+    keystones: &'a [UsageDisplay],
+    uniques: Vec<serde_json::Value>,
+}
+
+fn write_trade_links(out: &mut impl Write, links: impl IntoIterator<Item = (String, Option<trade::TradeLink>)>) -> Result<()> {
+    let links: Vec<(String, trade::TradeLink)> =
+        links.into_iter().filter_map(|(name, link)| Some((name, link?))).collect();
+    if links.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out)?;
+    writeln!(out, "{}", "Trade Links:".bright_green())?;
+    for (name, link) in links {
+        writeln!(out, "  • {}: {}", name.bright_yellow(), link.url)?;
+    }
+    Ok(())
+}
+
+pub async fn handle_serve(source: Arc<dyn PriceSource>, bind: &str, ttl: u64) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(bind)
+        .await
+        .with_context(|| format!("Failed to bind {}", bind))?;
+
+    eprintln!(
+        "{} {}",
+        "Serving prices on".bright_blue(),
+        format!("http://{}", listener.local_addr()?).bright_yellow()
+    );
+
+    let state = server::ServerState::new(source, std::time::Duration::from_secs(ttl));
+    server::serve(listener, state).await
+}
+
+pub async fn handle_exporter(
+    source: Arc<dyn PriceSource>,
+    config: &Path,
+    bind: &str,
+    interval: Option<u64>,
+) -> Result<()> {
+    let mut config = exporter::ExporterConfig::load(config)?;
+    if let Some(interval) = interval {
+        config.interval = interval;
+    }
+
+    let listener = tokio::net::TcpListener::bind(bind)
+        .await
+        .with_context(|| format!("Failed to bind {}", bind))?;
+
+    eprintln!(
+        "{} {} {}",
+        "Exporting".bright_blue(),
+        format!("{} categories", config.categories.len()).bright_yellow(),
+        format!("on http://{}/metrics every {}s", listener.local_addr()?, config.interval).bright_blue()
+    );
+
+    exporter::run(listener, source, config).await
+}
+
+pub async fn handle_watchlist(
+    source: &dyn PriceSource,
+    action: &WatchlistAction,
+    path: &Path,
+    settings: &Settings,
+) -> Result<()> {
+    let mut config = Config::load(path)?;
+    match action {
+        WatchlistAction::Show { name, league } => {
+            let items = config
+                .watchlists
+                .get(name)
+                .with_context(|| format!("No watchlist named '{}' in {}", name, path.display()))?;
+            let league = settings.league(league.as_deref());
+            let output = settings.output();
+            let state = watchlist::state_path(path, name, source.game(), &league);
+            let last = watchlist::LastView::load(&state)?;
+
+            if output.format == OutputFormat::Table {
+                println!(
+                    "{} {}",
+                    "Pricing watchlist".bright_blue(),
+                    format!("{} - {} - {} entries", name, league, items.len()).bright_yellow()
+                );
+                if last.viewed_at > 0 {
+                    let hours = watchlist::now().saturating_sub(last.viewed_at) as f64 / 3600.0;
+                    println!("{}", format!("Changes since the last view {:.1}h ago", hours).dimmed());
+                }
+            }
+
+            let book = watchlist::load_price_book(source, &league, items).await?;
+            let rows = watchlist::price_watchlist(&book, items, &last);
+            let divine_rate = book.divine_rate();
+
+            let display: Vec<WatchDisplay> = rows
+                .iter()
+                .filter_map(|row| {
+                    let price = row.price.as_ref()?;
+                    Some(WatchDisplay {
+                        name: price.name.clone(),
+                        category: price.category.clone(),
+                        variant: match (&row.item.variant, row.item.links) {
+                            (Some(variant), _) => variant.clone(),
+                            (None, Some(links)) => format!("{}L", links),
+                            (None, None) => String::new(),
+                        },
+                        chaos_value: price.chaos_value,
+                        divine_value: divine_rate
+                            .map(|rate| pricing::chaos_to_divine(price.chaos_value, rate))
+                            .unwrap_or(0.0),
+                        previous_chaos: row
+                            .previous
+                            .map(|value| format!("{:.1}", value))
+                            .unwrap_or_else(|| "-".to_string()),
+                        change: row
+                            .change_percent()
+                            .map(|change| format!("{:+.1}%", change))
+                            .unwrap_or_else(|| "-".to_string()),
+                    })
+                })
+                .collect();
+            print_rows(display, &output, value_columns(output.unit))?;
+
+            let unpriced: Vec<&watchlist::WatchRow> = rows.iter().filter(|row| row.price.is_none()).collect();
+            if !unpriced.is_empty() {
+                eprintln!();
+                eprintln!("{}", "Could not price:".red());
+                for row in unpriced {
+                    eprintln!("  • {} ({})", row.item.name.bright_yellow(), row.item.category);
+                }
+            }
+
+            watchlist::LastView::from_rows(&rows).save(&state)?;
+        }
+        WatchlistAction::List => {
+            if config.watchlists.is_empty() {
+                println!("{}", "No watchlists saved yet. Add one with `watchlist add`.".dimmed());
+            }
+            for (name, items) in &config.watchlists {
+                println!("  • {} ({} entries)", name.bright_yellow(), items.len());
+            }
+        }
+        WatchlistAction::Add {
+            list,
+            item_type,
+            name,
+            variant,
+            links,
+        } => {
+            config.watchlists.entry(list.clone()).or_default().push(watchlist::WatchItem {
+                category: item_type.clone(),
+                name: settings.expand_alias(name),
+                variant: variant.clone(),
+                links: *links,
+            });
+            config.validate()?;
+            config.save(path)?;
+            println!("{} {} to {}", "Added".bright_green(), name.bright_yellow(), list);
+        }
+        WatchlistAction::Remove { list, name } => {
+            let name = settings.expand_alias(name);
+            let items = config
+                .watchlists
+                .get_mut(list)
+                .with_context(|| format!("No watchlist named '{}'", list))?;
+            let before = items.len();
+            items.retain(|item| !item.name.eq_ignore_ascii_case(&name));
+            if items.len() == before {
+                anyhow::bail!("'{}' is not on watchlist '{}'", name, list);
+            }
+            if items.is_empty() {
+                config.watchlists.remove(list);
+            }
+            config.save(path)?;
+            println!("{} {} from {}", "Removed".bright_green(), name.bright_yellow(), list);
+        }
+    }
+    Ok(())
+}
+
+/// Price JSON-line queries from `input` (stdin when `None` or `-`) and print NDJSON
+pub async fn handle_batch(
+    source: &dyn PriceSource,
+    league: &str,
+    input: Option<&Path>,
+    settings: &Settings,
+) -> Result<()> {
+    let queries = match input {
+        Some(path) if path != Path::new("-") => {
+            let file = std::fs::File::open(path)
+                .with_context(|| format!("Failed to open batch file '{}'", path.display()))?;
+            watchlist::read_batch(std::io::BufReader::new(file))?
+        }
+        _ => watchlist::read_batch(std::io::stdin().lock())?,
+    };
+
+    let queries = queries
+        .into_iter()
+        .map(|(line, query)| {
+            let query = query.map(|mut query| {
+                query.name = settings.expand_alias(&query.name);
+                query
+            });
+            (line, query)
+        })
+        .collect();
+
+    for result in watchlist::run_batch(source, league, queries).await {
+        println!("{}", serde_json::to_string(&result)?);
+    }
+    Ok(())
+}
+
+/// Fetch raw overviews and report every field that drifted from the model.
+/// With `strict`, any drift makes the command fail.
+pub async fn handle_schema_check(
+    source: &dyn PriceSource,
+    league: &str,
+    item_types: &[String],
+    strict: bool,
+) -> Result<()> {
+    let types: Vec<String> = if item_types.is_empty() {
+        get_currency_types()
+            .into_iter()
+            .chain(get_item_types())
+            .map(str::to_string)
+            .collect()
+    } else {
+        item_types.to_vec()
+    };
+
+    println!(
+        "{} {}",
+        "Checking response schemas for".bright_blue(),
+        format!("{} - {} types", league, types.len()).bright_yellow()
+    );
+
+    let mut rows = Vec::new();
+    let mut failures = Vec::new();
+    for overview_type in &types {
+        let report = match source.get_raw_overview(league, overview_type).await {
+            Ok(body) => schema::check_overview(overview_type, &body),
+            Err(err) => Err(err),
+        };
+        let report = match report {
+            Ok(report) => report,
+            Err(err) => {
+                failures.push((overview_type.clone(), format!("{:#}", err)));
+                continue;
+            }
+        };
+
+        for drift in &report.drift {
+            rows.push(DriftDisplay {
+                overview_type: overview_type.clone(),
+                path: drift.path.clone(),
+                kind: drift.kind.to_string(),
+                lines: format!("{}/{}", drift.lines, report.total_lines),
+                example: drift.example.clone().unwrap_or_default(),
+            });
+        }
+        for skipped in &report.skipped {
+            rows.push(DriftDisplay {
+                overview_type: overview_type.clone(),
+                path: format!("{}[{}]", skipped.array, skipped.index),
+                kind: format!("skipped: {}", skipped.error),
+                lines: "1".to_string(),
+                example: skipped.name.clone().unwrap_or_default(),
+            });
+        }
+    }
+
+    if rows.is_empty() {
+        println!("{}", "No drift: every response matches the expected model.".green());
+    } else {
+        let mut table = Table::new(&rows);
+        table.with(Style::modern());
+        println!("{}", table);
+    }
+
+    if !failures.is_empty() {
+        println!();
+        println!("{}", "Could not check:".red());
+        for (overview_type, error) in &failures {
+            println!("  • {}: {}", overview_type.bright_yellow(), error);
+        }
+    }
+
+    if strict && !(rows.is_empty() && failures.is_empty()) {
+        anyhow::bail!("Schema drift found in {} fields", rows.len() + failures.len());
+    }
+    Ok(())
+}
+
+/// `config show|set|validate` against the config file at `path`
+pub fn handle_config(action: &ConfigAction, path: &Path, cli: &Profile, profile: Option<&str>) -> Result<()> {
+    match action {
+        ConfigAction::Show => {
+            let config = Config::load(path)?;
+            println!("{} {}", "Config file:".bright_blue(), path.display().to_string().bright_yellow());
+            let content = toml::to_string_pretty(&config)?;
+            if content.trim().is_empty() {
+                println!("{}", "(empty)".dimmed());
+            } else {
+                println!("{}", content.trim_end());
+            }
+
+            let settings = Settings::resolve(&config, cli, profile, |key| std::env::var(key).ok())?;
+            println!();
+            println!("{}", "Effective settings:".bright_green());
+            println!("  league    = {}", settings.league.bright_yellow());
+            println!("  format    = {:?}", settings.format);
+            println!("  unit      = {:?}", settings.unit);
+            println!(
+                "  cache_ttl = {}",
+                settings.cache_ttl.map(|ttl| format!("{}s", ttl)).unwrap_or_else(|| "-".to_string())
+            );
+            println!("  base_url  = {}", settings.base_url.as_deref().unwrap_or("-"));
+            println!("  snapshots = {}", settings.snapshots);
+            for (alias, target) in &settings.aliases {
+                println!("  alias {} = {}", alias.bright_yellow(), target);
+            }
+        }
+        ConfigAction::Set { key, value } => {
+            let mut config = Config::load(path)?;
+            config.set(key, value)?;
+            config.save(path)?;
+            println!("{} {} = {} in {}", "Set".bright_green(), key.bright_yellow(), value, path.display());
+        }
+        ConfigAction::Validate => {
+            let config = Config::load(path)?;
+            println!(
+                "{} {} is valid ({} profiles, {} aliases)",
+                "✓".bright_green(),
+                path.display(),
+                config.profiles.len(),
+                config.aliases.len()
+            );
+        }
+    }
+    Ok(())
+}
+
+pub async fn handle_leagues(source: &dyn PriceSource) -> Result<()> {
+    println!("{} {}", "Available League Names for".bright_green(), source.game().to_string().bright_green());
+    println!();
+
+    match source.get_leagues().await {
+        Ok(leagues) => {
+            for league in leagues {
+                println!("  • {}", league.name.bright_yellow());
+            }
+        }
+        Err(e) => {
+            eprintln!("{} {}", "Could not fetch live leagues, showing known ones:".yellow(), e);
+            for league in get_leagues_for(source.game()) {
+                println!("  • {}", league.bright_yellow());
+            }
+        }
+    }
+    
+    println!();
+    println!("{}", "Note: League names are case-sensitive. Use exact names as shown above.".dimmed());
+    Ok(())
+}
+
+pub async fn handle_types(game: Game) -> Result<()> {
+    println!("{}", "Available Data Types:".bright_green());
+    println!();
+
+    if game == Game::Poe2 {
+        println!("{}", "Exchange Types (use with currency or item):".bright_blue());
+        for overview_type in get_poe2_exchange_types() {
+            println!("  • {}", overview_type.bright_yellow());
+        }
+
+        println!();
+        println!("{}", "Note: Type names are case-sensitive. Use exact names as shown above.".dimmed());
+        return Ok(());
+    }
+    
+    println!("{}", "Currency Types:".bright_blue());
+    let currency_types = get_currency_types();
+    for currency_type in currency_types {
+        println!("  • {}", currency_type.bright_yellow());
+    }
+    
+    println!();
+    println!("{}", "Item Types:".bright_blue());
+    let item_types = get_item_types();
+    for item_type in item_types {
+        println!("  • {}", item_type.bright_yellow());
+    }
+    
+    println!();
+    println!("{}", "Note: Type names are case-sensitive. Use exact names as shown above.".dimmed());
+    Ok(())
+}
+
+fn four_places(value: &f64) -> String {
+    format!("{:.4}", value)
+}
+
+fn two_places(value: &f64) -> String {
+    format!("{:.2}", value)
+}
+
+fn one_place(value: &f64) -> String {
+    format!("{:.1}", value)
+}
+
+fn optional_text(value: &Option<String>) -> String {
+    value.clone().unwrap_or_default()
+}
+
+fn optional_value(value: &Option<f64>) -> String {
+    value.map(|value| format!("{:.1}", value)).unwrap_or_else(|| "-".to_string())
+}
+
+fn optional_change(value: &Option<f64>) -> String {
+    value.map(|value| format!("{:+.1}", value)).unwrap_or_else(|| "-".to_string())
+}
+
+fn optional_percent(value: &Option<f64>) -> String {
+    value.map(|value| format!("{:+.1}%", value)).unwrap_or_else(|| "-".to_string())
+}
+
+fn optional_signed<T: std::fmt::Display + Default + PartialOrd>(value: &Option<T>) -> String {
+    match value {
+        Some(value) if *value > T::default() => format!("+{}", value),
+        Some(value) => value.to_string(),
+        None => "-".to_string(),
+    }
+}
+
+#[derive(Tabled, Serialize)]
+struct CurrencyDisplay {
+    #[tabled(rename = "Currency")]
+    name: String,
+    #[tabled(rename = "Chaos Value")]
+    chaos_value: f64,
+    #[tabled(rename = "Divine Value", display_with = "four_places")]
+    divine_value: f64,
+    #[tabled(rename = "Pay Value")]
+    pay_value: f64,
+    #[tabled(rename = "Receive Value")]
+    receive_value: f64,
+    #[tabled(rename = "Pay Count")]
+    pay_count: i32,
+    #[tabled(rename = "Receive Count")]
+    receive_count: i32,
+    #[tabled(rename = "Trade URL", display_with = "optional_text")]
+    trade_url: Option<String>,
+}
+
+#[derive(Tabled, Serialize)]
+struct ExchangeDisplay {
+    #[tabled(rename = "Name")]
+    name: String,
+    #[tabled(rename = "Divine Value")]
+    divine_value: String,
+    #[tabled(rename = "Exalted Value")]
+    exalted_value: String,
+    #[tabled(rename = "Chaos Value")]
+    chaos_value: String,
+    #[tabled(rename = "Volume (div)")]
+    volume: String,
+    #[tabled(rename = "7d Change")]
+    change: String,
+}
+
+#[derive(Tabled, Serialize)]
+struct ItemDisplay {
+    #[tabled(rename = "Item Name")]
+    name: String,
+    #[tabled(rename = "Base Type")]
+    base_type: String,
+    #[tabled(rename = "Chaos Value")]
+    chaos_value: f64,
+    #[tabled(rename = "Divine Value")]
+    divine_value: f64,
+    #[tabled(rename = "Count")]
+    count: i32,
+    #[tabled(rename = "Listings")]
+    listing_count: i32,
+    #[tabled(rename = "Level")]
+    level_required: i32,
+    #[tabled(rename = "Trade URL", display_with = "optional_text")]
+    trade_url: Option<String>,
+}
+#[derive(Tabled, Serialize)]
+struct WatchDisplay {
+    #[tabled(rename = "Name")]
+    name: String,
+    #[tabled(rename = "Category")]
+    category: String,
+    #[tabled(rename = "Variant")]
+    variant: String,
+    #[tabled(rename = "Chaos Value")]
+    chaos_value: f64,
+    #[tabled(rename = "Divine Value", display_with = "four_places")]
+    divine_value: f64,
+    #[tabled(rename = "Last Chaos")]
+    previous_chaos: String,
+    #[tabled(rename = "Change")]
+    change: String,
+}
+
+#[derive(Tabled)]
+struct DriftDisplay {
+    #[tabled(rename = "Type")]
+    overview_type: String,
+    #[tabled(rename = "Field")]
+    path: String,
+    #[tabled(rename = "Drift")]
+    kind: String,
+    #[tabled(rename = "Lines")]
+    lines: String,
+    #[tabled(rename = "Example")]
+    example: String,
+}
+
+#[derive(Tabled, Serialize)]
+struct PortfolioDisplay {
+    #[tabled(rename = "Name")]
+    name: String,
+    #[tabled(rename = "Category")]
+    category: String,
+    #[tabled(rename = "Qty")]
+    quantity: u32,
+    #[tabled(rename = "Unit Chaos")]
+    unit_chaos: f64,
+    #[tabled(rename = "Total Chaos")]
+    total_chaos: f64,
+    #[tabled(rename = "Total Divine")]
+    total_divine: f64,
+}
+
+#[derive(Tabled, Serialize)]
+struct StashDisplay {
+    #[tabled(rename = "Name")]
+    name: String,
+    #[tabled(rename = "Category")]
+    category: String,
+    #[tabled(rename = "Qty")]
+    quantity: u32,
+    #[tabled(rename = "Unit Chaos")]
+    unit_chaos: f64,
+    #[tabled(rename = "Total Chaos")]
+    total_chaos: f64,
+    #[tabled(rename = "Total Divine")]
+    total_divine: f64,
+}
+
+#[derive(Tabled, Serialize)]
+struct DivCardDisplay {
+    #[tabled(rename = "Card")]
+    card: String,
+    #[tabled(rename = "Reward")]
+    reward: String,
+    #[tabled(rename = "Stack")]
+    stack_size: u32,
+    #[tabled(rename = "Set Cost")]
+    set_cost: f64,
+    #[tabled(rename = "Reward Value")]
+    reward_value: f64,
+    #[tabled(rename = "Profit")]
+    profit: f64,
+    #[tabled(rename = "Margin")]
+    margin: String,
+}
+
+#[derive(Tabled, Serialize)]
+struct TradeDisplay {
+    #[tabled(rename = "Name")]
+    name: String,
+    #[tabled(rename = "Category")]
+    category: String,
+    #[tabled(rename = "URL")]
+    url: String,
+}
+
+#[derive(Tabled, Serialize)]
+struct FilterBlockDisplay {
+    #[tabled(rename = "Header")]
+    header: String,
+    #[tabled(rename = "Bases")]
+    bases: usize,
+}
+
+#[derive(Tabled, Serialize)]
+struct GemDisplay {
+    #[tabled(rename = "Gem")]
+    name: String,
+    #[tabled(rename = "Strategy")]
+    strategy: String,
+    #[tabled(rename = "From")]
+    from: String,
+    #[tabled(rename = "To")]
+    to: String,
+    #[tabled(rename = "Cost")]
+    cost: f64,
+    #[tabled(rename = "Value")]
+    value: f64,
+    #[tabled(rename = "Profit")]
+    profit: f64,
+    #[tabled(rename = "Margin")]
+    margin: String,
+}
+
+#[derive(Tabled, Serialize)]
+struct UsageDisplay {
+    #[tabled(rename = "Name")]
+    name: String,
+    #[tabled(rename = "Characters")]
+    characters: usize,
+    #[tabled(rename = "Share")]
+    share: String,
+}
+
+#[derive(Tabled, Serialize)]
+struct UniqueDemandDisplay {
+    #[tabled(rename = "Unique")]
+    name: String,
+    #[tabled(rename = "Used By")]
+    share: String,
+    #[tabled(rename = "Category")]
+    category: String,
+    #[tabled(rename = "Chaos Value")]
+    chaos_value: String,
+    #[tabled(rename = "Divine Value")]
+    divine_value: String,
+}
+
+#[derive(Tabled, Serialize)]
+struct MetricDisplay {
+    #[tabled(rename = "Metric")]
+    metric: String,
+    #[tabled(rename = "Value")]
+    value: String,
+}
+
+impl MetricDisplay {
+    fn new(metric: &str, value: String) -> Self {
+        Self {
+            metric: metric.to_string(),
+            value,
+        }
+    }
+}
+
+#[derive(Tabled, Serialize)]
+struct BucketDisplay {
+    #[tabled(rename = "Change")]
+    range: String,
+    #[tabled(rename = "Entries")]
+    count: usize,
+}
+
+#[derive(Tabled, Serialize)]
+struct MoverDisplay {
+    #[tabled(rename = "Name")]
+    name: String,
+    #[tabled(rename = "Chaos Value")]
+    chaos_value: f64,
+    #[tabled(rename = "Change")]
+    change: String,
+}
+
+#[derive(Tabled, Serialize)]
+struct StatRow {
+    #[tabled(rename = "Section")]
+    section: String,
+    #[tabled(rename = "Name")]
+    name: String,
+    #[tabled(rename = "Value")]
+    value: String,
+}
+
+impl StatRow {
+    fn new(section: &str, name: &str, value: String) -> Self {
+        Self {
+            section: section.to_string(),
+            name: name.to_string(),
+            value,
+        }
+    }
+}
+
+#[derive(Tabled, Serialize)]
+struct DiffDisplay {
+    #[tabled(rename = "Status")]
+    status: String,
+    #[tabled(rename = "Name")]
+    name: String,
+    #[tabled(rename = "Details Id")]
+    details_id: String,
+    #[tabled(rename = "Before", display_with = "optional_value")]
+    before: Option<f64>,
+    #[tabled(rename = "After", display_with = "optional_value")]
+    after: Option<f64>,
+    #[tabled(rename = "Change", display_with = "optional_change")]
+    change: Option<f64>,
+    #[tabled(rename = "Change Percent", display_with = "optional_percent")]
+    change_percent: Option<f64>,
+    #[tabled(rename = "Count Change", display_with = "optional_signed")]
+    count_change: Option<i32>,
+    #[tabled(rename = "Rank Change", display_with = "optional_signed")]
+    rank_change: Option<i64>,
+}
+
+#[derive(Tabled, Serialize)]
+struct ForecastDisplay {
+    #[tabled(rename = "Day")]
+    day: usize,
+    #[tabled(rename = "Forecast", display_with = "two_places")]
+    value: f64,
+    #[tabled(rename = "Low", display_with = "two_places")]
+    low: f64,
+    #[tabled(rename = "High", display_with = "two_places")]
+    high: f64,
+}
+
+#[derive(Tabled, Serialize)]
+struct AnomalyDisplay {
+    #[tabled(rename = "Name")]
+    name: String,
+    #[tabled(rename = "Chaos Value", display_with = "two_places")]
+    chaos_value: f64,
+    #[tabled(rename = "Score", display_with = "one_place")]
+    score: f64,
+    #[tabled(rename = "Reasons")]
+    reasons: String,
+}
+
+#[derive(Tabled, Serialize)]
+struct TopDisplay {
+    #[tabled(rename = "Rank")]
+    rank: usize,
+    #[tabled(rename = "Category")]
+    category: String,
+    #[tabled(rename = "Name")]
+    name: String,
+    #[tabled(rename = "Chaos Value", display_with = "two_places")]
+    chaos_value: f64,
+    #[tabled(rename = "Divine Value", display_with = "two_places")]
+    divine_value: f64,
+    #[tabled(rename = "Listings")]
+    listings: i32,
+    #[tabled(rename = "Confidence")]
+    confidence: String,
+}
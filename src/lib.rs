@@ -1,58 +1,50 @@
-use clap::{Parser, Subcommand};
-
+pub mod anomalies;
 pub mod api;
+pub mod bases;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod builds;
+#[cfg(feature = "cli")]
+pub mod cli;
+pub mod compare;
+#[cfg(feature = "cli")]
+pub mod config;
+pub mod diff;
+pub mod divcards;
+#[cfg(feature = "server")]
+pub mod exporter;
+#[cfg(feature = "cli")]
+pub mod filter;
+pub mod forecast;
+#[cfg(feature = "cli")]
+pub mod gems;
+#[cfg(feature = "cli")]
+pub mod handlers;
+pub mod mods;
+#[cfg(feature = "cli")]
+pub mod portfolio;
+pub mod pricing;
+pub mod schema;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "cache")]
+pub mod snapshots;
+pub mod source;
+pub mod stash;
+pub mod stats;
+pub mod top;
+pub mod trade;
 pub mod types;
+pub mod watchlist;
 
-pub use api::PoeNinjaClient;
+pub use api::{Game, PoeNinjaClient};
+#[cfg(feature = "cli")]
+pub use cli::{Cli, Commands, ConfigAction, WatchlistAction};
+#[cfg(feature = "cli")]
+pub use config::{OutputFormat, Unit};
+pub use source::PriceSource;
 pub use types::*;
 
-// Export CLI types for testing
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
-#[command(name = "poe-ninja")]
-pub struct Cli {
-    #[command(subcommand)]
-    pub command: Commands,
-}
-
-#[derive(Subcommand, Debug)]
-pub enum Commands {
-    /// Get currency prices and exchange rates
-    Currency {
-        /// League name (e.g., "Settlers", "Standard", "Hardcore")
-        #[arg(short, long, default_value = "Standard")]
-        league: String,
-        /// Currency type (Currency, Fragment)
-        #[arg(short, long, default_value = "Currency")]
-        currency_type: String,
-        /// Filter by currency name
-        #[arg(short, long)]
-        name: Option<String>,
-    },
-    /// Get item prices
-    Item {
-        /// League name
-        #[arg(short, long, default_value = "Standard")]
-        league: String,
-        /// Item type (Oil, Essence, UniqueWeapon, UniqueArmour, etc.)
-        #[arg(short, long)]
-        item_type: String,
-        /// Filter by item name
-        #[arg(short, long)]
-        name: Option<String>,
-        /// Minimum chaos value filter
-        #[arg(long)]
-        min_chaos: Option<f64>,
-        /// Maximum chaos value filter  
-        #[arg(long)]
-        max_chaos: Option<f64>,
-    },
-    /// List available leagues
-    Leagues,
-    /// List available item types
-    Types,
-}
-
 // Extract business logic into testable functions
 pub fn filter_currencies_by_name(
     currencies: Vec<types::CurrencyLine>,
@@ -101,13 +93,66 @@ pub fn filter_items_by_criteria(
 }
 
 pub fn sort_currencies_by_value(mut currencies: Vec<types::CurrencyLine>) -> Vec<types::CurrencyLine> {
-    currencies.sort_by(|a, b| b.chaos_equivalent.partial_cmp(&a.chaos_equivalent).unwrap());
+    currencies.sort_by(|a, b| b.chaos_equivalent.unwrap_or(0.0).total_cmp(&a.chaos_equivalent.unwrap_or(0.0)));
     currencies
 }
 
 pub fn sort_items_by_value(mut items: Vec<types::ItemLine>) -> Vec<types::ItemLine> {
-    items.sort_by(|a, b| b.chaos_value.partial_cmp(&a.chaos_value).unwrap());
+    items.sort_by(|a, b| b.chaos_value.total_cmp(&a.chaos_value));
+    items
+}
+
+/// PoE2 exchange lines matching a name filter and a chaos value range, most
+/// valuable first. Chaos values come from the overview's own exchange rates.
+pub fn filter_exchange_lines<'a>(
+    response: &'a types::Poe2ExchangeOverviewResponse,
+    name_filter: Option<&str>,
+    min_chaos: Option<f64>,
+    max_chaos: Option<f64>,
+) -> Vec<&'a types::Poe2ExchangeLine> {
+    let chaos = |line: &types::Poe2ExchangeLine| response.value_in(line, "chaos").unwrap_or(0.0);
+
+    let mut lines: Vec<_> = response
+        .lines
+        .iter()
+        .filter(|line| {
+            name_filter.is_none_or(|filter| {
+                response.item_info(&line.id).is_some_and(|info| {
+                    info.name.to_lowercase().contains(&filter.to_lowercase())
+                })
+            })
+        })
+        .filter(|line| min_chaos.is_none_or(|min| chaos(line) >= min))
+        .filter(|line| max_chaos.is_none_or(|max| chaos(line) <= max))
+        .collect();
+
+    lines.sort_by(|a, b| b.primary_value.total_cmp(&a.primary_value));
+    lines
+}
+
+/// Find a currency by exact name, falling back to the first partial match
+pub fn find_currency_by_name<'a>(
+    currencies: &'a [types::CurrencyLine],
+    name: &str,
+) -> Option<&'a types::CurrencyLine> {
+    let needle = name.to_lowercase();
+    currencies
+        .iter()
+        .find(|currency| currency.currency_type_name.to_lowercase() == needle)
+        .or_else(|| {
+            currencies
+                .iter()
+                .find(|currency| currency.currency_type_name.to_lowercase().contains(&needle))
+        })
+}
+
+/// Find an item by exact name, falling back to the first partial match
+pub fn find_item_by_name<'a>(items: &'a [types::ItemLine], name: &str) -> Option<&'a types::ItemLine> {
+    let needle = name.to_lowercase();
     items
+        .iter()
+        .find(|item| item.name.to_lowercase() == needle)
+        .or_else(|| items.iter().find(|item| item.name.to_lowercase().contains(&needle)))
 }
 
 // Helper for getting league list
@@ -122,6 +167,17 @@ pub fn get_available_leagues() -> Vec<&'static str> {
     ]
 }
 
+pub fn get_poe2_leagues() -> Vec<&'static str> {
+    vec!["Standard", "Hardcore", "Rise of the Abyssal", "HC Rise of the Abyssal"]
+}
+
+pub fn get_leagues_for(game: Game) -> Vec<&'static str> {
+    match game {
+        Game::Poe1 => get_available_leagues(),
+        Game::Poe2 => get_poe2_leagues(),
+    }
+}
+
 // Helper for getting item types
 pub fn get_currency_types() -> Vec<&'static str> {
     vec!["Currency", "Fragment"]
@@ -138,6 +194,15 @@ pub fn get_item_types() -> Vec<&'static str> {
     ]
 }
 
+/// PoE2 prices everything through the currency exchange, one overview type per tab
+pub fn get_poe2_exchange_types() -> Vec<&'static str> {
+    vec![
+        "Currency", "Fragments", "Runes", "Essences", "SoulCores", "Idols",
+        "Ritual", "Expedition", "Delirium", "Breach", "Abyss", "UncutGems",
+        "LineageSupportGems",
+    ]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -155,6 +220,7 @@ mod tests {
                 low_confidence_pay_spark_line: types::SparkLine { data: None, total_change: None },
                 low_confidence_receive_spark_line: types::SparkLine { data: None, total_change: None },
                 details_id: "exalted-orb".to_string(),
+                extras: Default::default(),
             },
             types::CurrencyLine {
                 currency_type_name: "Chaos Orb".to_string(),
@@ -166,6 +232,7 @@ mod tests {
                 low_confidence_pay_spark_line: types::SparkLine { data: None, total_change: None },
                 low_confidence_receive_spark_line: types::SparkLine { data: None, total_change: None },
                 details_id: "chaos-orb".to_string(),
+                extras: Default::default(),
             },
         ];
 
@@ -174,6 +241,47 @@ mod tests {
         assert_eq!(filtered[0].currency_type_name, "Exalted Orb");
     }
 
+    #[test]
+    fn test_find_currency_by_name_prefers_exact_match() {
+        let currency = |name: &str| types::CurrencyLine {
+            currency_type_name: name.to_string(),
+            ..Default::default()
+        };
+        let currencies = vec![currency("Tainted Chaos Orb"), currency("Chaos Orb")];
+
+        let exact = find_currency_by_name(&currencies, "chaos orb").unwrap();
+        assert_eq!(exact.currency_type_name, "Chaos Orb");
+        let partial = find_currency_by_name(&currencies, "tainted").unwrap();
+        assert_eq!(partial.currency_type_name, "Tainted Chaos Orb");
+        assert!(find_currency_by_name(&currencies, "divine").is_none());
+    }
+
+    #[test]
+    fn test_sorting_tolerates_nan() {
+        let currency = |name: &str, chaos: Option<f64>| types::CurrencyLine {
+            currency_type_name: name.to_string(),
+            chaos_equivalent: chaos,
+            ..Default::default()
+        };
+        let currencies = sort_currencies_by_value(vec![
+            currency("Chaos Orb", Some(1.0)),
+            currency("Broken", Some(f64::NAN)),
+            currency("Unpriced", None),
+            currency("Divine Orb", Some(200.0)),
+        ]);
+        assert_eq!(currencies[1].currency_type_name, "Divine Orb");
+        assert_eq!(currencies[3].currency_type_name, "Unpriced");
+
+        let item = |name: &str, chaos_value: f64| types::ItemLine {
+            name: name.to_string(),
+            chaos_value,
+            ..Default::default()
+        };
+        let items = sort_items_by_value(vec![item("Cheap", 1.0), item("Broken", f64::NAN), item("Dear", 50.0)]);
+        assert_eq!(items[1].name, "Dear");
+        assert_eq!(items[2].name, "Cheap");
+    }
+
     #[test]
     fn test_filter_items_by_price_range() {
         let items = vec![
@@ -204,6 +312,7 @@ mod tests {
                 listing_count: None,
                 links: None,
                 trade_info: None,
+                extras: Default::default(),
             },
             types::ItemLine {
                 id: 2,
@@ -232,6 +341,7 @@ mod tests {
                 listing_count: None,
                 links: None,
                 trade_info: None,
+                extras: Default::default(),
             },
         ];
 
@@ -248,6 +358,27 @@ mod tests {
         assert!(leagues.contains(&"Hardcore"));
     }
 
+    #[test]
+    fn test_filter_exchange_lines() {
+        let response: types::Poe2ExchangeOverviewResponse = serde_json::from_str(include_str!(
+            "../tests/fixtures/poe2/exchange_overview_currency.json"
+        ))
+        .unwrap();
+
+        let all = filter_exchange_lines(&response, None, None, None);
+        assert_eq!(all.len(), 4);
+        assert_eq!(all[0].id, "mirror");
+
+        // 27.1 chaos per divine: annul is ~2.47c, chaos itself ~1c
+        let mid = filter_exchange_lines(&response, None, Some(2.0), Some(100.0));
+        let ids: Vec<_> = mid.iter().map(|line| line.id.as_str()).collect();
+        assert_eq!(ids, vec!["divine", "annul"]);
+
+        let named = filter_exchange_lines(&response, Some("orb of"), None, None);
+        assert_eq!(named.len(), 1);
+        assert_eq!(named[0].id, "annul");
+    }
+
     #[test]
     fn test_item_types_comprehensive() {
         let types = get_item_types();
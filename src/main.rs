@@ -1,22 +1,105 @@
-use anyhow::{Context, Result};
-use colored::*;
-use tabled::{settings::Style, Table, Tabled};
+use anyhow::Result;
 use clap::Parser;
 
-use poe_ninja_cli::{Cli, Commands, PoeNinjaClient, filter_currencies_by_name, filter_items_by_criteria, sort_currencies_by_value, sort_items_by_value, get_available_leagues, get_item_types, get_currency_types};
+use anyhow::Context;
+use poe_ninja_cli::api::{select_transport, RequestKey, WriteWarning};
+use poe_ninja_cli::config::{config_path, Config, Profile, Settings};
+use poe_ninja_cli::bases::BaseFilters;
+use poe_ninja_cli::diff::Thresholds;
+use poe_ninja_cli::handlers::*;
+use poe_ninja_cli::source::{LocalDirSource, PriceSource};
+use poe_ninja_cli::mods::ModFilter;
+use poe_ninja_cli::top;
+use poe_ninja_cli::{Cli, Commands, Game, PoeNinjaClient};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
-    let client = PoeNinjaClient::new();
+    let overrides = Profile {
+        format: cli.format,
+        unit: cli.unit,
+        ..Profile::default()
+    };
+    // Without HOME or XDG_CONFIG_HOME only the commands that edit or read the
+    // file itself need one; everything else runs on the defaults
+    let path = config_path();
+
+    if let Commands::Config { action } = &cli.command {
+        let path = path.context("No config directory available")?;
+        return handle_config(action, &path, &overrides, cli.profile.as_deref());
+    }
+
+    let config = match &path {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    let settings = Settings::resolve(&config, &overrides, cli.profile.as_deref(), |key| std::env::var(key).ok())?;
+
+    let warn: WriteWarning = Arc::new(|err: &anyhow::Error| eprintln!("Warning: {:#}", err));
+    let mut client = None;
+    let shared: Arc<dyn PriceSource> = match &cli.data_dir {
+        Some(dir) => Arc::new(LocalDirSource::new(dir)),
+        None => {
+            let transport = select_transport(
+                cli.offline,
+                cli.record.as_deref(),
+                cli.replay.as_deref(),
+                settings.cache_ttl,
+                settings.snapshots,
+                warn.clone(),
+            )?;
+            let mut live = PoeNinjaClient::new()
+                .with_game(cli.game)
+                .with_transport(transport)
+                .with_strict(cli.strict);
+            if let Some(base_url) = &settings.base_url {
+                live = live.with_base_url(base_url);
+            }
+            let live = Arc::new(live);
+            client = Some(live.clone());
+            live
+        }
+    };
+
+    let result = run(&cli, &settings, path.as_deref(), shared, &warn).await;
+    if let Some(skipped) = client.map(|client| client.take_skipped()).filter(|skipped| !skipped.is_empty()) {
+        eprintln!(
+            "Warning: skipped {} overview lines that did not match the expected model (see `schema-check`)",
+            skipped.len()
+        );
+    }
+    result
+}
+
+async fn run(
+    cli: &Cli,
+    settings: &Settings,
+    path: Option<&Path>,
+    shared: Arc<dyn PriceSource>,
+    warn: &WriteWarning,
+) -> Result<()> {
+    let output = settings.output();
+    let alias = |name: &Option<String>| name.as_deref().map(|name| settings.expand_alias(name));
+    let source = shared.as_ref();
 
     match &cli.command {
         Commands::Currency {
             league,
             currency_type,
             name,
+            trade_link,
         } => {
-            handle_currency(&client, &league, &currency_type, name.as_deref()).await?;
+            let league = settings.league(league.as_deref());
+            let name = alias(name);
+            if cli.game == Game::Poe2 {
+                require_poe1_trade_links(*trade_link)?;
+                handle_exchange(source, &league, currency_type, name.as_deref(), None, None, &output).await?;
+            } else {
+                handle_currency(source, &league, currency_type, name.as_deref(), *trade_link, &output).await?;
+            }
         }
         Commands::Item {
             league,
@@ -24,178 +107,218 @@ async fn main() -> Result<()> {
             name,
             min_chaos,
             max_chaos,
+            mods,
+            show_mods,
+            trade_link,
         } => {
-            handle_item(&client, &league, &item_type, name.as_deref(), *min_chaos, *max_chaos)
+            let league = settings.league(league.as_deref());
+            let name = alias(name);
+            if cli.game == Game::Poe2 {
+                require_poe1_trade_links(*trade_link)?;
+                if !mods.is_empty() || *show_mods {
+                    anyhow::bail!("Modifiers are only available for Path of Exile 1");
+                }
+                handle_exchange(source, &league, item_type, name.as_deref(), *min_chaos, *max_chaos, &output)
+                    .await?;
+            } else {
+                let mod_filters = mods.iter().map(|filter| ModFilter::parse(filter)).collect::<Result<Vec<_>>>()?;
+                handle_item(
+                    source,
+                    &league,
+                    item_type,
+                    name.as_deref(),
+                    *min_chaos,
+                    *max_chaos,
+                    &mod_filters,
+                    *show_mods,
+                    *trade_link,
+                    &output,
+                )
                 .await?;
+            }
+        }
+        Commands::Trade {
+            league,
+            item_type,
+            name,
+            have,
+        } => {
+            let name = settings.expand_alias(name);
+            handle_trade(source, &settings.league(league.as_deref()), item_type, &name, have, &output).await?;
+        }
+        Commands::Portfolio { file, league } => {
+            handle_portfolio(source, &settings.league(league.as_deref()), file, &output).await?;
+        }
+        Commands::StashValue { file, league, top } => {
+            handle_stash_value(source, &settings.league(league.as_deref()), file, *top, &output).await?;
         }
+        Commands::Divcards {
+            league,
+            name,
+            min_profit,
+            all,
+        } => {
+            let name = alias(name);
+            let league = settings.league(league.as_deref());
+            handle_divcards(source, &league, name.as_deref(), *min_profit, *all, &output).await?;
+        }
+        Commands::Gems {
+            league,
+            name,
+            sort,
+            strategy,
+            outcomes,
+            min_profit,
+        } => {
+            let name = alias(name);
+            handle_gems(
+                source,
+                &settings.league(league.as_deref()),
+                name.as_deref(),
+                sort,
+                strategy.as_deref(),
+                outcomes.as_deref(),
+                *min_profit,
+                &output,
+            )
+            .await?;
+        }
+        Commands::Filter {
+            input,
+            league,
+            output: destination,
+            tiers,
+        } => {
+            let league = settings.league(league.as_deref());
+            handle_filter(source, &league, input, destination.as_deref(), tiers.as_deref(), &output).await?;
+        }
+        Commands::Compare {
+            leagues,
+            item_type,
+            name,
+            min_diff_percent,
+        } => {
+            let name = alias(name);
+            handle_compare(source, leagues, item_type, name.as_deref(), *min_diff_percent, &output).await?;
+        }
+        Commands::Stats {
+            league,
+            item_type,
+            name,
+            min_chaos,
+            max_chaos,
+            top,
+        } => {
+            let name = alias(name);
+            let league = settings.league(league.as_deref());
+            handle_stats(source, &league, item_type, name.as_deref(), *min_chaos, *max_chaos, *top, &output).await?;
+        }
+        Commands::Diff {
+            before,
+            after,
+            league,
+            item_type,
+            min_chaos_change,
+            min_percent,
+        } => {
+            let key = item_type
+                .as_deref()
+                .map(|item_type| RequestKey::overview(cli.game, &settings.league(league.as_deref()), item_type));
+            let thresholds = Thresholds {
+                min_chaos: *min_chaos_change,
+                min_percent: *min_percent,
+            };
+            handle_diff(before, after, key.as_ref(), &thresholds, &output)?;
+        }
+        Commands::Forecast {
+            league,
+            item_type,
+            name,
+            days,
+            model,
+            history,
+            threshold,
+        } => {
+            let name = settings.expand_alias(name);
+            let league = settings.league(league.as_deref());
+            handle_forecast(source, &league, item_type, &name, *days, *model, *history, *threshold, &output).await?;
+        }
+        Commands::Anomalies {
+            league,
+            item_type,
+            min_score,
+        } => {
+            handle_anomalies(source, &settings.league(league.as_deref()), item_type, *min_score, &output).await?;
+        }
+        Commands::Top {
+            league,
+            item_types,
+            count,
+            per_category,
+            min_confidence,
+            exclude,
+            exclude_category,
+            exclude_corrupted,
+            max_chaos,
+            delay,
+        } => {
+            let exclusions = top::Exclusions {
+                min_confidence: *min_confidence,
+                names: exclude.clone(),
+                categories: exclude_category.clone(),
+                corrupted: *exclude_corrupted,
+                max_chaos: *max_chaos,
+            };
+            let league = settings.league(league.as_deref());
+            let delay = Duration::from_millis(*delay);
+            handle_top(source, &league, item_types, *count, *per_category, &exclusions, delay, &output).await?;
+        }
+        Commands::Bases {
+            league,
+            name,
+            min_ilvl,
+            influence,
+        } => {
+            let filters = BaseFilters {
+                name: alias(name),
+                min_ilvl: *min_ilvl,
+                influences: influence.clone(),
+            };
+            let warn = (!cli.strict).then_some(warn);
+            handle_bases(source, &settings.league(league.as_deref()), &filters, &output, warn).await?;
+        }
+        Commands::Builds { league, top } => {
+            handle_builds(source, &settings.league(league.as_deref()), *top, &output).await?;
+        }
+        Commands::Serve { bind, ttl } => {
+            let ttl = ttl.or(settings.cache_ttl).unwrap_or(300);
+            handle_serve(shared.clone(), bind, ttl).await?;
+        }
+        Commands::Exporter {
+            config,
+            bind,
+            interval,
+        } => {
+            handle_exporter(shared.clone(), config, bind, *interval).await?;
+        }
+        Commands::Watchlist { action } => {
+            let path = path.context("No config directory available for watchlists")?;
+            handle_watchlist(source, action, path, settings).await?;
+        }
+        Commands::Batch { input, league } => {
+            handle_batch(source, &settings.league(league.as_deref()), input.as_deref(), settings).await?;
+        }
+        Commands::SchemaCheck { league, item_types } => {
+            handle_schema_check(source, &settings.league(league.as_deref()), item_types, cli.strict).await?;
+        }
+        Commands::Config { .. } => unreachable!("handled before the price source is built"),
         Commands::Leagues => {
-            handle_leagues().await?;
+            handle_leagues(source).await?;
         }
         Commands::Types => {
-            handle_types().await?;
+            handle_types(cli.game).await?;
         }
     }
 
     Ok(())
 }
-
-async fn handle_currency(
-    client: &PoeNinjaClient,
-    league: &str,
-    currency_type: &str,
-    name_filter: Option<&str>,
-) -> Result<()> {
-    println!(
-        "{} {}",
-        "Fetching currency data for".bright_blue(),
-        format!("{} - {}", league, currency_type).bright_yellow()
-    );
-
-    let response = client
-        .get_currency_overview(league, currency_type)
-        .await
-        .context("Failed to fetch currency data")?;
-
-    let filtered_currencies = filter_currencies_by_name(response.lines, name_filter);
-    let sorted_currencies = sort_currencies_by_value(filtered_currencies);
-
-    let currencies: Vec<CurrencyDisplay> = sorted_currencies
-        .into_iter()
-        .map(|currency| CurrencyDisplay {
-            name: currency.currency_type_name,
-            chaos_equivalent: currency.chaos_equivalent.unwrap_or(0.0),
-            pay_value: currency.pay.as_ref().map(|p| p.value).unwrap_or(0.0),
-            receive_value: currency.receive.as_ref().map(|r| r.value).unwrap_or(0.0),
-            pay_count: currency.pay.as_ref().map(|p| p.count).unwrap_or(0),
-            receive_count: currency.receive.as_ref().map(|r| r.count).unwrap_or(0),
-        })
-        .collect();
-
-    if currencies.is_empty() {
-        println!("{}", "No currencies found with the given filters.".red());
-        return Ok(());
-    }
-
-    let mut table = Table::new(currencies);
-    table.with(Style::modern());
-    println!("{}", table);
-
-    Ok(())
-}
-
-async fn handle_item(
-    client: &PoeNinjaClient,
-    league: &str,
-    item_type: &str,
-    name_filter: Option<&str>,
-    min_chaos: Option<f64>,
-    max_chaos: Option<f64>,
-) -> Result<()> {
-    println!(
-        "{} {}",
-        "Fetching item data for".bright_blue(),
-        format!("{} - {}", league, item_type).bright_yellow()
-    );
-
-    let response = client
-        .get_item_overview(league, item_type)
-        .await
-        .context("Failed to fetch item data")?;
-
-    let filtered_items = filter_items_by_criteria(response.lines, name_filter, min_chaos, max_chaos);
-    let sorted_items = sort_items_by_value(filtered_items);
-
-    let items: Vec<ItemDisplay> = sorted_items
-        .into_iter()
-        .map(|item| ItemDisplay {
-            name: item.name,
-            base_type: item.base_type.unwrap_or_else(|| "N/A".to_string()),
-            chaos_value: item.chaos_value,
-            divine_value: item.divine_value.unwrap_or(0.0),
-            count: item.count,
-            listing_count: item.listing_count.unwrap_or(0),
-            level_required: item.level_required.unwrap_or(0),
-        })
-        .collect();
-
-    if items.is_empty() {
-        println!("{}", "No items found with the given filters.".red());
-        return Ok(());
-    }
-
-    let mut table = Table::new(items);
-    table.with(Style::modern());
-    println!("{}", table);
-
-    Ok(())
-}
-
-async fn handle_leagues() -> Result<()> {
-    println!("{}", "Available League Names:".bright_green());
-    println!();
-    
-    let leagues = get_available_leagues();
-    for league in leagues {
-        println!("  • {}", league.bright_yellow());
-    }
-    
-    println!();
-    println!("{}", "Note: League names are case-sensitive. Use exact names as shown above.".dimmed());
-    Ok(())
-}
-
-async fn handle_types() -> Result<()> {
-    println!("{}", "Available Data Types:".bright_green());
-    println!();
-    
-    println!("{}", "Currency Types:".bright_blue());
-    let currency_types = get_currency_types();
-    for currency_type in currency_types {
-        println!("  • {}", currency_type.bright_yellow());
-    }
-    
-    println!();
-    println!("{}", "Item Types:".bright_blue());
-    let item_types = get_item_types();
-    for item_type in item_types {
-        println!("  • {}", item_type.bright_yellow());
-    }
-    
-    println!();
-    println!("{}", "Note: Type names are case-sensitive. Use exact names as shown above.".dimmed());
-    Ok(())
-}
-
-#[derive(Tabled)]
-struct CurrencyDisplay {
-    #[tabled(rename = "Currency")]
-    name: String,
-    #[tabled(rename = "Chaos Value")]
-    chaos_equivalent: f64,
-    #[tabled(rename = "Pay Value")]
-    pay_value: f64,
-    #[tabled(rename = "Receive Value")]
-    receive_value: f64,
-    #[tabled(rename = "Pay Count")]
-    pay_count: i32,
-    #[tabled(rename = "Receive Count")]
-    receive_count: i32,
-}
-
-#[derive(Tabled)]
-struct ItemDisplay {
-    #[tabled(rename = "Item Name")]
-    name: String,
-    #[tabled(rename = "Base Type")]
-    base_type: String,
-    #[tabled(rename = "Chaos Value")]
-    chaos_value: f64,
-    #[tabled(rename = "Divine Value")]
-    divine_value: f64,
-    #[tabled(rename = "Count")]
-    count: i32,
-    #[tabled(rename = "Listings")]
-    listing_count: i32,
-    #[tabled(rename = "Level")]
-    level_required: i32,
-}
\ No newline at end of file
@@ -0,0 +1,229 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Serialize;
+use std::sync::OnceLock;
+
+use crate::types::*;
+
+/// A number as poe.ninja writes it in modifier text
+const NUMBER: &str = r"-?\d+(?:\.\d+)?";
+
+/// A plain number or a rolled range such as `(60-80)`
+fn value_pattern() -> String {
+    format!(r"(?:\(\s*({n})\s*(?:-|–|to)\s*({n})\s*\)|({n}))", n = NUMBER)
+}
+
+fn value_regex() -> &'static Regex {
+    static VALUE: OnceLock<Regex> = OnceLock::new();
+    VALUE.get_or_init(|| Regex::new(&value_pattern()).expect("valid regex"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    AtLeast,
+    AtMost,
+    Above,
+    Below,
+    Equal,
+}
+
+impl Comparison {
+    fn parse(operator: &str) -> Option<Self> {
+        match operator {
+            ">=" => Some(Self::AtLeast),
+            "<=" => Some(Self::AtMost),
+            ">" => Some(Self::Above),
+            "<" => Some(Self::Below),
+            "=" | "==" => Some(Self::Equal),
+            _ => None,
+        }
+    }
+
+    /// Whether some roll between `low` and `high` meets the condition
+    pub fn allows(self, low: f64, high: f64, value: f64) -> bool {
+        match self {
+            Self::AtLeast => high >= value,
+            Self::Above => high > value,
+            Self::AtMost => low <= value,
+            Self::Below => low < value,
+            Self::Equal => low <= value && value <= high,
+        }
+    }
+}
+
+/// One `--mod` filter: a case-insensitive regex over modifier text, optionally followed
+/// by a numeric condition such as `>= 80`. In a pattern containing `#`, everything else
+/// is literal and `#` stands for a number or a rolled range, so `+(# to #) to maximum Life`
+/// and `+# to maximum Life` both match `+(80-100) to maximum Life`.
+#[derive(Debug, Clone)]
+pub struct ModFilter {
+    pattern: Regex,
+    condition: Option<(Comparison, f64)>,
+}
+
+impl ModFilter {
+    pub fn parse(input: &str) -> Result<Self> {
+        let condition_re = Regex::new(&format!(r"^(.*?)\s*(>=|<=|==|>|<|=)\s*({})\s*$", NUMBER)).expect("valid regex");
+        let (text, condition) = match condition_re.captures(input) {
+            Some(captures) => {
+                let comparison = Comparison::parse(&captures[2]).expect("operator from the regex");
+                let value: f64 = captures[3].parse().expect("number from the regex");
+                (captures[1].to_string(), Some((comparison, value)))
+            }
+            None => (input.to_string(), None),
+        };
+        if text.trim().is_empty() {
+            anyhow::bail!("Modifier filter '{}' has no text to match", input);
+        }
+
+        let source = if text.contains('#') {
+            let placeholder_range = Regex::new(r"\(\s*#\s*(?:-|–|to)\s*#\s*\)").expect("valid regex");
+            let text = placeholder_range.replace_all(text.trim(), "#");
+            text.split('#').map(regex::escape).collect::<Vec<_>>().join(&value_pattern())
+        } else {
+            text.trim().to_string()
+        };
+        let pattern = Regex::new(&format!("(?i){}", source))
+            .with_context(|| format!("Invalid modifier filter '{}'", input))?;
+        Ok(Self { pattern, condition })
+    }
+
+    /// Whether one modifier line matches. The condition applies to the first number or range
+    /// in the matched part of the line, or in the whole line when the match has none.
+    pub fn matches_text(&self, text: &str) -> bool {
+        let Some(found) = self.pattern.find(text) else {
+            return false;
+        };
+        let Some((comparison, value)) = self.condition else {
+            return true;
+        };
+        match roll_range(found.as_str()).or_else(|| roll_range(text)) {
+            Some((low, high)) => comparison.allows(low, high, value),
+            None => false,
+        }
+    }
+
+    /// Whether any implicit or explicit modifier of `item` matches
+    pub fn matches(&self, item: &ItemLine) -> bool {
+        modifiers(item).any(|(_, modifier)| self.matches_text(&modifier.text))
+    }
+}
+
+/// The first number or rolled range in `text`, as (low, high)
+pub fn roll_range(text: &str) -> Option<(f64, f64)> {
+    let captures = value_regex().captures(text)?;
+    let number = |index: usize| captures.get(index).and_then(|found| found.as_str().parse::<f64>().ok());
+    match (number(1), number(2), number(3)) {
+        (Some(low), Some(high), _) => Some((low.min(high), low.max(high))),
+        (_, _, Some(value)) => Some((value, value)),
+        _ => None,
+    }
+}
+
+/// Items whose modifiers satisfy every filter
+pub fn filter_items_by_mods(items: Vec<ItemLine>, filters: &[ModFilter]) -> Vec<ItemLine> {
+    items
+        .into_iter()
+        .filter(|item| filters.iter().all(|filter| filter.matches(item)))
+        .collect()
+}
+
+/// Implicit modifiers first, each paired with whether it is implicit
+fn modifiers(item: &ItemLine) -> impl Iterator<Item = (bool, &Modifier)> {
+    item.implicit_modifiers
+        .iter()
+        .map(|modifier| (true, modifier))
+        .chain(item.explicit_modifiers.iter().map(|modifier| (false, modifier)))
+}
+
+/// A modifier as listed under an item
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModLine {
+    pub text: String,
+    pub implicit: bool,
+    pub optional: bool,
+}
+
+impl ModLine {
+    /// `text`, tagged when implicit or optional
+    pub fn label(&self) -> String {
+        let mut label = self.text.clone();
+        if self.implicit {
+            label = format!("{} (implicit)", label);
+        }
+        if self.optional {
+            label = format!("{} (optional)", label);
+        }
+        label
+    }
+}
+
+pub fn mod_lines(item: &ItemLine) -> Vec<ModLine> {
+    modifiers(item)
+        .map(|(implicit, modifier)| ModLine {
+            text: modifier.text.clone(),
+            implicit,
+            optional: modifier.optional,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(filter: &str, text: &str) -> bool {
+        ModFilter::parse(filter).unwrap().matches_text(text)
+    }
+
+    #[test]
+    fn test_roll_range() {
+        assert_eq!(roll_range("+(80-100) to maximum Life"), Some((80.0, 100.0)));
+        assert_eq!(roll_range("(0.4 to 0.8)% of Physical Attack Damage Leeched as Life"), Some((0.4, 0.8)));
+        assert_eq!(roll_range("-4 Physical Damage taken from Attack Hits"), Some((-4.0, -4.0)));
+        assert_eq!(roll_range("Has 1 Abyssal Socket"), Some((1.0, 1.0)));
+        assert_eq!(roll_range("Cannot be Frozen"), None);
+    }
+
+    #[test]
+    fn test_placeholders_and_conditions() {
+        let life = "+(80-100) to maximum Life";
+        assert!(matches("+(# to #) to maximum Life >= 80", life));
+        assert!(matches("+# to maximum Life >= 100", life));
+        assert!(!matches("+# to maximum Life > 100", life));
+        assert!(matches("+# TO MAXIMUM LIFE <= 80", life));
+        assert!(!matches("+# to maximum Life < 80", life));
+        assert!(matches("+# to maximum Life = 90", life));
+        assert!(matches("+# to maximum Life", "+70 to maximum Life"));
+        assert!(!matches("+# to maximum Mana", life));
+    }
+
+    #[test]
+    fn test_regex_filters() {
+        assert!(matches("maximum (life|mana)", "+(40-60) to maximum Mana"));
+        assert!(matches("maximum life >= 50", "+(40-60) to maximum Life"));
+        assert!(!matches("^adds", "+(40-60) to maximum Life"));
+        assert!(!matches("Cannot be Frozen > 1", "Cannot be Frozen"));
+        assert!(ModFilter::parse("maximum (life").is_err());
+        assert!(ModFilter::parse(">= 80").is_err());
+    }
+
+    #[test]
+    fn test_item_filter_and_lines() {
+        let item: ItemLine = serde_json::from_str(
+            r#"{"id": 1, "name": "Precursor's Emblem", "chaosValue": 12.0, "detailsId": "precursors-emblem",
+                "implicitModifiers": [{"text": "+(8-10) to all Attributes", "optional": false}],
+                "explicitModifiers": [{"text": "+(20-30) to maximum Life", "optional": true}]}"#,
+        )
+        .unwrap();
+        let keep = |filters: &[&str]| {
+            let filters: Vec<ModFilter> = filters.iter().map(|filter| ModFilter::parse(filter).unwrap()).collect();
+            filter_items_by_mods(vec![item.clone()], &filters).len() == 1
+        };
+        assert!(keep(&["all Attributes", "maximum Life >= 25"]));
+        assert!(!keep(&["all Attributes", "maximum Life >= 40"]));
+
+        let labels: Vec<String> = mod_lines(&item).iter().map(ModLine::label).collect();
+        assert_eq!(labels, vec!["+(8-10) to all Attributes (implicit)", "+(20-30) to maximum Life (optional)"]);
+    }
+}
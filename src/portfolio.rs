@@ -0,0 +1,325 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::source::PriceSource;
+use crate::pricing::{chaos_to_divine, LoadedBook, PriceBook, PriceMatch, PriceQuery};
+
+/// One entry of a holdings file
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Holding {
+    pub name: String,
+    #[serde(default = "default_quantity")]
+    pub quantity: u32,
+    /// Overview type to price against; searched in order when omitted
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub variant: Option<String>,
+    #[serde(default)]
+    pub links: Option<i32>,
+}
+
+fn default_quantity() -> u32 {
+    1
+}
+
+impl Holding {
+    pub fn query(&self) -> PriceQuery<'_> {
+        PriceQuery {
+            name: &self.name,
+            category: self.category.as_deref(),
+            variant: self.variant.as_deref(),
+            links: self.links,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HoldingsFormat {
+    Toml,
+    Csv,
+    Json,
+}
+
+impl HoldingsFormat {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("toml") => Ok(Self::Toml),
+            Some("csv") => Ok(Self::Csv),
+            Some("json") => Ok(Self::Json),
+            _ => Err(anyhow!(
+                "Unsupported holdings file '{}': expected .toml, .csv or .json",
+                path.display()
+            )),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct HoldingsFile {
+    #[serde(default, alias = "holding")]
+    holdings: Vec<Holding>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonHoldings {
+    List(Vec<Holding>),
+    File(HoldingsFile),
+}
+
+pub fn parse_holdings(content: &str, format: HoldingsFormat) -> Result<Vec<Holding>> {
+    match format {
+        HoldingsFormat::Toml => {
+            let file: HoldingsFile = toml::from_str(content).context("Failed to parse TOML holdings")?;
+            Ok(file.holdings)
+        }
+        HoldingsFormat::Json => {
+            let holdings: JsonHoldings =
+                serde_json::from_str(content).context("Failed to parse JSON holdings")?;
+            Ok(match holdings {
+                JsonHoldings::List(list) => list,
+                JsonHoldings::File(file) => file.holdings,
+            })
+        }
+        HoldingsFormat::Csv => {
+            let mut reader = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(content.as_bytes());
+            reader
+                .deserialize()
+                .enumerate()
+                .map(|(index, row)| {
+                    row.with_context(|| format!("Failed to parse CSV holdings row {}", index + 1))
+                })
+                .collect()
+        }
+    }
+}
+
+pub fn load_holdings(path: &Path) -> Result<Vec<Holding>> {
+    let format = HoldingsFormat::from_path(path)?;
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read holdings file '{}'", path.display()))?;
+    parse_holdings(&content, format)
+}
+
+/// Categories an entry without a category can be in, in search order: only
+/// weapons and body armours have links, and currency lines have no variants
+pub fn search_order(holding: &Holding) -> Vec<&'static str> {
+    if holding.links.is_some() {
+        return vec!["UniqueWeapon", "UniqueArmour"];
+    }
+    let item_types = crate::get_item_types();
+    if holding.variant.is_some() {
+        return item_types;
+    }
+    crate::get_currency_types().into_iter().chain(item_types).collect()
+}
+
+/// Fetch every category the holdings need; entries without a category are
+/// searched through their `search_order` until they resolve
+pub async fn load_price_book(
+    source: &dyn PriceSource,
+    league: &str,
+    holdings: &[Holding],
+) -> LoadedBook {
+    let mut loaded = LoadedBook::default();
+    loaded.ensure(source, league, "Currency").await;
+
+    for holding in holdings {
+        if let Some(category) = &holding.category {
+            loaded.ensure(source, league, category).await;
+        }
+    }
+
+    for holding in holdings.iter().filter(|h| h.category.is_none()) {
+        for category in search_order(holding) {
+            if loaded.book.lookup(&holding.query()).is_some() {
+                break;
+            }
+            loaded.ensure(source, league, category).await;
+        }
+    }
+
+    loaded
+}
+
+#[derive(Debug, Clone)]
+pub struct PortfolioLine {
+    pub holding: Holding,
+    pub price: PriceMatch,
+    pub total_chaos: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Valuation {
+    pub lines: Vec<PortfolioLine>,
+    pub unpriced: Vec<Holding>,
+    pub total_chaos: f64,
+    pub divine_rate: Option<f64>,
+}
+
+impl Valuation {
+    pub fn total_divine(&self) -> Option<f64> {
+        self.divine_rate.map(|rate| chaos_to_divine(self.total_chaos, rate))
+    }
+}
+
+/// Price every holding, most valuable lines first
+pub fn value_holdings(book: &PriceBook, holdings: &[Holding]) -> Valuation {
+    let mut lines = Vec::new();
+    let mut unpriced = Vec::new();
+
+    for holding in holdings {
+        match book.lookup(&holding.query()) {
+            Some(price) => {
+                let total_chaos = price.chaos_value * holding.quantity as f64;
+                lines.push(PortfolioLine {
+                    holding: holding.clone(),
+                    price,
+                    total_chaos,
+                });
+            }
+            None => unpriced.push(holding.clone()),
+        }
+    }
+
+    lines.sort_by(|a, b| b.total_chaos.total_cmp(&a.total_chaos));
+    let total_chaos = lines.iter().map(|line| line.total_chaos).sum();
+
+    Valuation {
+        lines,
+        unpriced,
+        total_chaos,
+        divine_rate: book.divine_rate(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::*;
+
+    fn currency(name: &str, chaos: f64) -> CurrencyLine {
+        CurrencyLine {
+            currency_type_name: name.to_string(),
+            chaos_equivalent: Some(chaos),
+            details_id: crate::pricing::slugify(name),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_toml_holdings() {
+        let content = r#"
+            [[holdings]]
+            name = "Divine Orb"
+            quantity = 12
+
+            [[holdings]]
+            name = "Tabula Rasa"
+            category = "UniqueArmour"
+            links = 6
+        "#;
+
+        let holdings = parse_holdings(content, HoldingsFormat::Toml).unwrap();
+        assert_eq!(holdings.len(), 2);
+        assert_eq!(holdings[0].quantity, 12);
+        assert_eq!(holdings[1].quantity, 1);
+        assert_eq!(holdings[1].category.as_deref(), Some("UniqueArmour"));
+        assert_eq!(holdings[1].links, Some(6));
+    }
+
+    #[test]
+    fn test_parse_csv_holdings() {
+        let content = "name,quantity,category,variant,links\n\
+                       Divine Orb,3,,,\n\
+                       Watcher's Eye, 1 ,UniqueJewel,2 Jewels,\n";
+
+        let holdings = parse_holdings(content, HoldingsFormat::Csv).unwrap();
+        assert_eq!(holdings.len(), 2);
+        assert_eq!(holdings[0].category, None);
+        assert_eq!(holdings[1].quantity, 1);
+        assert_eq!(holdings[1].variant.as_deref(), Some("2 Jewels"));
+    }
+
+    #[test]
+    fn test_parse_json_holdings() {
+        let list = r#"[{"name": "Exalted Orb", "quantity": 40}]"#;
+        let wrapped = r#"{"holdings": [{"name": "Exalted Orb", "quantity": 40}]}"#;
+
+        assert_eq!(
+            parse_holdings(list, HoldingsFormat::Json).unwrap(),
+            parse_holdings(wrapped, HoldingsFormat::Json).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(HoldingsFormat::from_path(Path::new("bank.TOML")).unwrap(), HoldingsFormat::Toml);
+        assert!(HoldingsFormat::from_path(Path::new("bank.txt")).is_err());
+    }
+
+    #[test]
+    fn test_value_holdings() {
+        let mut book = PriceBook::new();
+        book.insert_currencies(
+            "Currency",
+            vec![currency("Divine Orb", 200.0), currency("Exalted Orb", 20.0)],
+        );
+
+        let holdings = vec![
+            Holding { name: "Exalted Orb".to_string(), quantity: 10, category: None, variant: None, links: None },
+            Holding { name: "Divine Orb".to_string(), quantity: 2, category: None, variant: None, links: None },
+            Holding { name: "Mystery Box".to_string(), quantity: 1, category: None, variant: None, links: None },
+        ];
+
+        let valuation = value_holdings(&book, &holdings);
+        assert_eq!(valuation.lines.len(), 2);
+        assert_eq!(valuation.lines[0].price.name, "Divine Orb");
+        assert_eq!(valuation.total_chaos, 600.0);
+        assert_eq!(valuation.total_divine(), Some(3.0));
+        assert_eq!(valuation.unpriced.len(), 1);
+        assert_eq!(valuation.unpriced[0].name, "Mystery Box");
+    }
+
+    #[tokio::test]
+    async fn test_failed_categories_leave_holdings_unpriced() {
+        let source = crate::source::MockSource::new().with_currencies(
+            "Standard",
+            "Currency",
+            CurrencyOverviewResponse {
+                lines: vec![currency("Divine Orb", 200.0)],
+                currency_details: vec![],
+            },
+        );
+        let holding = |name: &str, category: Option<&str>, links: Option<i32>| Holding {
+            name: name.to_string(),
+            quantity: 1,
+            category: category.map(str::to_string),
+            variant: None,
+            links,
+        };
+        let holdings = vec![
+            holding("Divine Orb", None, None),
+            holding("Mageblood", Some("UniqueAccessory"), None),
+            holding("Tabula Rasa", None, Some(6)),
+        ];
+
+        let loaded = load_price_book(&source, "Standard", &holdings).await;
+        let skipped: Vec<&str> = loaded.skipped.iter().map(|(category, _)| category.as_str()).collect();
+        assert_eq!(skipped, vec!["UniqueAccessory", "UniqueWeapon", "UniqueArmour"]);
+
+        let valuation = value_holdings(&loaded.book, &holdings);
+        assert_eq!(valuation.total_chaos, 200.0);
+        assert_eq!(valuation.unpriced.len(), 2);
+    }
+}
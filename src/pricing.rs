@@ -0,0 +1,414 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+
+use crate::source::PriceSource;
+use crate::types::*;
+
+pub const CHAOS_ORB: &str = "Chaos Orb";
+pub const DIVINE_ORB: &str = "Divine Orb";
+
+/// Overview types that hold unique items
+pub const UNIQUE_CATEGORIES: &[&str] = &[
+    "UniqueWeapon", "UniqueArmour", "UniqueAccessory", "UniqueJewel",
+    "UniqueFlask", "UniqueMap", "UniqueRelic",
+];
+
+/// Overview types that hold stackable currency-like items
+pub const CURRENCY_LIKE_CATEGORIES: &[&str] = &[
+    "Currency", "Fragment", "Oil", "Fossil", "Resonator", "Essence", "Scarab",
+    "DeliriumOrb", "Omen", "Incubator", "Vials", "Invitation", "Memory",
+    "Coffin", "AllflameEmber",
+];
+
+/// Chaos value of one Divine Orb, taken from a currency overview
+pub fn divine_rate(currencies: &[CurrencyLine]) -> Option<f64> {
+    currencies
+        .iter()
+        .find(|currency| currency.currency_type_name == DIVINE_ORB)
+        .and_then(|currency| currency.chaos_equivalent)
+        .filter(|rate| *rate > 0.0)
+}
+
+/// Convert a chaos amount into divines at the given rate
+pub fn chaos_to_divine(chaos: f64, divine_rate: f64) -> f64 {
+    if divine_rate > 0.0 {
+        chaos / divine_rate
+    } else {
+        0.0
+    }
+}
+
+/// Convert `amount` of one currency into another, given both chaos values
+pub fn convert(amount: f64, from_chaos_value: f64, to_chaos_value: f64) -> Option<f64> {
+    if to_chaos_value > 0.0 {
+        Some(amount * from_chaos_value / to_chaos_value)
+    } else {
+        None
+    }
+}
+
+/// Turn a display name into the slug poe.ninja uses for `details_id`
+pub fn slugify(name: &str) -> String {
+    let mut slug = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if c == '\'' {
+            continue;
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_matches('-').to_string()
+}
+
+/// Words poe.ninja appends to a `details_id` for relics and influenced bases
+const VARIANT_WORDS: &[&str] = &[
+    "relic", "shaper", "elder", "crusader", "hunter", "redeemer", "warlord", "synthesised",
+];
+
+/// Whether one `-`-separated part of a `details_id` suffix names a variant:
+/// links (`6l`), a gem level or quality (`20`, `20c`), an item level or a
+/// word from `VARIANT_WORDS`
+fn is_variant_part(part: &str) -> bool {
+    let digits = part.trim_end_matches(['l', 'c']);
+    let numeric = !digits.is_empty()
+        && digits.len() + 1 >= part.len()
+        && digits.chars().all(|c| c.is_ascii_digit());
+    numeric || VARIANT_WORDS.contains(&part)
+}
+
+/// Whether a `details_id` belongs to the given name: the full slug, optionally
+/// followed by variant parts such as `-6l`, `-relic` or `-20-20c`
+pub fn details_id_matches(details_id: &str, name: &str) -> bool {
+    let slug = slugify(name);
+    !slug.is_empty()
+        && (details_id == slug
+            || details_id
+                .strip_prefix(&slug)
+                .and_then(|rest| rest.strip_prefix('-'))
+                .is_some_and(|rest| rest.split('-').all(is_variant_part)))
+}
+
+/// What to look up in a `PriceBook`
+#[derive(Debug, Clone, Default)]
+pub struct PriceQuery<'a> {
+    pub name: &'a str,
+    pub category: Option<&'a str>,
+    pub variant: Option<&'a str>,
+    pub links: Option<i32>,
+    pub gem_level: Option<i32>,
+    pub gem_quality: Option<i32>,
+    pub corrupted: Option<bool>,
+}
+
+impl<'a> PriceQuery<'a> {
+    pub fn named(name: &'a str) -> Self {
+        Self {
+            name,
+            ..Default::default()
+        }
+    }
+}
+
+/// A resolved price for a query
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceMatch {
+    pub name: String,
+    pub category: String,
+    pub details_id: String,
+    pub chaos_value: f64,
+}
+
+/// Overview lines for several categories of one league, indexed by category
+#[derive(Debug, Default)]
+pub struct PriceBook {
+    currencies: HashMap<String, Vec<CurrencyLine>>,
+    items: HashMap<String, Vec<ItemLine>>,
+}
+
+impl PriceBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert_currencies(&mut self, category: &str, lines: Vec<CurrencyLine>) {
+        self.currencies.insert(category.to_string(), lines);
+    }
+
+    pub fn insert_items(&mut self, category: &str, lines: Vec<ItemLine>) {
+        self.items.insert(category.to_string(), lines);
+    }
+
+    pub fn items(&self, category: &str) -> Option<&[ItemLine]> {
+        self.items.get(category).map(|lines| lines.as_slice())
+    }
+
+    pub fn currencies(&self, category: &str) -> Option<&[CurrencyLine]> {
+        self.currencies.get(category).map(|lines| lines.as_slice())
+    }
+
+    pub fn has_category(&self, category: &str) -> bool {
+        self.currencies.contains_key(category) || self.items.contains_key(category)
+    }
+
+    /// Fetch a category from poe.ninja unless it is already loaded
+    pub async fn ensure_category(
+        &mut self,
+        source: &dyn PriceSource,
+        league: &str,
+        category: &str,
+    ) -> Result<()> {
+        if self.has_category(category) {
+            return Ok(());
+        }
+
+        if crate::get_currency_types().contains(&category) {
+            let response = source
+                .get_currency_overview(league, category)
+                .await
+                .with_context(|| format!("Failed to fetch {} prices", category))?;
+            self.insert_currencies(category, response.lines);
+        } else {
+            let response = source
+                .get_item_overview(league, category)
+                .await
+                .with_context(|| format!("Failed to fetch {} prices", category))?;
+            self.insert_items(category, response.lines);
+        }
+
+        Ok(())
+    }
+
+    /// Chaos value of one Divine Orb, if the Currency category is loaded
+    pub fn divine_rate(&self) -> Option<f64> {
+        self.currencies
+            .get("Currency")
+            .and_then(|lines| divine_rate(lines))
+    }
+
+    pub fn lookup(&self, query: &PriceQuery) -> Option<PriceMatch> {
+        if query.name.eq_ignore_ascii_case(CHAOS_ORB)
+            && query.category.is_none_or(|category| category == "Currency")
+        {
+            return Some(PriceMatch {
+                name: CHAOS_ORB.to_string(),
+                category: "Currency".to_string(),
+                details_id: "chaos-orb".to_string(),
+                chaos_value: 1.0,
+            });
+        }
+
+        let wanted = |category: &str| query.category.is_none_or(|c| c == category);
+
+        let mut currency_categories: Vec<&String> =
+            self.currencies.keys().filter(|c| wanted(c)).collect();
+        currency_categories.sort();
+        for category in currency_categories {
+            let found = self.currencies[category].iter().find(|currency| {
+                currency.currency_type_name.eq_ignore_ascii_case(query.name)
+                    || details_id_matches(&currency.details_id, query.name)
+            });
+            if let Some(currency) = found {
+                return Some(PriceMatch {
+                    name: currency.currency_type_name.clone(),
+                    category: category.clone(),
+                    details_id: currency.details_id.clone(),
+                    chaos_value: currency.chaos_equivalent.unwrap_or(0.0),
+                });
+            }
+        }
+
+        let mut item_categories: Vec<&String> = self.items.keys().filter(|c| wanted(c)).collect();
+        item_categories.sort();
+        for category in item_categories {
+            if let Some(item) = best_item_match(&self.items[category], query) {
+                return Some(PriceMatch {
+                    name: item.name.clone(),
+                    category: category.clone(),
+                    details_id: item.details_id.clone(),
+                    chaos_value: item.chaos_value,
+                });
+            }
+        }
+
+        None
+    }
+
+    /// Look a query up in each category in turn, returning the first match
+    pub fn lookup_in(&self, categories: &[&str], query: &PriceQuery) -> Option<PriceMatch> {
+        categories.iter().find_map(|category| {
+            self.lookup(&PriceQuery {
+                category: Some(category),
+                ..query.clone()
+            })
+        })
+    }
+}
+
+/// A price book plus the categories that failed to load. Entries in those
+/// categories end up unpriced rather than failing the whole command.
+#[derive(Debug, Default)]
+pub struct LoadedBook {
+    pub book: PriceBook,
+    pub skipped: Vec<(String, anyhow::Error)>,
+}
+
+impl LoadedBook {
+    /// Fetch a category unless it is loaded or already failed, recording a failure
+    pub async fn ensure(&mut self, source: &dyn PriceSource, league: &str, category: &str) {
+        if self.skipped.iter().any(|(skipped, _)| skipped == category) {
+            return;
+        }
+        if let Err(err) = self.book.ensure_category(source, league, category).await {
+            self.skipped.push((category.to_string(), err));
+        }
+    }
+}
+
+/// Pick the line that matches a query, preferring the plainest variant when
+/// the query leaves links, variant or corruption unspecified
+pub fn best_item_match<'a>(items: &'a [ItemLine], query: &PriceQuery) -> Option<&'a ItemLine> {
+    items
+        .iter()
+        .filter(|item| {
+            item.name.eq_ignore_ascii_case(query.name) || details_id_matches(&item.details_id, query.name)
+        })
+        .filter(|item| match query.variant {
+            Some(variant) => item
+                .variant
+                .as_deref()
+                .is_some_and(|v| v.eq_ignore_ascii_case(variant)),
+            None => true,
+        })
+        .filter(|item| query.links.is_none() || item.links == query.links)
+        .filter(|item| query.gem_level.is_none() || item.gem_level == query.gem_level)
+        .filter(|item| query.gem_quality.is_none_or(|q| item.gem_quality.unwrap_or(0) == q))
+        .filter(|item| query.corrupted.is_none_or(|c| item.corrupted.unwrap_or(false) == c))
+        .min_by_key(|item| {
+            let mut extra = 0;
+            if query.variant.is_none() && item.variant.is_some() {
+                extra += 1;
+            }
+            if query.links.is_none() && item.links.is_some() {
+                extra += 1;
+            }
+            if query.corrupted.is_none() && item.corrupted == Some(true) {
+                extra += 1;
+            }
+            extra
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn currency(name: &str, chaos: f64) -> CurrencyLine {
+        CurrencyLine {
+            currency_type_name: name.to_string(),
+            chaos_equivalent: Some(chaos),
+            details_id: slugify(name),
+            ..Default::default()
+        }
+    }
+
+    fn item(name: &str, chaos: f64, links: Option<i32>, variant: Option<&str>) -> ItemLine {
+        ItemLine {
+            id: 1,
+            name: name.to_string(),
+            variant: variant.map(|v| v.to_string()),
+            chaos_value: chaos,
+            count: 1,
+            details_id: match links {
+                Some(l) => format!("{}-{}l", slugify(name), l),
+                None => slugify(name),
+            },
+            links,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Kaom's Heart"), "kaoms-heart");
+        assert_eq!(slugify("Orb of Alteration"), "orb-of-alteration");
+        assert_eq!(slugify("  Mjölner "), "mjölner");
+    }
+
+    #[test]
+    fn test_details_id_matches() {
+        assert!(details_id_matches("tabula-rasa", "Tabula Rasa"));
+        assert!(details_id_matches("tabula-rasa-6l", "Tabula Rasa"));
+        assert!(!details_id_matches("tabularasa", "Tabula"));
+        assert!(!details_id_matches("tabula-rasa", "Tabula"));
+        assert!(!details_id_matches("tabula-rasa-6l", "Tabula"));
+        assert!(details_id_matches("headhunter-relic", "Headhunter"));
+        assert!(details_id_matches("vaal-grace-21-20c", "Vaal Grace"));
+        assert!(details_id_matches("empower-support-4c", "Empower Support"));
+        assert!(details_id_matches("vaal-regalia-86-shaper", "Vaal Regalia"));
+        assert!(!details_id_matches("anything", ""));
+    }
+
+    #[test]
+    fn test_conversion() {
+        let currencies = vec![currency("Divine Orb", 200.0), currency("Exalted Orb", 20.0)];
+        let rate = divine_rate(&currencies).unwrap();
+
+        assert_eq!(rate, 200.0);
+        assert_eq!(chaos_to_divine(500.0, rate), 2.5);
+        assert_eq!(chaos_to_divine(500.0, 0.0), 0.0);
+        assert_eq!(convert(10.0, 20.0, 200.0), Some(1.0));
+        assert_eq!(convert(10.0, 20.0, 0.0), None);
+    }
+
+    #[test]
+    fn test_lookup_prefers_plain_variant() {
+        let mut book = PriceBook::new();
+        book.insert_items(
+            "UniqueArmour",
+            vec![
+                item("Tabula Rasa", 40.0, Some(6), None),
+                item("Tabula Rasa", 10.0, None, None),
+            ],
+        );
+
+        let plain = book.lookup(&PriceQuery::named("tabula rasa")).unwrap();
+        assert_eq!(plain.chaos_value, 10.0);
+
+        let linked = book
+            .lookup(&PriceQuery { links: Some(6), ..PriceQuery::named("Tabula Rasa") })
+            .unwrap();
+        assert_eq!(linked.chaos_value, 40.0);
+        assert_eq!(linked.details_id, "tabula-rasa-6l");
+    }
+
+    #[test]
+    fn test_lookup_currency_and_chaos() {
+        let mut book = PriceBook::new();
+        book.insert_currencies("Currency", vec![currency("Divine Orb", 180.0)]);
+
+        assert_eq!(book.divine_rate(), Some(180.0));
+        assert_eq!(book.lookup(&PriceQuery::named("Chaos Orb")).unwrap().chaos_value, 1.0);
+        assert_eq!(book.lookup(&PriceQuery::named("divine-orb")).unwrap().name, "Divine Orb");
+        assert!(book
+            .lookup(&PriceQuery { category: Some("Fragment"), ..PriceQuery::named("Divine Orb") })
+            .is_none());
+    }
+
+    #[test]
+    fn test_lookup_variant_must_match() {
+        let mut book = PriceBook::new();
+        book.insert_items(
+            "UniqueJewel",
+            vec![item("Watcher's Eye", 500.0, None, Some("2 Jewels"))],
+        );
+
+        assert!(book
+            .lookup(&PriceQuery { variant: Some("3 Jewels"), ..PriceQuery::named("Watcher's Eye") })
+            .is_none());
+        assert!(book
+            .lookup(&PriceQuery { variant: Some("2 jewels"), ..PriceQuery::named("Watcher's Eye") })
+            .is_some());
+    }
+}
@@ -0,0 +1,361 @@
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+use crate::types::*;
+
+/// An overview line that did not fit the model and was left out
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkippedLine {
+    pub array: String,
+    pub index: usize,
+    pub name: Option<String>,
+    pub error: String,
+}
+
+/// A response plus the lines left out of it
+pub type Parsed<T> = Result<(T, Vec<SkippedLine>)>;
+
+/// Remove the entries of `value[array]` that do not deserialize as `L`
+fn drop_invalid<L: DeserializeOwned>(value: &mut Value, array: &str, name_key: &str) -> Vec<SkippedLine> {
+    let Some(entries) = value.get_mut(array).and_then(Value::as_array_mut) else {
+        return Vec::new();
+    };
+
+    let mut skipped = Vec::new();
+    let mut index = 0;
+    entries.retain(|entry| {
+        let kept = match L::deserialize(entry) {
+            Ok(_) => true,
+            Err(err) => {
+                skipped.push(SkippedLine {
+                    array: array.to_string(),
+                    index,
+                    name: entry.get(name_key).and_then(Value::as_str).map(str::to_string),
+                    error: err.to_string(),
+                });
+                false
+            }
+        };
+        index += 1;
+        kept
+    });
+    skipped
+}
+
+/// Parse a currency overview, skipping lines that do not fit instead of failing
+pub fn parse_currency_overview(body: &str) -> Parsed<CurrencyOverviewResponse> {
+    let mut value: Value = serde_json::from_str(body).context("Response is not JSON")?;
+    let mut skipped = drop_invalid::<CurrencyLine>(&mut value, "lines", "currencyTypeName");
+    skipped.extend(drop_invalid::<CurrencyDetail>(&mut value, "currencyDetails", "name"));
+    Ok((serde_json::from_value(value)?, skipped))
+}
+
+/// Parse an item overview, skipping lines that do not fit instead of failing
+pub fn parse_item_overview(body: &str) -> Parsed<ItemOverviewResponse> {
+    let mut value: Value = serde_json::from_str(body).context("Response is not JSON")?;
+    let skipped = drop_invalid::<ItemLine>(&mut value, "lines", "name");
+    Ok((serde_json::from_value(value)?, skipped))
+}
+
+/// JSON type of a value, as reported in drift
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Expected keys of a JSON object, taken from a model type's default value.
+/// `Option` fields serialize as `null` there, so they are the nullable ones.
+#[derive(Debug, Clone)]
+pub struct Shape {
+    fields: BTreeMap<String, Field>,
+}
+
+#[derive(Debug, Clone)]
+struct Field {
+    nullable: bool,
+    kind: &'static str,
+    nested: Option<Shape>,
+}
+
+impl Shape {
+    pub fn of<T: Serialize + Default>() -> Shape {
+        Shape::from_value(&serde_json::to_value(T::default()).unwrap_or_default())
+    }
+
+    fn from_value(value: &Value) -> Shape {
+        let fields = value
+            .as_object()
+            .map(|object| {
+                object
+                    .iter()
+                    .map(|(key, value)| {
+                        let field = Field {
+                            nullable: value.is_null(),
+                            kind: kind(value),
+                            nested: value.is_object().then(|| Shape::from_value(value)),
+                        };
+                        (key.clone(), field)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Shape { fields }
+    }
+
+    /// Check an `Option` field holding an object against its own shape
+    pub fn nest(mut self, key: &str, shape: Shape) -> Shape {
+        if let Some(field) = self.fields.get_mut(key) {
+            field.kind = "object";
+            field.nested = Some(shape);
+        }
+        self
+    }
+
+    fn compare(&self, path: &str, object: &Map<String, Value>, found: &mut Vec<(String, DriftKind)>) {
+        for (key, value) in object {
+            let path = format!("{}.{}", path, key);
+            match self.fields.get(key) {
+                None => found.push((path, DriftKind::UnknownField)),
+                Some(field) if value.is_null() => {
+                    if !field.nullable {
+                        found.push((path, DriftKind::UnexpectedNull));
+                    }
+                }
+                Some(field) if field.kind != "null" && field.kind != kind(value) => {
+                    found.push((
+                        path,
+                        DriftKind::TypeMismatch {
+                            expected: field.kind,
+                            found: kind(value),
+                        },
+                    ));
+                }
+                Some(field) => {
+                    if let (Some(shape), Some(object)) = (&field.nested, value.as_object()) {
+                        shape.compare(&path, object, found);
+                    }
+                }
+            }
+        }
+
+        for (key, field) in &self.fields {
+            if !field.nullable && !object.contains_key(key) {
+                found.push((format!("{}.{}", path, key), DriftKind::MissingField));
+            }
+        }
+    }
+}
+
+/// How a payload differs from the model
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DriftKind {
+    /// Not in the model; kept in the line's `extras`
+    UnknownField,
+    /// Expected but absent; the default is used
+    MissingField,
+    /// `null` where the model has no `Option`; the default is used
+    UnexpectedNull,
+    /// Lines with this field are skipped
+    TypeMismatch { expected: &'static str, found: &'static str },
+}
+
+impl std::fmt::Display for DriftKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DriftKind::UnknownField => write!(f, "unknown field"),
+            DriftKind::MissingField => write!(f, "missing field"),
+            DriftKind::UnexpectedNull => write!(f, "unexpected null"),
+            DriftKind::TypeMismatch { expected, found } => write!(f, "{} instead of {}", found, expected),
+        }
+    }
+}
+
+/// One drifted field, with how many lines show it and the first of them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Drift {
+    pub path: String,
+    pub kind: DriftKind,
+    pub lines: usize,
+    pub example: Option<String>,
+}
+
+/// Everything that drifted in one response
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SchemaReport {
+    pub total_lines: usize,
+    pub drift: Vec<Drift>,
+    pub skipped: Vec<SkippedLine>,
+}
+
+impl SchemaReport {
+    pub fn is_clean(&self) -> bool {
+        self.drift.is_empty() && self.skipped.is_empty()
+    }
+
+    /// One-line description for errors and warnings
+    pub fn summary(&self) -> String {
+        let mut parts: Vec<String> = self
+            .drift
+            .iter()
+            .map(|drift| format!("{} ({}, {} lines)", drift.path, drift.kind, drift.lines))
+            .collect();
+        if !self.skipped.is_empty() {
+            parts.push(format!("{} of {} lines skipped", self.skipped.len(), self.total_lines));
+        }
+        parts.join("; ")
+    }
+}
+
+fn check_array(
+    value: &Value,
+    array: &str,
+    name_key: &str,
+    shape: &Shape,
+    drift: &mut BTreeMap<(String, DriftKind), Drift>,
+) -> usize {
+    let Some(entries) = value.get(array).and_then(Value::as_array) else {
+        return 0;
+    };
+
+    for entry in entries {
+        let Some(object) = entry.as_object() else {
+            continue;
+        };
+        let mut found = Vec::new();
+        shape.compare(&format!("{}[]", array), object, &mut found);
+        for (path, kind) in found {
+            drift
+                .entry((path.clone(), kind.clone()))
+                .or_insert_with(|| Drift {
+                    path,
+                    kind,
+                    lines: 0,
+                    example: object.get(name_key).and_then(Value::as_str).map(str::to_string),
+                })
+                .lines += 1;
+        }
+    }
+    entries.len()
+}
+
+/// Compare a raw currency overview with the model
+pub fn check_currency_overview(body: &str) -> Result<SchemaReport> {
+    let value: Value = serde_json::from_str(body).context("Response is not JSON")?;
+    let line_shape = Shape::of::<CurrencyLine>()
+        .nest("pay", Shape::of::<CurrencyData>())
+        .nest("receive", Shape::of::<CurrencyData>());
+
+    let mut drift = BTreeMap::new();
+    let total_lines = check_array(&value, "lines", "currencyTypeName", &line_shape, &mut drift);
+    check_array(&value, "currencyDetails", "name", &Shape::of::<CurrencyDetail>(), &mut drift);
+
+    Ok(SchemaReport {
+        total_lines,
+        drift: drift.into_values().collect(),
+        skipped: parse_currency_overview(body)?.1,
+    })
+}
+
+/// Compare a raw item overview with the model
+pub fn check_item_overview(body: &str) -> Result<SchemaReport> {
+    let value: Value = serde_json::from_str(body).context("Response is not JSON")?;
+    let mut drift = BTreeMap::new();
+    let total_lines = check_array(&value, "lines", "name", &Shape::of::<ItemLine>(), &mut drift);
+
+    Ok(SchemaReport {
+        total_lines,
+        drift: drift.into_values().collect(),
+        skipped: parse_item_overview(body)?.1,
+    })
+}
+
+/// Check a raw overview of either kind, picked by overview type
+pub fn check_overview(overview_type: &str, body: &str) -> Result<SchemaReport> {
+    if crate::get_currency_types().contains(&overview_type) {
+        check_currency_overview(body)
+    } else {
+        check_item_overview(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DRIFTED_ITEMS: &str = r#"{"lines": [
+        {"id": 1, "name": "Starforge", "icon": null, "flavourText": null, "sparkline": {"data": [1.0], "totalChange": 2.0},
+         "implicitModifiers": [], "explicitModifiers": [], "chaosValue": 310.0, "count": 12, "detailsId": "starforge",
+         "isChanceable": true},
+        {"id": 2, "name": "Broken", "chaosValue": "lots", "detailsId": "broken"},
+        {"id": 3, "name": "Lioneye's Glare", "chaosValue": 1.0, "count": null, "detailsId": "lioneyes-glare",
+         "isChanceable": false}
+    ]}"#;
+
+    #[test]
+    fn test_tolerant_item_parsing() {
+        let (response, skipped) = parse_item_overview(DRIFTED_ITEMS).unwrap();
+
+        assert_eq!(response.lines.len(), 2);
+        assert_eq!(response.lines[0].icon, "");
+        assert_eq!(response.lines[0].extras["isChanceable"], Value::Bool(true));
+        assert_eq!(response.lines[1].count, 0);
+        assert!(response.lines[1].sparkline.data.is_none());
+
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].index, 1);
+        assert_eq!(skipped[0].name.as_deref(), Some("Broken"));
+    }
+
+    #[test]
+    fn test_tolerant_currency_parsing() {
+        let body = r#"{"lines": [
+            {"currencyTypeName": "Divine Orb", "chaosEquivalent": 207.5, "paySparkLine": null, "detailsId": "divine-orb",
+             "receive": {"id": 1, "pay_currency_id": 1, "get_currency_id": 3, "sample_time_utc": null, "count": 5, "value": 207.5}},
+            {"currencyTypeName": "No Id"}
+        ], "currencyDetails": [{"id": 3, "name": "Divine Orb"}, {"name": "Missing id"}]}"#;
+
+        let (response, skipped) = parse_currency_overview(body).unwrap();
+        assert_eq!(response.lines.len(), 1);
+        assert_eq!(response.lines[0].receive.as_ref().unwrap().sample_time_utc, "");
+        assert_eq!(response.currency_details.len(), 1);
+        assert_eq!(skipped.iter().map(|line| line.array.as_str()).collect::<Vec<_>>(), vec!["lines", "currencyDetails"]);
+    }
+
+    #[test]
+    fn test_schema_check_reports_drift() {
+        let report = check_item_overview(DRIFTED_ITEMS).unwrap();
+        let find = |path: &str, kind: DriftKind| {
+            report
+                .drift
+                .iter()
+                .find(|drift| drift.path == path && drift.kind == kind)
+                .unwrap()
+        };
+
+        assert_eq!(report.total_lines, 3);
+        assert_eq!(find("lines[].isChanceable", DriftKind::UnknownField).lines, 2);
+        assert_eq!(find("lines[].icon", DriftKind::UnexpectedNull).example.as_deref(), Some("Starforge"));
+        assert_eq!(find("lines[].icon", DriftKind::MissingField).lines, 2);
+        find("lines[].chaosValue", DriftKind::TypeMismatch { expected: "number", found: "string" });
+        assert!(report.drift.iter().all(|drift| drift.path != "lines[].mapTier"));
+        assert!(report.summary().contains("1 of 3 lines skipped"));
+    }
+
+    #[test]
+    fn test_fixtures_match_the_model() {
+        let currencies = include_str!("../tests/fixtures/replay/poe1/currencyoverview/standard/Currency.json");
+        let items = include_str!("../tests/fixtures/replay/poe1/itemoverview/standard/UniqueWeapon.json");
+
+        assert!(check_currency_overview(currencies).unwrap().skipped.is_empty());
+        assert!(check_item_overview(items).unwrap().skipped.is_empty());
+    }
+}
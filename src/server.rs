@@ -0,0 +1,428 @@
+use anyhow::Result;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub use crate::api::SearchHit;
+use crate::api::{currency_hits, item_hits, search_types, sort_hits};
+use crate::pricing::{self, PriceBook, PriceQuery};
+use crate::source::PriceSource;
+use crate::types::*;
+use crate::{filter_currencies_by_name, filter_items_by_criteria, get_currency_types, get_item_types};
+
+struct Entry<V> {
+    value: Arc<V>,
+    fetched: Instant,
+    refreshing: bool,
+}
+
+/// Stale-while-revalidate cache: fresh entries are served as is, stale ones
+/// are served immediately while a background task fetches a replacement
+pub struct SwrCache<V> {
+    entries: Mutex<HashMap<String, Entry<V>>>,
+    ttl: Duration,
+}
+
+impl<V: Send + Sync + 'static> SwrCache<V> {
+    pub fn new(ttl: Duration) -> Arc<Self> {
+        Arc::new(Self {
+            entries: Mutex::new(HashMap::new()),
+            ttl,
+        })
+    }
+
+    pub async fn get_or_fetch<F, Fut>(self: &Arc<Self>, key: String, fetch: F) -> Result<Arc<V>>
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<V>> + Send + 'static,
+    {
+        let cached = {
+            let mut entries = self.entries.lock().unwrap();
+            match entries.get_mut(&key) {
+                Some(entry) => {
+                    let stale = entry.fetched.elapsed() >= self.ttl;
+                    let revalidate = stale && !entry.refreshing;
+                    if revalidate {
+                        entry.refreshing = true;
+                    }
+                    Some((entry.value.clone(), revalidate))
+                }
+                None => None,
+            }
+        };
+
+        match cached {
+            Some((value, revalidate)) => {
+                if revalidate {
+                    let cache = self.clone();
+                    tokio::spawn(async move {
+                        let fresh = fetch().await;
+                        let mut entries = cache.entries.lock().unwrap();
+                        match fresh {
+                            Ok(value) => cache.store(&mut entries, key, value),
+                            // Keep serving the stale value and retry on a later request
+                            Err(_) => {
+                                if let Some(entry) = entries.get_mut(&key) {
+                                    entry.refreshing = false;
+                                }
+                            }
+                        }
+                    });
+                }
+                Ok(value)
+            }
+            None => {
+                let value = Arc::new(fetch().await?);
+                let mut entries = self.entries.lock().unwrap();
+                entries.insert(
+                    key,
+                    Entry {
+                        value: value.clone(),
+                        fetched: Instant::now(),
+                        refreshing: false,
+                    },
+                );
+                Ok(value)
+            }
+        }
+    }
+
+    fn store(&self, entries: &mut HashMap<String, Entry<V>>, key: String, value: V) {
+        entries.insert(
+            key,
+            Entry {
+                value: Arc::new(value),
+                fetched: Instant::now(),
+                refreshing: false,
+            },
+        );
+    }
+}
+
+/// Shared by every request: the upstream source and one cache per response kind
+pub struct ServerState {
+    source: Arc<dyn PriceSource>,
+    currencies: Arc<SwrCache<CurrencyOverviewResponse>>,
+    items: Arc<SwrCache<ItemOverviewResponse>>,
+    leagues: Arc<SwrCache<Vec<EconomyLeague>>>,
+}
+
+impl ServerState {
+    pub fn new(source: Arc<dyn PriceSource>, ttl: Duration) -> Arc<Self> {
+        Arc::new(Self {
+            source,
+            currencies: SwrCache::new(ttl),
+            items: SwrCache::new(ttl),
+            leagues: SwrCache::new(ttl),
+        })
+    }
+
+    async fn currencies(&self, league: &str, currency_type: &str) -> Result<Arc<CurrencyOverviewResponse>> {
+        let source = self.source.clone();
+        let (league, currency_type) = (league.to_string(), currency_type.to_string());
+        self.currencies
+            .get_or_fetch(format!("{}/{}", league, currency_type), move || async move {
+                source.get_currency_overview(&league, &currency_type).await
+            })
+            .await
+    }
+
+    async fn items(&self, league: &str, item_type: &str) -> Result<Arc<ItemOverviewResponse>> {
+        let source = self.source.clone();
+        let (league, item_type) = (league.to_string(), item_type.to_string());
+        self.items
+            .get_or_fetch(format!("{}/{}", league, item_type), move || async move {
+                source.get_item_overview(&league, &item_type).await
+            })
+            .await
+    }
+
+    async fn league_list(&self) -> Result<Arc<Vec<EconomyLeague>>> {
+        let source = self.source.clone();
+        self.leagues
+            .get_or_fetch(String::new(), move || async move { source.get_leagues().await })
+            .await
+    }
+
+    /// Price book for the given categories, built from cached overviews
+    async fn price_book(&self, league: &str, categories: &[&str]) -> Result<PriceBook> {
+        let mut book = PriceBook::new();
+        for category in categories {
+            if get_currency_types().contains(category) {
+                book.insert_currencies(category, self.currencies(league, category).await?.lines.clone());
+            } else {
+                book.insert_items(category, self.items(league, category).await?.lines.clone());
+            }
+        }
+        Ok(book)
+    }
+}
+
+/// JSON error body with a status code
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            message: message.into(),
+        }
+    }
+
+    fn not_found(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::NOT_FOUND,
+            message: message.into(),
+        }
+    }
+
+    /// The upstream source failed or had no data
+    fn upstream(error: anyhow::Error) -> Self {
+        Self {
+            status: StatusCode::BAD_GATEWAY,
+            message: format!("{:#}", error),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(serde_json::json!({ "error": self.message }))).into_response()
+    }
+}
+
+fn default_league() -> String {
+    "Standard".to_string()
+}
+
+fn default_currency_type() -> String {
+    "Currency".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CurrencyParams {
+    #[serde(default = "default_league")]
+    league: String,
+    #[serde(rename = "type", default = "default_currency_type")]
+    currency_type: String,
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ItemParams {
+    #[serde(default = "default_league")]
+    league: String,
+    #[serde(rename = "type")]
+    item_type: String,
+    name: Option<String>,
+    min_chaos: Option<f64>,
+    max_chaos: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchParams {
+    #[serde(default = "default_league")]
+    league: String,
+    q: String,
+    /// Comma separated overview types; all known types by default
+    types: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConvertParams {
+    #[serde(default = "default_league")]
+    league: String,
+    #[serde(default = "default_amount")]
+    amount: f64,
+    from: String,
+    #[serde(default = "default_target")]
+    to: String,
+}
+
+fn default_amount() -> f64 {
+    1.0
+}
+
+fn default_target() -> String {
+    pricing::CHAOS_ORB.to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Conversion {
+    pub amount: f64,
+    pub from: String,
+    pub to: String,
+    pub result: f64,
+}
+
+async fn currency(
+    State(state): State<Arc<ServerState>>,
+    Query(params): Query<CurrencyParams>,
+) -> Result<Json<Vec<CurrencyLine>>, ApiError> {
+    if !get_currency_types().contains(&params.currency_type.as_str()) {
+        return Err(ApiError::bad_request(format!("Unknown currency type '{}'", params.currency_type)));
+    }
+    let response = state
+        .currencies(&params.league, &params.currency_type)
+        .await
+        .map_err(ApiError::upstream)?;
+
+    let lines = filter_currencies_by_name(response.lines.clone(), params.name.as_deref());
+    Ok(Json(crate::sort_currencies_by_value(lines)))
+}
+
+async fn item(
+    State(state): State<Arc<ServerState>>,
+    Query(params): Query<ItemParams>,
+) -> Result<Json<Vec<ItemLine>>, ApiError> {
+    if !get_item_types().contains(&params.item_type.as_str()) {
+        return Err(ApiError::bad_request(format!("Unknown item type '{}'", params.item_type)));
+    }
+    let response = state
+        .items(&params.league, &params.item_type)
+        .await
+        .map_err(ApiError::upstream)?;
+
+    let lines = filter_items_by_criteria(
+        response.lines.clone(),
+        params.name.as_deref(),
+        params.min_chaos,
+        params.max_chaos,
+    );
+    Ok(Json(crate::sort_items_by_value(lines)))
+}
+
+async fn search(
+    State(state): State<Arc<ServerState>>,
+    Query(params): Query<SearchParams>,
+) -> Result<Json<Vec<SearchHit>>, ApiError> {
+    let types: Vec<&str> = match &params.types {
+        Some(types) => types.split(',').map(str::trim).collect(),
+        None => Vec::new(),
+    };
+    let types = search_types(&types).map_err(|err| ApiError::bad_request(err.to_string()))?;
+
+    let needle = params.q.to_lowercase();
+    let mut hits = Vec::new();
+    for overview_type in types {
+        if get_currency_types().contains(&overview_type) {
+            let response = state.currencies(&params.league, overview_type).await.map_err(ApiError::upstream)?;
+            hits.extend(currency_hits(&response, overview_type, &needle));
+        } else {
+            let response = state.items(&params.league, overview_type).await.map_err(ApiError::upstream)?;
+            hits.extend(item_hits(&response, overview_type, &needle));
+        }
+    }
+
+    sort_hits(&mut hits);
+    Ok(Json(hits))
+}
+
+async fn convert(
+    State(state): State<Arc<ServerState>>,
+    Query(params): Query<ConvertParams>,
+) -> Result<Json<Conversion>, ApiError> {
+    let book = state
+        .price_book(&params.league, &get_currency_types())
+        .await
+        .map_err(ApiError::upstream)?;
+
+    let price = |name: &str| {
+        book.lookup(&PriceQuery::named(name))
+            .ok_or_else(|| ApiError::not_found(format!("No price for '{}'", name)))
+    };
+    let from = price(&params.from)?;
+    let to = price(&params.to)?;
+    let result = pricing::convert(params.amount, from.chaos_value, to.chaos_value)
+        .ok_or_else(|| ApiError::bad_request(format!("'{}' has no value to convert into", to.name)))?;
+
+    Ok(Json(Conversion {
+        amount: params.amount,
+        from: from.name,
+        to: to.name,
+        result,
+    }))
+}
+
+async fn leagues(State(state): State<Arc<ServerState>>) -> Result<Json<Vec<EconomyLeague>>, ApiError> {
+    let leagues = state.league_list().await.map_err(ApiError::upstream)?;
+    Ok(Json(leagues.as_ref().clone()))
+}
+
+pub fn router(state: Arc<ServerState>) -> Router {
+    Router::new()
+        .route("/currency", get(currency))
+        .route("/item", get(item))
+        .route("/search", get(search))
+        .route("/convert", get(convert))
+        .route("/leagues", get(leagues))
+        .with_state(state)
+}
+
+/// Serve the API on an already bound listener until the process exits
+pub async fn serve(listener: tokio::net::TcpListener, state: Arc<ServerState>) -> Result<()> {
+    axum::serve(listener, router(state)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_swr_cache_serves_stale_while_revalidating() {
+        let cache: Arc<SwrCache<usize>> = SwrCache::new(Duration::ZERO);
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let fetch = |calls: Arc<AtomicUsize>| {
+            move || async move { Ok(calls.fetch_add(1, Ordering::SeqCst) + 1) }
+        };
+
+        let first = cache.get_or_fetch("k".to_string(), fetch(calls.clone())).await.unwrap();
+        assert_eq!(*first, 1);
+
+        // Stale immediately: the old value comes back and a refresh starts
+        let second = cache.get_or_fetch("k".to_string(), fetch(calls.clone())).await.unwrap();
+        assert_eq!(*second, 1);
+
+        // Once the background refresh lands, later requests see the new value
+        let mut latest = *second;
+        for _ in 0..100 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            latest = *cache.get_or_fetch("k".to_string(), fetch(calls.clone())).await.unwrap();
+            if latest > 1 {
+                break;
+            }
+        }
+        assert!(latest > 1);
+    }
+
+    #[tokio::test]
+    async fn test_fresh_entries_are_not_refetched() {
+        let cache: Arc<SwrCache<&'static str>> = SwrCache::new(Duration::from_secs(60));
+
+        cache.get_or_fetch("k".to_string(), || async { Ok("first") }).await.unwrap();
+        let value = cache
+            .get_or_fetch("k".to_string(), || async { anyhow::bail!("should not be called") })
+            .await
+            .unwrap();
+        assert_eq!(*value, "first");
+
+        let missing = cache
+            .get_or_fetch("other".to_string(), || async { anyhow::bail!("upstream down") })
+            .await;
+        assert!(missing.is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::api::{default_cache_dir, warn_or_fail, RequestKey, Transport, WriteWarning};
+
+/// Timestamped copies of live overview responses, kept under
+/// `<dir>/<game>/<endpoint>/<league>/<type>/<unix seconds>.json`
+pub struct SnapshotStore {
+    dir: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// `snapshots` inside the default cache directory
+    pub fn open_default() -> Result<Self> {
+        let dir = default_cache_dir().context("No cache directory available for snapshots")?;
+        Ok(Self::new(dir.join("snapshots")))
+    }
+
+    fn key_dir(&self, key: &RequestKey) -> PathBuf {
+        self.dir.join(key.path().with_extension(""))
+    }
+
+    /// Snapshot times of `key`, oldest first
+    pub fn times(&self, key: &RequestKey) -> Vec<u64> {
+        let mut times: Vec<u64> = std::fs::read_dir(self.key_dir(key))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok()?.path().file_stem()?.to_str()?.parse().ok())
+            .collect();
+        times.sort_unstable();
+        times
+    }
+
+    /// The latest snapshot taken at or before `time`
+    pub fn at(&self, key: &RequestKey, time: u64) -> Option<(u64, PathBuf)> {
+        let found = self.times(key).into_iter().rev().find(|taken| *taken <= time)?;
+        Some((found, self.key_dir(key).join(format!("{}.json", found))))
+    }
+
+    pub fn save(&self, key: &RequestKey, time: u64, body: &str) -> Result<()> {
+        let dir = self.key_dir(key);
+        std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create '{}'", dir.display()))?;
+        let path = dir.join(format!("{}.json", time));
+        std::fs::write(&path, body).with_context(|| format!("Failed to write snapshot '{}'", path.display()))
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// Keeps a snapshot of every overview fetched from `inner`, at most one per `interval` and key
+pub struct SnapshotTransport {
+    inner: Box<dyn Transport>,
+    store: SnapshotStore,
+    interval: Duration,
+    warn: Option<WriteWarning>,
+}
+
+impl SnapshotTransport {
+    /// A snapshot that cannot be written fails the request
+    pub fn new(inner: Box<dyn Transport>, store: SnapshotStore, interval: Duration) -> Self {
+        Self {
+            inner,
+            store,
+            interval,
+            warn: None,
+        }
+    }
+
+    /// Report write failures to `warn` instead of failing the request
+    pub fn with_write_warning(mut self, warn: WriteWarning) -> Self {
+        self.warn = Some(warn);
+        self
+    }
+}
+
+#[async_trait]
+impl Transport for SnapshotTransport {
+    async fn fetch(&self, url: &str, key: &RequestKey) -> Result<String> {
+        let body = self.inner.fetch(url, key).await?;
+        if key.endpoint.ends_with("overview") {
+            let time = now();
+            let due = self
+                .store
+                .times(key)
+                .last()
+                .is_none_or(|last| time.saturating_sub(*last) >= self.interval.as_secs());
+            if due {
+                warn_or_fail(self.store.save(key, time, &body), self.warn.as_ref())?;
+            }
+        }
+        Ok(body)
+    }
+}
+
+/// Unix seconds, `YYYY-MM-DD` (the end of that day, UTC) or `YYYY-MM-DDTHH:MM` (UTC)
+pub fn parse_time(input: &str) -> Result<u64> {
+    if let Ok(seconds) = input.parse() {
+        return Ok(seconds);
+    }
+
+    let invalid = || format!("Invalid time '{}', expected unix seconds, YYYY-MM-DD or YYYY-MM-DDTHH:MM", input);
+    let (date, time) = match input.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (input, None),
+    };
+    let mut parts = date.splitn(3, '-').map(str::parse::<i64>);
+    let (Some(Ok(year)), Some(Ok(month)), Some(Ok(day))) = (parts.next(), parts.next(), parts.next()) else {
+        anyhow::bail!(invalid());
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        anyhow::bail!(invalid());
+    }
+
+    let seconds_of_day = match time {
+        Some(time) => {
+            let (hour, minute) = time.split_once(':').with_context(invalid)?;
+            let (hour, minute): (i64, i64) = (hour.parse().with_context(invalid)?, minute.parse().with_context(invalid)?);
+            if !(0..24).contains(&hour) || !(0..60).contains(&minute) {
+                anyhow::bail!(invalid());
+            }
+            hour * 3600 + minute * 60
+        }
+        None => 86_399,
+    };
+
+    let seconds = days_from_civil(year, month, day) * 86_400 + seconds_of_day;
+    u64::try_from(seconds).ok().with_context(invalid)
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The path of a saved response if `input` names a file, otherwise the snapshot of `key` at that time
+pub fn resolve(store: Option<&SnapshotStore>, key: Option<&RequestKey>, input: &str) -> Result<(String, PathBuf)> {
+    let path = Path::new(input);
+    if path.is_file() {
+        return Ok((input.to_string(), path.to_path_buf()));
+    }
+
+    let time = parse_time(input).with_context(|| format!("'{}' is neither a file nor a snapshot time", input))?;
+    let key = key.context("Pass --item-type (and --league) to diff snapshots by time")?;
+    let store = store.context("No cache directory available for snapshots")?;
+    match store.at(key, time) {
+        Some((taken, path)) => Ok((format!("snapshot {}", taken), path)),
+        None => {
+            let times = store.times(key);
+            if times.is_empty() {
+                anyhow::bail!("No snapshots of {} yet; live runs keep one per hour", key.path().display());
+            }
+            anyhow::bail!(
+                "No snapshot of {} at or before {}; snapshots: {}",
+                key.path().display(),
+                time,
+                times.iter().map(u64::to_string).collect::<Vec<_>>().join(", ")
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::Game;
+
+    fn key() -> RequestKey {
+        RequestKey {
+            game: Game::Poe1,
+            endpoint: "itemoverview".to_string(),
+            league: Some("Standard".to_string()),
+            kind: Some("Oil".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("1700000000").unwrap(), 1_700_000_000);
+        assert_eq!(parse_time("1970-01-01T00:00").unwrap(), 0);
+        assert_eq!(parse_time("2024-02-29T12:30").unwrap(), 1_709_209_800);
+        assert_eq!(parse_time("2024-03-01").unwrap(), 1_709_337_599);
+        assert!(parse_time("2024-13-01").is_err());
+        assert!(parse_time("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_store_finds_latest_snapshot_before() {
+        let dir = std::env::temp_dir().join(format!("poe-ninja-snapshots-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = SnapshotStore::new(&dir);
+        store.save(&key(), 100, "first").unwrap();
+        store.save(&key(), 200, "second").unwrap();
+
+        assert_eq!(store.times(&key()), vec![100, 200]);
+        assert_eq!(store.at(&key(), 150).unwrap().0, 100);
+        assert_eq!(store.at(&key(), 200).unwrap().0, 200);
+        assert!(store.at(&key(), 99).is_none());
+
+        let (label, path) = resolve(Some(&store), Some(&key()), "250").unwrap();
+        assert_eq!(label, "snapshot 200");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "second");
+        assert!(resolve(Some(&store), None, "250").unwrap_err().to_string().contains("--item-type"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,298 @@
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::api::{Game, PoeNinjaClient};
+use crate::pricing::slugify;
+use crate::schema;
+use crate::types::*;
+
+/// Anything that can answer the overview, league and history queries the
+/// commands need. History values are chaos per unit, one point per day.
+#[async_trait]
+pub trait PriceSource: Send + Sync {
+    async fn get_currency_overview(&self, league: &str, currency_type: &str) -> Result<CurrencyOverviewResponse>;
+
+    async fn get_item_overview(&self, league: &str, item_type: &str) -> Result<ItemOverviewResponse>;
+
+    async fn get_leagues(&self) -> Result<Vec<EconomyLeague>>;
+
+    /// `id` is `CurrencyDetail::id` for currency types and `ItemLine::id` otherwise
+    async fn get_history(&self, league: &str, overview_type: &str, id: i32) -> Result<Vec<HistoryPoint>>;
+
+    fn game(&self) -> Game {
+        Game::Poe1
+    }
+
+    async fn get_exchange_overview(&self, _league: &str, _overview_type: &str) -> Result<Poe2ExchangeOverviewResponse> {
+        bail!("This price source has no PoE2 exchange data")
+    }
+
+    async fn get_build_overview(&self, _league_url: &str) -> Result<BuildOverviewResponse> {
+        bail!("This price source has no build data")
+    }
+
+    /// The overview body as served, before parsing
+    async fn get_raw_overview(&self, _league: &str, _overview_type: &str) -> Result<String> {
+        bail!("This price source has no raw responses")
+    }
+}
+
+#[async_trait]
+impl PriceSource for PoeNinjaClient {
+    async fn get_currency_overview(&self, league: &str, currency_type: &str) -> Result<CurrencyOverviewResponse> {
+        PoeNinjaClient::get_currency_overview(self, league, currency_type).await
+    }
+
+    async fn get_item_overview(&self, league: &str, item_type: &str) -> Result<ItemOverviewResponse> {
+        PoeNinjaClient::get_item_overview(self, league, item_type).await
+    }
+
+    async fn get_leagues(&self) -> Result<Vec<EconomyLeague>> {
+        PoeNinjaClient::get_leagues(self).await
+    }
+
+    async fn get_history(&self, league: &str, overview_type: &str, id: i32) -> Result<Vec<HistoryPoint>> {
+        if crate::get_currency_types().contains(&overview_type) {
+            let history = self.get_currency_history(league, overview_type, id).await?;
+            Ok(history.receive_currency_graph_data)
+        } else {
+            self.get_item_history(league, overview_type, id).await
+        }
+    }
+
+    fn game(&self) -> Game {
+        PoeNinjaClient::game(self)
+    }
+
+    async fn get_exchange_overview(&self, league: &str, overview_type: &str) -> Result<Poe2ExchangeOverviewResponse> {
+        PoeNinjaClient::get_exchange_overview(self, league, overview_type).await
+    }
+
+    async fn get_build_overview(&self, league_url: &str) -> Result<BuildOverviewResponse> {
+        PoeNinjaClient::get_build_overview(self, league_url).await
+    }
+
+    async fn get_raw_overview(&self, league: &str, overview_type: &str) -> Result<String> {
+        PoeNinjaClient::get_raw_overview(self, league, overview_type).await
+    }
+}
+
+/// Overviews saved as plain JSON files:
+///
+/// ```text
+/// <dir>/<league>/Currency.json          currency overview
+/// <dir>/<league>/UniqueWeapon.json      item overview
+/// <dir>/<league>/history/Oil/12.json    history points for id 12
+/// ```
+///
+/// League directories use the slug of the league name, e.g. `hardcore-settlers`.
+pub struct LocalDirSource {
+    dir: PathBuf,
+}
+
+impl LocalDirSource {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn read_raw(&self, path: &Path) -> Result<String> {
+        std::fs::read_to_string(path).with_context(|| format!("No local data at '{}'", path.display()))
+    }
+
+    fn read<T: serde::de::DeserializeOwned>(&self, path: &Path) -> Result<T> {
+        let content = self.read_raw(path)?;
+        serde_json::from_str(&content).with_context(|| format!("Failed to parse '{}'", path.display()))
+    }
+
+    fn overview_path(&self, league: &str, overview_type: &str) -> PathBuf {
+        self.dir.join(slugify(league)).join(format!("{}.json", overview_type))
+    }
+}
+
+#[async_trait]
+impl PriceSource for LocalDirSource {
+    async fn get_currency_overview(&self, league: &str, currency_type: &str) -> Result<CurrencyOverviewResponse> {
+        let path = self.overview_path(league, currency_type);
+        let (response, _) = schema::parse_currency_overview(&self.read_raw(&path)?)
+            .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+        Ok(response)
+    }
+
+    async fn get_item_overview(&self, league: &str, item_type: &str) -> Result<ItemOverviewResponse> {
+        let path = self.overview_path(league, item_type);
+        let (response, _) = schema::parse_item_overview(&self.read_raw(&path)?)
+            .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+        Ok(response)
+    }
+
+    /// Every league directory, named after the directory
+    async fn get_leagues(&self) -> Result<Vec<EconomyLeague>> {
+        let entries = std::fs::read_dir(&self.dir)
+            .with_context(|| format!("Failed to read data directory '{}'", self.dir.display()))?;
+
+        let mut leagues = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            leagues.push(EconomyLeague {
+                url: name.clone(),
+                display_name: name.clone(),
+                hardcore: name.contains("hardcore"),
+                indexed: true,
+                name,
+            });
+        }
+        leagues.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(leagues)
+    }
+
+    async fn get_raw_overview(&self, league: &str, overview_type: &str) -> Result<String> {
+        self.read_raw(&self.overview_path(league, overview_type))
+    }
+
+    async fn get_history(&self, league: &str, overview_type: &str, id: i32) -> Result<Vec<HistoryPoint>> {
+        let path = self
+            .dir
+            .join(slugify(league))
+            .join("history")
+            .join(overview_type)
+            .join(format!("{}.json", id));
+        self.read(&path)
+    }
+}
+
+/// In-memory source for tests and examples
+#[derive(Debug, Default, Clone)]
+pub struct MockSource {
+    currencies: HashMap<(String, String), CurrencyOverviewResponse>,
+    items: HashMap<(String, String), ItemOverviewResponse>,
+    leagues: Vec<EconomyLeague>,
+    history: HashMap<(String, String, i32), Vec<HistoryPoint>>,
+}
+
+impl MockSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_currencies(mut self, league: &str, currency_type: &str, response: CurrencyOverviewResponse) -> Self {
+        self.currencies
+            .insert((league.to_string(), currency_type.to_string()), response);
+        self
+    }
+
+    pub fn with_items(mut self, league: &str, item_type: &str, lines: Vec<ItemLine>) -> Self {
+        self.items.insert(
+            (league.to_string(), item_type.to_string()),
+            ItemOverviewResponse { lines },
+        );
+        self
+    }
+
+    pub fn with_league(mut self, name: &str) -> Self {
+        self.leagues.push(EconomyLeague {
+            name: name.to_string(),
+            url: slugify(name),
+            display_name: name.to_string(),
+            hardcore: name.contains("Hardcore"),
+            indexed: true,
+        });
+        self
+    }
+
+    pub fn with_history(mut self, league: &str, overview_type: &str, id: i32, points: Vec<HistoryPoint>) -> Self {
+        self.history
+            .insert((league.to_string(), overview_type.to_string(), id), points);
+        self
+    }
+}
+
+#[async_trait]
+impl PriceSource for MockSource {
+    async fn get_currency_overview(&self, league: &str, currency_type: &str) -> Result<CurrencyOverviewResponse> {
+        self.currencies
+            .get(&(league.to_string(), currency_type.to_string()))
+            .cloned()
+            .with_context(|| format!("No mock currency data for {} - {}", league, currency_type))
+    }
+
+    async fn get_item_overview(&self, league: &str, item_type: &str) -> Result<ItemOverviewResponse> {
+        self.items
+            .get(&(league.to_string(), item_type.to_string()))
+            .cloned()
+            .with_context(|| format!("No mock item data for {} - {}", league, item_type))
+    }
+
+    async fn get_leagues(&self) -> Result<Vec<EconomyLeague>> {
+        Ok(self.leagues.clone())
+    }
+
+    async fn get_history(&self, league: &str, overview_type: &str, id: i32) -> Result<Vec<HistoryPoint>> {
+        self.history
+            .get(&(league.to_string(), overview_type.to_string(), id))
+            .cloned()
+            .with_context(|| format!("No mock history for {} - {} #{}", league, overview_type, id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("poe-ninja-local-{}", std::process::id()));
+        let league = dir.join("standard");
+        std::fs::create_dir_all(league.join("history").join("Currency")).unwrap();
+        std::fs::write(
+            league.join("Currency.json"),
+            include_str!("../tests/fixtures/replay/poe1/currencyoverview/standard/Currency.json"),
+        )
+        .unwrap();
+        std::fs::write(
+            league.join("history").join("Currency").join("3.json"),
+            r#"[{"count": 10, "value": 200.0, "daysAgo": 1}, {"count": 12, "value": 207.5, "daysAgo": 0}]"#,
+        )
+        .unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_local_dir_source() {
+        let dir = fixture_dir();
+        let source = LocalDirSource::new(&dir);
+
+        let currencies = source.get_currency_overview("Standard", "Currency").await.unwrap();
+        assert_eq!(currencies.lines.len(), 2);
+
+        let leagues = source.get_leagues().await.unwrap();
+        assert_eq!(leagues.len(), 1);
+        assert_eq!(leagues[0].name, "standard");
+
+        let history = source.get_history("Standard", "Currency", 3).await.unwrap();
+        assert_eq!(history[1].value, 207.5);
+
+        let missing = source.get_item_overview("Standard", "Oil").await.unwrap_err();
+        assert!(missing.to_string().contains("No local data"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_mock_source() {
+        let source = MockSource::new()
+            .with_items("Standard", "Oil", vec![])
+            .with_league("Settlers")
+            .with_history("Standard", "Oil", 1, vec![HistoryPoint { count: 1, value: 2.0, days_ago: 0 }]);
+
+        assert!(source.get_item_overview("Standard", "Oil").await.unwrap().lines.is_empty());
+        assert!(source.get_item_overview("Settlers", "Oil").await.is_err());
+        assert_eq!(source.get_leagues().await.unwrap()[0].url, "settlers");
+        assert_eq!(source.get_history("Standard", "Oil", 1).await.unwrap().len(), 1);
+        assert!(source.get_exchange_overview("Standard", "Currency").await.is_err());
+    }
+}
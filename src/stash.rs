@@ -0,0 +1,418 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::source::PriceSource;
+use crate::pricing::{
+    LoadedBook, PriceBook, PriceMatch, PriceQuery, CURRENCY_LIKE_CATEGORIES, UNIQUE_CATEGORIES,
+};
+
+/// Item rarity/kind as reported by the `frameType` field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameType {
+    Normal,
+    Magic,
+    Rare,
+    Unique,
+    Gem,
+    Currency,
+    DivinationCard,
+    Quest,
+    Relic,
+    Other(i32),
+}
+
+impl From<i32> for FrameType {
+    fn from(value: i32) -> Self {
+        match value {
+            0 => Self::Normal,
+            1 => Self::Magic,
+            2 => Self::Rare,
+            3 => Self::Unique,
+            4 => Self::Gem,
+            5 => Self::Currency,
+            6 => Self::DivinationCard,
+            7 => Self::Quest,
+            9 | 10 => Self::Relic,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Socket {
+    pub group: i32,
+    #[serde(default)]
+    pub attr: Option<String>,
+    #[serde(rename = "sColour", default)]
+    pub colour: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ItemProperty {
+    pub name: String,
+    #[serde(default)]
+    pub values: Vec<(String, i32)>,
+}
+
+/// One item from the public stash API
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct StashItem {
+    #[serde(rename = "frameType")]
+    pub frame_type: i32,
+    #[serde(default)]
+    pub name: String,
+    #[serde(rename = "typeLine")]
+    pub type_line: String,
+    #[serde(rename = "baseType", default)]
+    pub base_type: Option<String>,
+    #[serde(default)]
+    pub ilvl: Option<i32>,
+    #[serde(default)]
+    pub sockets: Vec<Socket>,
+    #[serde(rename = "stackSize", default)]
+    pub stack_size: Option<u32>,
+    #[serde(default)]
+    pub properties: Vec<ItemProperty>,
+    #[serde(default)]
+    pub corrupted: bool,
+}
+
+impl StashItem {
+    pub fn frame(&self) -> FrameType {
+        FrameType::from(self.frame_type)
+    }
+
+    /// Uniques are known by name, everything else by type line
+    pub fn display_name(&self) -> &str {
+        if self.frame() == FrameType::Unique || self.frame() == FrameType::Relic {
+            &self.name
+        } else {
+            &self.type_line
+        }
+    }
+
+    pub fn quantity(&self) -> u32 {
+        self.stack_size.unwrap_or(1).max(1)
+    }
+
+    /// Size of the largest linked socket group
+    pub fn largest_link_group(&self) -> i32 {
+        let mut groups = std::collections::HashMap::new();
+        for socket in &self.sockets {
+            *groups.entry(socket.group).or_insert(0) += 1;
+        }
+        groups.values().copied().max().unwrap_or(0)
+    }
+
+    pub fn property_number(&self, name: &str) -> Option<i32> {
+        let property = self.properties.iter().find(|p| p.name == name)?;
+        let (value, _) = property.values.first()?;
+        let digits: String = value
+            .chars()
+            .skip_while(|c| !c.is_ascii_digit())
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        digits.parse().ok()
+    }
+
+    /// poe.ninja categories this item could be priced in, in lookup order
+    pub fn categories(&self) -> &'static [&'static str] {
+        match self.frame() {
+            FrameType::Unique | FrameType::Relic => UNIQUE_CATEGORIES,
+            FrameType::Gem => &["SkillGem"],
+            FrameType::Currency => CURRENCY_LIKE_CATEGORIES,
+            FrameType::DivinationCard => &["DivinationCard"],
+            FrameType::Normal | FrameType::Magic | FrameType::Rare
+                if self.type_line.ends_with(" Map") =>
+            {
+                &["Map"]
+            }
+            _ => &[],
+        }
+    }
+
+    pub fn query(&self) -> PriceQuery<'_> {
+        let mut query = PriceQuery::named(self.display_name());
+        match self.frame() {
+            FrameType::Unique | FrameType::Relic => {
+                // poe.ninja only lists five and six link variants separately
+                let links = self.largest_link_group();
+                if links >= 5 {
+                    query.links = Some(links);
+                }
+            }
+            FrameType::Gem => {
+                query.gem_level = self.property_number("Level");
+                query.gem_quality = Some(self.property_number("Quality").unwrap_or(0));
+                query.corrupted = Some(self.corrupted);
+            }
+            _ => {}
+        }
+        query
+    }
+}
+
+#[derive(Deserialize)]
+struct PublicStash {
+    #[serde(default)]
+    items: Vec<StashItem>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StashExport {
+    PublicStashes { stashes: Vec<PublicStash> },
+    Tab { items: Vec<StashItem> },
+    Items(Vec<StashItem>),
+}
+
+/// Parse a public stash API response, a single stash tab or a bare item list
+pub fn parse_stash(content: &str) -> Result<Vec<StashItem>> {
+    let export: StashExport =
+        serde_json::from_str(content).context("Failed to parse stash JSON")?;
+
+    Ok(match export {
+        StashExport::PublicStashes { stashes } => {
+            stashes.into_iter().flat_map(|stash| stash.items).collect()
+        }
+        StashExport::Tab { items } | StashExport::Items(items) => items,
+    })
+}
+
+pub fn load_stash(path: &Path) -> Result<Vec<StashItem>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read stash file '{}'", path.display()))?;
+    parse_stash(&content)
+}
+
+/// Fetch the categories the stash items need. Each item is searched through its
+/// categories until it resolves, and categories that fail to load are skipped.
+pub async fn load_price_book(source: &dyn PriceSource, league: &str, items: &[StashItem]) -> LoadedBook {
+    let mut loaded = LoadedBook::default();
+    loaded.ensure(source, league, "Currency").await;
+
+    for item in items {
+        let query = item.query();
+        for category in item.categories() {
+            if loaded.book.lookup_in(item.categories(), &query).is_some() {
+                break;
+            }
+            loaded.ensure(source, league, category).await;
+        }
+    }
+
+    loaded
+}
+
+#[derive(Debug, Clone)]
+pub struct ValuedItem {
+    pub item: StashItem,
+    pub price: PriceMatch,
+    pub quantity: u32,
+    pub total_chaos: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct StashValuation {
+    pub items: Vec<ValuedItem>,
+    pub unpriced: Vec<StashItem>,
+    pub total_chaos: f64,
+    pub divine_rate: Option<f64>,
+}
+
+impl StashValuation {
+    /// The most valuable entries, each with its share of the total
+    pub fn top_contributors(&self, limit: usize) -> Vec<(&ValuedItem, f64)> {
+        self.items
+            .iter()
+            .take(limit)
+            .map(|valued| {
+                let share = if self.total_chaos > 0.0 {
+                    valued.total_chaos / self.total_chaos * 100.0
+                } else {
+                    0.0
+                };
+                (valued, share)
+            })
+            .collect()
+    }
+}
+
+/// Price every stash item, most valuable entries first
+pub fn value_stash(book: &PriceBook, items: &[StashItem]) -> StashValuation {
+    let mut valued = Vec::new();
+    let mut unpriced = Vec::new();
+
+    for item in items {
+        let price = book.lookup_in(item.categories(), &item.query());
+
+        match price {
+            Some(price) => {
+                let quantity = item.quantity();
+                valued.push(ValuedItem {
+                    item: item.clone(),
+                    total_chaos: price.chaos_value * quantity as f64,
+                    price,
+                    quantity,
+                });
+            }
+            None => unpriced.push(item.clone()),
+        }
+    }
+
+    valued.sort_by(|a, b| b.total_chaos.total_cmp(&a.total_chaos));
+    let total_chaos = valued.iter().map(|v| v.total_chaos).sum();
+
+    StashValuation {
+        items: valued,
+        unpriced,
+        total_chaos,
+        divine_rate: book.divine_rate(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::*;
+
+    const STASH_JSON: &str = r#"{
+        "next_change_id": "1-2-3",
+        "stashes": [{
+            "id": "abc",
+            "public": true,
+            "league": "Standard",
+            "items": [
+                {
+                    "frameType": 5, "name": "", "typeLine": "Divine Orb",
+                    "baseType": "Divine Orb", "stackSize": 7, "ilvl": 0
+                },
+                {
+                    "frameType": 3, "name": "Tabula Rasa", "typeLine": "Simple Robe",
+                    "baseType": "Simple Robe", "ilvl": 70,
+                    "sockets": [
+                        {"group": 0, "attr": "G", "sColour": "W"},
+                        {"group": 0, "attr": "G", "sColour": "W"},
+                        {"group": 0, "attr": "G", "sColour": "W"},
+                        {"group": 0, "attr": "G", "sColour": "W"},
+                        {"group": 0, "attr": "G", "sColour": "W"},
+                        {"group": 0, "attr": "G", "sColour": "W"}
+                    ]
+                },
+                {
+                    "frameType": 4, "name": "", "typeLine": "Enlighten Support",
+                    "corrupted": true,
+                    "properties": [
+                        {"name": "Level", "values": [["4 (Max)", 0]], "displayMode": 0},
+                        {"name": "Quality", "values": [["+20%", 1]], "displayMode": 0}
+                    ]
+                },
+                {
+                    "frameType": 2, "name": "Doom Grip", "typeLine": "Vaal Gauntlets"
+                }
+            ]
+        }]
+    }"#;
+
+    fn item_line(name: &str, chaos: f64) -> ItemLine {
+        ItemLine {
+            id: 1,
+            name: name.to_string(),
+            chaos_value: chaos,
+            count: 1,
+            details_id: crate::pricing::slugify(name),
+            ..Default::default()
+        }
+    }
+
+    fn currency_line(name: &str, chaos: f64) -> CurrencyLine {
+        CurrencyLine {
+            currency_type_name: name.to_string(),
+            chaos_equivalent: Some(chaos),
+            details_id: crate::pricing::slugify(name),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_public_stash_items() {
+        let items = parse_stash(STASH_JSON).unwrap();
+
+        assert_eq!(items.len(), 4);
+        assert_eq!(items[0].frame(), FrameType::Currency);
+        assert_eq!(items[0].quantity(), 7);
+        assert_eq!(items[1].display_name(), "Tabula Rasa");
+        assert_eq!(items[1].largest_link_group(), 6);
+        assert_eq!(items[2].property_number("Level"), Some(4));
+        assert_eq!(items[2].property_number("Quality"), Some(20));
+        assert!(items[2].corrupted);
+        assert!(items[3].categories().is_empty());
+    }
+
+    #[test]
+    fn test_parse_single_tab_and_bare_list() {
+        let tab = r#"{"items": [{"frameType": 6, "typeLine": "The Doctor", "stackSize": 2}]}"#;
+        let list = r#"[{"frameType": 6, "typeLine": "The Doctor", "stackSize": 2}]"#;
+
+        assert_eq!(parse_stash(tab).unwrap()[0].type_line, "The Doctor");
+        assert_eq!(parse_stash(list).unwrap()[0].quantity(), 2);
+    }
+
+    #[test]
+    fn test_value_stash() {
+        let items = parse_stash(STASH_JSON).unwrap();
+
+        let mut tabula_6l = item_line("Tabula Rasa", 50.0);
+        tabula_6l.links = Some(6);
+        let mut enlighten = item_line("Enlighten Support", 900.0);
+        enlighten.gem_level = Some(4);
+        enlighten.gem_quality = Some(20);
+        enlighten.corrupted = Some(true);
+        let mut enlighten_clean = item_line("Enlighten Support", 600.0);
+        enlighten_clean.gem_level = Some(4);
+        enlighten_clean.gem_quality = Some(20);
+
+        let mut book = PriceBook::new();
+        book.insert_currencies("Currency", vec![currency_line("Divine Orb", 200.0)]);
+        book.insert_items("UniqueArmour", vec![item_line("Tabula Rasa", 10.0), tabula_6l]);
+        book.insert_items("SkillGem", vec![enlighten_clean, enlighten]);
+
+        let valuation = value_stash(&book, &items);
+
+        assert_eq!(valuation.items.len(), 3);
+        assert_eq!(valuation.items[0].price.name, "Divine Orb");
+        assert_eq!(valuation.items[0].total_chaos, 1400.0);
+        assert_eq!(valuation.items[1].total_chaos, 900.0);
+        assert_eq!(valuation.items[2].total_chaos, 50.0);
+        assert_eq!(valuation.total_chaos, 2350.0);
+        assert_eq!(valuation.unpriced.len(), 1);
+
+        let top = valuation.top_contributors(1);
+        assert_eq!(top.len(), 1);
+        assert!((top[0].1 - 1400.0 / 2350.0 * 100.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_failed_categories_leave_items_unpriced() {
+        let items = parse_stash(STASH_JSON).unwrap();
+        let source = crate::source::MockSource::new().with_currencies(
+            "Standard",
+            "Currency",
+            CurrencyOverviewResponse {
+                lines: vec![currency_line("Divine Orb", 200.0)],
+                currency_details: vec![],
+            },
+        );
+
+        let loaded = load_price_book(&source, "Standard", &items).await;
+        let skipped: Vec<&str> = loaded.skipped.iter().map(|(category, _)| category.as_str()).collect();
+        let mut expected = UNIQUE_CATEGORIES.to_vec();
+        expected.push("SkillGem");
+        assert_eq!(skipped, expected);
+
+        let valuation = value_stash(&loaded.book, &items);
+        assert_eq!(valuation.total_chaos, 1400.0);
+        assert_eq!(valuation.unpriced.len(), 3);
+    }
+}
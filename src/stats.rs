@@ -0,0 +1,279 @@
+use serde::Serialize;
+
+use crate::types::*;
+
+/// Percentiles reported besides the median
+pub const PERCENTILES: [u8; 4] = [10, 25, 75, 90];
+
+/// Edges of the `total_change` buckets, in percent
+const CHANGE_EDGES: [f64; 6] = [-50.0, -20.0, -5.0, 5.0, 20.0, 50.0];
+
+/// The fields of an overview line that feed the market summary
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatLine {
+    pub details_id: String,
+    pub name: String,
+    pub chaos_value: f64,
+    /// Percent change over the sparkline's seven days
+    pub total_change: Option<f64>,
+    /// poe.ninja only fills the low confidence sparkline when there were too few listings
+    pub low_confidence: bool,
+}
+
+/// Whether any day of the sparkline has a value
+pub fn has_data(sparkline: &SparkLine) -> bool {
+    sparkline
+        .data
+        .as_ref()
+        .is_some_and(|data| data.iter().any(Option::is_some))
+}
+
+fn change(sparkline: &SparkLine, low_confidence: &SparkLine) -> (Option<f64>, bool) {
+    if has_data(sparkline) {
+        (sparkline.total_change, false)
+    } else {
+        (low_confidence.total_change, true)
+    }
+}
+
+pub fn lines_from_items(items: &[ItemLine]) -> Vec<StatLine> {
+    items
+        .iter()
+        .map(|item| {
+            let (total_change, low_confidence) = change(&item.sparkline, &item.low_confidence_sparkline);
+            StatLine {
+                details_id: item.details_id.clone(),
+                name: match &item.variant {
+                    Some(variant) => format!("{} ({})", item.name, variant),
+                    None => item.name.clone(),
+                },
+                chaos_value: item.chaos_value,
+                total_change,
+                low_confidence,
+            }
+        })
+        .collect()
+}
+
+/// Currencies use the receive side, which is the direction of `chaosEquivalent`
+pub fn lines_from_currencies(currencies: &[CurrencyLine]) -> Vec<StatLine> {
+    currencies
+        .iter()
+        .map(|currency| {
+            let (total_change, low_confidence) =
+                change(&currency.receive_spark_line, &currency.low_confidence_receive_spark_line);
+            StatLine {
+                details_id: currency.details_id.clone(),
+                name: currency.currency_type_name.clone(),
+                chaos_value: currency.chaos_equivalent.unwrap_or(0.0),
+                total_change,
+                low_confidence,
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Percentile {
+    pub percentile: u8,
+    pub chaos_value: f64,
+}
+
+/// Number of lines whose `total_change` falls in `range`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ChangeBucket {
+    pub range: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Mover {
+    pub details_id: String,
+    pub name: String,
+    pub chaos_value: f64,
+    pub total_change: f64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct MarketStats {
+    pub count: usize,
+    pub total_chaos: f64,
+    pub mean_chaos: f64,
+    pub median_chaos: f64,
+    pub percentiles: Vec<Percentile>,
+    pub low_confidence: usize,
+    /// Fraction of lines, 0 to 1
+    pub low_confidence_share: f64,
+    pub median_change: Option<f64>,
+    pub change_distribution: Vec<ChangeBucket>,
+    pub gainers: Vec<Mover>,
+    pub losers: Vec<Mover>,
+}
+
+/// Linearly interpolated percentile of an ascending slice, 0 when empty
+pub fn percentile(sorted: &[f64], percent: f64) -> f64 {
+    match sorted.len() {
+        0 => 0.0,
+        1 => sorted[0],
+        len => {
+            let rank = percent.clamp(0.0, 100.0) / 100.0 * (len - 1) as f64;
+            let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+            sorted[low] + (sorted[high] - sorted[low]) * (rank - low as f64)
+        }
+    }
+}
+
+fn bucket_label(index: usize) -> String {
+    match index {
+        0 => format!("< {:+}%", CHANGE_EDGES[0]),
+        index if index == CHANGE_EDGES.len() => format!(">= {:+}%", CHANGE_EDGES[index - 1]),
+        index => format!("{:+}% to {:+}%", CHANGE_EDGES[index - 1], CHANGE_EDGES[index]),
+    }
+}
+
+fn change_distribution(lines: &[StatLine]) -> Vec<ChangeBucket> {
+    let mut counts = vec![0; CHANGE_EDGES.len() + 1];
+    let mut missing = 0;
+    for line in lines {
+        match line.total_change {
+            Some(change) => counts[CHANGE_EDGES.iter().filter(|edge| change >= **edge).count()] += 1,
+            None => missing += 1,
+        }
+    }
+
+    let mut buckets: Vec<ChangeBucket> = counts
+        .into_iter()
+        .enumerate()
+        .map(|(index, count)| ChangeBucket { range: bucket_label(index), count })
+        .collect();
+    buckets.push(ChangeBucket { range: "no data".to_string(), count: missing });
+    buckets
+}
+
+/// Summarize `lines`; gainers and losers skip low confidence lines, whose swings are mostly noise
+pub fn summarize(lines: &[StatLine], top: usize) -> MarketStats {
+    let mut values: Vec<f64> = lines.iter().map(|line| line.chaos_value).collect();
+    values.sort_by(f64::total_cmp);
+    let mut changes: Vec<f64> = lines.iter().filter_map(|line| line.total_change).collect();
+    changes.sort_by(f64::total_cmp);
+
+    let total_chaos: f64 = values.iter().sum();
+    let low_confidence = lines.iter().filter(|line| line.low_confidence).count();
+
+    let mut movers: Vec<Mover> = lines
+        .iter()
+        .filter(|line| !line.low_confidence)
+        .filter_map(|line| {
+            Some(Mover {
+                details_id: line.details_id.clone(),
+                name: line.name.clone(),
+                chaos_value: line.chaos_value,
+                total_change: line.total_change?,
+            })
+        })
+        .collect();
+    movers.sort_by(|a, b| b.total_change.total_cmp(&a.total_change));
+
+    let gainers = movers.iter().filter(|mover| mover.total_change > 0.0).take(top).cloned().collect();
+    let losers = movers.iter().rev().filter(|mover| mover.total_change < 0.0).take(top).cloned().collect();
+
+    MarketStats {
+        count: lines.len(),
+        total_chaos,
+        mean_chaos: if lines.is_empty() { 0.0 } else { total_chaos / lines.len() as f64 },
+        median_chaos: percentile(&values, 50.0),
+        percentiles: PERCENTILES
+            .iter()
+            .map(|percent| Percentile {
+                percentile: *percent,
+                chaos_value: percentile(&values, *percent as f64),
+            })
+            .collect(),
+        low_confidence,
+        low_confidence_share: if lines.is_empty() { 0.0 } else { low_confidence as f64 / lines.len() as f64 },
+        median_change: (!changes.is_empty()).then(|| percentile(&changes, 50.0)),
+        change_distribution: change_distribution(lines),
+        gainers,
+        losers,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(name: &str, chaos_value: f64, total_change: Option<f64>, low_confidence: bool) -> StatLine {
+        StatLine {
+            details_id: name.to_lowercase(),
+            name: name.to_string(),
+            chaos_value,
+            total_change,
+            low_confidence,
+        }
+    }
+
+    #[test]
+    fn test_percentile_interpolates() {
+        let values = [1.0, 2.0, 3.0, 4.0];
+        assert_eq!(percentile(&values, 50.0), 2.5);
+        assert_eq!(percentile(&values, 0.0), 1.0);
+        assert_eq!(percentile(&values, 100.0), 4.0);
+        assert_eq!(percentile(&values, 25.0), 1.75);
+        assert_eq!(percentile(&[], 50.0), 0.0);
+    }
+
+    #[test]
+    fn test_summarize() {
+        let lines = vec![
+            line("A", 10.0, Some(60.0), false),
+            line("B", 20.0, Some(-30.0), false),
+            line("C", 30.0, Some(2.0), false),
+            line("D", 40.0, Some(400.0), true),
+            line("E", 100.0, None, true),
+        ];
+        let stats = summarize(&lines, 1);
+
+        assert_eq!(stats.count, 5);
+        assert_eq!(stats.total_chaos, 200.0);
+        assert_eq!(stats.median_chaos, 30.0);
+        assert_eq!(stats.percentiles[1], Percentile { percentile: 25, chaos_value: 20.0 });
+        assert_eq!(stats.low_confidence_share, 0.4);
+        assert_eq!(stats.median_change, Some(31.0));
+
+        let counts: Vec<(&str, usize)> = stats
+            .change_distribution
+            .iter()
+            .map(|bucket| (bucket.range.as_str(), bucket.count))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![
+                ("< -50%", 0),
+                ("-50% to -20%", 1),
+                ("-20% to -5%", 0),
+                ("-5% to +5%", 1),
+                ("+5% to +20%", 0),
+                ("+20% to +50%", 0),
+                (">= +50%", 2),
+                ("no data", 1),
+            ]
+        );
+
+        assert_eq!(stats.gainers.len(), 1);
+        assert_eq!(stats.gainers[0].name, "A");
+        assert_eq!(stats.losers[0].name, "B");
+    }
+
+    #[test]
+    fn test_low_confidence_from_sparklines() {
+        let item: ItemLine = serde_json::from_str(
+            r#"{"id": 1, "name": "Tabula Rasa", "chaosValue": 12.0, "detailsId": "tabula-rasa",
+                "sparkline": {"data": [], "totalChange": 0.0},
+                "lowConfidenceSparkline": {"data": [0.0, 5.0], "totalChange": 5.0}}"#,
+        )
+        .unwrap();
+        let lines = lines_from_items(&[item]);
+        assert!(lines[0].low_confidence);
+        assert_eq!(lines[0].total_change, Some(5.0));
+    }
+}
@@ -0,0 +1,215 @@
+use serde::Serialize;
+
+use crate::stats::has_data;
+use crate::types::*;
+
+/// Listings an entry needs for high confidence
+pub const HIGH_CONFIDENCE_LISTINGS: i32 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Confidence {
+    /// poe.ninja left the regular sparkline empty for too few listings
+    #[default]
+    Low,
+    /// A regular sparkline on fewer than 20 listings
+    Medium,
+    /// A regular sparkline on 20 listings or more
+    High,
+}
+
+impl Confidence {
+    pub fn of(regular_sparkline: &SparkLine, listings: i32) -> Self {
+        if !has_data(regular_sparkline) {
+            Confidence::Low
+        } else if listings < HIGH_CONFIDENCE_LISTINGS {
+            Confidence::Medium
+        } else {
+            Confidence::High
+        }
+    }
+}
+
+/// One priced entry of any category
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Ranked {
+    pub category: String,
+    pub details_id: String,
+    pub name: String,
+    pub chaos_value: f64,
+    /// Only item overviews carry their own divine value
+    pub divine_value: Option<f64>,
+    pub listings: i32,
+    pub confidence: Confidence,
+    pub corrupted: bool,
+}
+
+pub fn entries_from_items(category: &str, items: &[ItemLine]) -> Vec<Ranked> {
+    items
+        .iter()
+        .map(|item| {
+            let listings = item.listing_count.unwrap_or(item.count);
+            Ranked {
+                category: category.to_string(),
+                details_id: item.details_id.clone(),
+                name: match &item.variant {
+                    Some(variant) => format!("{} ({})", item.name, variant),
+                    None => item.name.clone(),
+                },
+                chaos_value: item.chaos_value,
+                divine_value: item.divine_value,
+                listings,
+                confidence: Confidence::of(&item.sparkline, listings),
+                corrupted: item.corrupted.unwrap_or(false),
+            }
+        })
+        .collect()
+}
+
+/// Currencies use the receive side, the direction of `chaosEquivalent`
+pub fn entries_from_currencies(category: &str, currencies: &[CurrencyLine]) -> Vec<Ranked> {
+    currencies
+        .iter()
+        .map(|currency| {
+            let listings = currency
+                .receive
+                .as_ref()
+                .map(|receive| receive.listing_count.unwrap_or(receive.count))
+                .unwrap_or(0);
+            Ranked {
+                category: category.to_string(),
+                details_id: currency.details_id.clone(),
+                name: currency.currency_type_name.clone(),
+                chaos_value: currency.chaos_equivalent.unwrap_or(0.0),
+                divine_value: None,
+                listings,
+                confidence: Confidence::of(&currency.receive_spark_line, listings),
+                corrupted: false,
+            }
+        })
+        .collect()
+}
+
+/// What to leave out of the ranking
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Exclusions {
+    pub min_confidence: Confidence,
+    /// Case-insensitive parts of names
+    pub names: Vec<String>,
+    pub categories: Vec<String>,
+    pub corrupted: bool,
+    /// Leave out entries above this, e.g. mirror-tier items nobody picks up
+    pub max_chaos: Option<f64>,
+}
+
+impl Exclusions {
+    pub fn allows(&self, entry: &Ranked) -> bool {
+        let name = entry.name.to_lowercase();
+        entry.confidence >= self.min_confidence
+            && !self.names.iter().any(|excluded| name.contains(&excluded.to_lowercase()))
+            && !self.categories.iter().any(|excluded| excluded.eq_ignore_ascii_case(&entry.category))
+            && (!self.corrupted || !entry.corrupted)
+            && self.max_chaos.is_none_or(|max| entry.chaos_value <= max)
+    }
+}
+
+/// The `n` most valuable allowed entries, or the `n` most valuable of each category
+/// in the order categories first appear
+pub fn top(entries: Vec<Ranked>, exclusions: &Exclusions, n: usize, per_category: bool) -> Vec<Ranked> {
+    let mut allowed: Vec<Ranked> = entries.into_iter().filter(|entry| exclusions.allows(entry)).collect();
+    if !per_category {
+        allowed.sort_by(|a, b| b.chaos_value.total_cmp(&a.chaos_value));
+        allowed.truncate(n);
+        return allowed;
+    }
+
+    let mut categories: Vec<String> = Vec::new();
+    for entry in &allowed {
+        if !categories.contains(&entry.category) {
+            categories.push(entry.category.clone());
+        }
+    }
+    categories
+        .iter()
+        .flat_map(|category| {
+            let mut group: Vec<Ranked> = allowed.iter().filter(|entry| &entry.category == category).cloned().collect();
+            group.sort_by(|a, b| b.chaos_value.total_cmp(&a.chaos_value));
+            group.truncate(n);
+            group
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(category: &str, name: &str, chaos_value: f64, confidence: Confidence, corrupted: bool) -> Ranked {
+        Ranked {
+            category: category.to_string(),
+            details_id: name.to_lowercase(),
+            name: name.to_string(),
+            chaos_value,
+            divine_value: None,
+            listings: 0,
+            confidence,
+            corrupted,
+        }
+    }
+
+    fn entries() -> Vec<Ranked> {
+        vec![
+            entry("UniqueArmour", "Mageblood", 30000.0, Confidence::High, false),
+            entry("UniqueArmour", "Headhunter", 9000.0, Confidence::High, true),
+            entry("UniqueArmour", "Tabula Rasa", 10.0, Confidence::High, false),
+            entry("Currency", "Mirror of Kalandra", 90000.0, Confidence::Medium, false),
+            entry("Currency", "Divine Orb", 200.0, Confidence::High, false),
+            entry("Currency", "Exalted Orb", 15.0, Confidence::Low, false),
+        ]
+    }
+
+    fn names(entries: &[Ranked]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_top_league_wide_and_per_category() {
+        let exclusions = Exclusions::default();
+        assert_eq!(names(&top(entries(), &exclusions, 3, false)), vec!["Mirror of Kalandra", "Mageblood", "Headhunter"]);
+        assert_eq!(
+            names(&top(entries(), &exclusions, 2, true)),
+            vec!["Mageblood", "Headhunter", "Mirror of Kalandra", "Divine Orb"]
+        );
+    }
+
+    #[test]
+    fn test_exclusions() {
+        let exclusions = Exclusions {
+            min_confidence: Confidence::High,
+            names: vec!["mirror".to_string()],
+            corrupted: true,
+            max_chaos: Some(20000.0),
+            ..Exclusions::default()
+        };
+        assert_eq!(names(&top(entries(), &exclusions, 10, false)), vec!["Divine Orb", "Tabula Rasa"]);
+
+        let exclusions = Exclusions {
+            min_confidence: Confidence::Medium,
+            categories: vec!["uniquearmour".to_string()],
+            ..Exclusions::default()
+        };
+        assert_eq!(names(&top(entries(), &exclusions, 10, false)), vec!["Mirror of Kalandra", "Divine Orb"]);
+    }
+
+    #[test]
+    fn test_confidence_from_sparkline_and_listings() {
+        let regular = SparkLine {
+            data: Some(vec![Some(0.0), Some(1.0)]),
+            total_change: Some(1.0),
+        };
+        assert_eq!(Confidence::of(&SparkLine::default(), 500), Confidence::Low);
+        assert_eq!(Confidence::of(&regular, 19), Confidence::Medium);
+        assert_eq!(Confidence::of(&regular, 20), Confidence::High);
+    }
+}
@@ -0,0 +1,267 @@
+use serde_json::{json, Map, Value};
+
+use crate::api::urlencoding;
+use crate::types::*;
+
+pub const TRADE_BASE_URL: &str = "https://www.pathofexile.com/trade";
+
+/// A generated trade site query together with the URL that opens it
+#[derive(Debug, Clone)]
+pub struct TradeLink {
+    pub query: Value,
+    pub url: String,
+}
+
+/// Build an official trade API search query for an item line
+pub fn build_item_search(item: &ItemLine) -> Value {
+    let mut query = Map::new();
+    query.insert("status".to_string(), json!({ "option": "online" }));
+
+    // Uniques are searched by name + base; everything else is searched by type
+    let is_unique = matches!(&item.base_type, Some(base) if !base.is_empty() && base != &item.name);
+    if is_unique {
+        query.insert("name".to_string(), json!(item.name));
+        query.insert("type".to_string(), json!(item.base_type));
+    } else {
+        query.insert("type".to_string(), json!(item.name));
+    }
+
+    let mut filters = Map::new();
+
+    if is_unique {
+        filters.insert(
+            "type_filters".to_string(),
+            json!({ "filters": { "rarity": { "option": "unique" } } }),
+        );
+    }
+
+    if let Some(links) = item.links {
+        filters.insert(
+            "socket_filters".to_string(),
+            json!({ "filters": { "links": { "min": links } } }),
+        );
+    }
+
+    let mut misc = Map::new();
+    if let Some(level) = item.gem_level {
+        misc.insert("gem_level".to_string(), json!({ "min": level }));
+    }
+    if let Some(quality) = item.gem_quality {
+        misc.insert("quality".to_string(), json!({ "min": quality }));
+    }
+    if let Some(corrupted) = item.corrupted {
+        misc.insert(
+            "corrupted".to_string(),
+            json!({ "option": if corrupted { "true" } else { "false" } }),
+        );
+    }
+    if !misc.is_empty() {
+        filters.insert("misc_filters".to_string(), json!({ "filters": misc }));
+    }
+
+    if !filters.is_empty() {
+        query.insert("filters".to_string(), Value::Object(filters));
+    }
+
+    let stat_filters: Vec<Value> = item
+        .trade_info
+        .iter()
+        .flatten()
+        .map(|info| {
+            json!({
+                "id": trade_stat_id(&info.mod_name),
+                "value": { "min": info.min, "max": info.max },
+            })
+        })
+        .collect();
+    if !stat_filters.is_empty() {
+        query.insert(
+            "stats".to_string(),
+            json!([{ "type": "and", "filters": stat_filters }]),
+        );
+    }
+
+    json!({
+        "query": query,
+        "sort": { "price": "asc" },
+    })
+}
+
+/// Build a bulk exchange query trading `have` for `want` (both trade ids)
+pub fn build_bulk_exchange(want: &str, have: &str) -> Value {
+    json!({
+        "query": {
+            "status": { "option": "online" },
+            "have": [have],
+            "want": [want],
+        },
+        "sort": { "have": "asc" },
+        "engine": "new",
+    })
+}
+
+/// URL that opens a search query on the trade site
+pub fn search_url(league: &str, query: &Value) -> String {
+    format!(
+        "{}/search/{}?q={}",
+        TRADE_BASE_URL,
+        urlencoding::encode(league),
+        urlencoding::encode(&query.to_string())
+    )
+}
+
+/// URL that opens a bulk exchange query on the trade site
+pub fn exchange_url(league: &str, query: &Value) -> String {
+    format!(
+        "{}/exchange/{}?q={}",
+        TRADE_BASE_URL,
+        urlencoding::encode(league),
+        urlencoding::encode(&query.to_string())
+    )
+}
+
+pub fn item_trade_link(league: &str, item: &ItemLine) -> TradeLink {
+    let query = build_item_search(item);
+    let url = search_url(league, &query);
+    TradeLink { query, url }
+}
+
+/// Bulk exchange link for a currency, or `None` when poe.ninja has no trade id for it
+pub fn currency_trade_link(league: &str, detail: &CurrencyDetail, have: &str) -> Option<TradeLink> {
+    let want = detail.trade_id.as_deref()?;
+    let query = build_bulk_exchange(want, have);
+    let url = exchange_url(league, &query);
+    Some(TradeLink { query, url })
+}
+
+/// Find the currency details entry that belongs to a currency line
+pub fn find_currency_detail<'a>(
+    details: &'a [CurrencyDetail],
+    currency_name: &str,
+) -> Option<&'a CurrencyDetail> {
+    details
+        .iter()
+        .find(|detail| detail.name.eq_ignore_ascii_case(currency_name))
+}
+
+// poe.ninja reports bare stat ids for explicit mods
+fn trade_stat_id(mod_name: &str) -> String {
+    if mod_name.contains('.') {
+        mod_name.to_string()
+    } else {
+        format!("explicit.{}", mod_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_item() -> ItemLine {
+        ItemLine {
+            id: 1,
+            name: "Tabula Rasa".to_string(),
+            base_type: Some("Simple Robe".to_string()),
+            corrupted: Some(false),
+            chaos_value: 10.0,
+            count: 1,
+            details_id: "tabula-rasa-simple-robe".to_string(),
+            links: Some(6),
+            trade_info: Some(vec![TradeInfo {
+                mod_name: "stat_3299347043".to_string(),
+                min: 80,
+                max: 100,
+            }]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_unique_item_search_query() {
+        let query = build_item_search(&sample_item());
+
+        assert_eq!(query["query"]["name"], "Tabula Rasa");
+        assert_eq!(query["query"]["type"], "Simple Robe");
+        assert_eq!(query["query"]["filters"]["type_filters"]["filters"]["rarity"]["option"], "unique");
+        assert_eq!(query["query"]["filters"]["socket_filters"]["filters"]["links"]["min"], 6);
+        assert_eq!(query["query"]["filters"]["misc_filters"]["filters"]["corrupted"]["option"], "false");
+
+        let stat = &query["query"]["stats"][0]["filters"][0];
+        assert_eq!(stat["id"], "explicit.stat_3299347043");
+        assert_eq!(stat["value"]["min"], 80);
+        assert_eq!(stat["value"]["max"], 100);
+    }
+
+    #[test]
+    fn test_gem_search_query() {
+        let mut gem = sample_item();
+        gem.name = "Enlighten Support".to_string();
+        gem.base_type = None;
+        gem.links = None;
+        gem.trade_info = None;
+        gem.corrupted = Some(true);
+        gem.gem_level = Some(4);
+        gem.gem_quality = Some(20);
+
+        let query = build_item_search(&gem);
+
+        assert_eq!(query["query"]["type"], "Enlighten Support");
+        assert!(query["query"].get("name").is_none());
+        assert!(query["query"].get("stats").is_none());
+        let misc = &query["query"]["filters"]["misc_filters"]["filters"];
+        assert_eq!(misc["gem_level"]["min"], 4);
+        assert_eq!(misc["quality"]["min"], 20);
+        assert_eq!(misc["corrupted"]["option"], "true");
+    }
+
+    #[test]
+    fn test_bulk_exchange_query_and_url() {
+        let detail = CurrencyDetail {
+            id: 3,
+            icon: None,
+            name: "Divine Orb".to_string(),
+            trade_id: Some("divine".to_string()),
+        };
+
+        let link = currency_trade_link("Hardcore Settlers", &detail, "chaos").unwrap();
+
+        assert_eq!(link.query["query"]["want"][0], "divine");
+        assert_eq!(link.query["query"]["have"][0], "chaos");
+        assert!(link.url.starts_with("https://www.pathofexile.com/trade/exchange/Hardcore%20Settlers?q="));
+        assert!(link.url.contains("%22divine%22"));
+    }
+
+    #[test]
+    fn test_currency_without_trade_id_has_no_link() {
+        let detail = CurrencyDetail {
+            id: 4,
+            icon: None,
+            name: "Unknown Shard".to_string(),
+            trade_id: None,
+        };
+
+        assert!(currency_trade_link("Standard", &detail, "chaos").is_none());
+    }
+
+    #[test]
+    fn test_search_url_encodes_query() {
+        let link = item_trade_link("Standard", &sample_item());
+
+        assert!(link.url.starts_with("https://www.pathofexile.com/trade/search/Standard?q=%7B"));
+        assert!(!link.url.contains(' '));
+        assert!(!link.url.contains('"'));
+    }
+
+    #[test]
+    fn test_find_currency_detail_is_case_insensitive() {
+        let details = vec![CurrencyDetail {
+            id: 1,
+            icon: None,
+            name: "Exalted Orb".to_string(),
+            trade_id: Some("exalted".to_string()),
+        }];
+
+        assert!(find_currency_detail(&details, "exalted orb").is_some());
+        assert!(find_currency_detail(&details, "Chaos Orb").is_none());
+    }
+}
@@ -1,43 +1,60 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, HashMap};
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Fields poe.ninja added that the model does not know about
+pub type Extras = BTreeMap<String, serde_json::Value>;
+
+/// Deserialize `null` as the type's default, for fields that are not `Option`
+/// but have been seen as `null` in live payloads
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct CurrencyOverviewResponse {
     pub lines: Vec<CurrencyLine>,
     #[serde(rename = "currencyDetails")]
     pub currency_details: Vec<CurrencyDetail>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct CurrencyLine {
     #[serde(rename = "currencyTypeName")]
     pub currency_type_name: String,
     pub pay: Option<CurrencyData>,
     pub receive: Option<CurrencyData>,
-    #[serde(rename = "paySparkLine")]
+    #[serde(rename = "paySparkLine", default, deserialize_with = "null_as_default")]
     pub pay_spark_line: SparkLine,
-    #[serde(rename = "receiveSparkLine")]
+    #[serde(rename = "receiveSparkLine", default, deserialize_with = "null_as_default")]
     pub receive_spark_line: SparkLine,
     #[serde(rename = "chaosEquivalent")]
     pub chaos_equivalent: Option<f64>,
-    #[serde(rename = "lowConfidencePaySparkLine")]
+    #[serde(rename = "lowConfidencePaySparkLine", default, deserialize_with = "null_as_default")]
     pub low_confidence_pay_spark_line: SparkLine,
-    #[serde(rename = "lowConfidenceReceiveSparkLine")]
+    #[serde(rename = "lowConfidenceReceiveSparkLine", default, deserialize_with = "null_as_default")]
     pub low_confidence_receive_spark_line: SparkLine,
     #[serde(rename = "detailsId")]
     pub details_id: String,
+    #[serde(flatten)]
+    pub extras: Extras,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct CurrencyData {
     pub id: i32,
-    #[serde(rename = "league_id")]
+    #[serde(rename = "league_id", default, deserialize_with = "null_as_default")]
     pub league_id: i32,
     #[serde(rename = "pay_currency_id")]
     pub pay_currency_id: i32,
     #[serde(rename = "get_currency_id")]
     pub get_currency_id: i32,
-    #[serde(rename = "sample_time_utc")]
+    #[serde(rename = "sample_time_utc", default, deserialize_with = "null_as_default")]
     pub sample_time_utc: String,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub count: i32,
     pub value: f64,
     #[serde(rename = "data_point_count")]
@@ -48,7 +65,7 @@ pub struct CurrencyData {
     pub listing_count: Option<i32>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct CurrencyDetail {
     pub id: i32,
     pub icon: Option<String>,
@@ -57,15 +74,16 @@ pub struct CurrencyDetail {
     pub trade_id: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ItemOverviewResponse {
     pub lines: Vec<ItemLine>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct ItemLine {
     pub id: i32,
     pub name: String,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub icon: String,
     #[serde(rename = "mapTier")]
     pub map_tier: Option<i32>,
@@ -78,14 +96,15 @@ pub struct ItemLine {
     pub variant: Option<String>,
     #[serde(rename = "itemClass")]
     pub item_class: Option<i32>,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub sparkline: SparkLine,
-    #[serde(rename = "lowConfidenceSparkline")]
+    #[serde(rename = "lowConfidenceSparkline", default, deserialize_with = "null_as_default")]
     pub low_confidence_sparkline: SparkLine,
-    #[serde(rename = "implicitModifiers")]
+    #[serde(rename = "implicitModifiers", default, deserialize_with = "null_as_default")]
     pub implicit_modifiers: Vec<Modifier>,
-    #[serde(rename = "explicitModifiers")]
+    #[serde(rename = "explicitModifiers", default, deserialize_with = "null_as_default")]
     pub explicit_modifiers: Vec<Modifier>,
-    #[serde(rename = "flavourText")]
+    #[serde(rename = "flavourText", default, deserialize_with = "null_as_default")]
     pub flavour_text: String,
     pub corrupted: Option<bool>,
     #[serde(rename = "gemLevel")]
@@ -100,6 +119,7 @@ pub struct ItemLine {
     pub exalted_value: Option<f64>,
     #[serde(rename = "divineValue")]
     pub divine_value: Option<f64>,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub count: i32,
     #[serde(rename = "detailsId")]
     pub details_id: String,
@@ -108,22 +128,26 @@ pub struct ItemLine {
     pub links: Option<i32>,
     #[serde(rename = "tradeInfo")]
     pub trade_info: Option<Vec<TradeInfo>>,
+    #[serde(flatten)]
+    pub extras: Extras,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct SparkLine {
     pub data: Option<Vec<Option<f64>>>,
     #[serde(rename = "totalChange")]
     pub total_change: Option<f64>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct Modifier {
+    #[serde(default, deserialize_with = "null_as_default")]
     pub text: String,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub optional: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct TradeInfo {
     #[serde(rename = "mod")]
     pub mod_name: String,
@@ -131,6 +155,153 @@ pub struct TradeInfo {
     pub max: i32,
 }
 
+/// One day of a price history graph
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct HistoryPoint {
+    pub count: i32,
+    pub value: f64,
+    #[serde(rename = "daysAgo")]
+    pub days_ago: i32,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CurrencyHistoryResponse {
+    #[serde(rename = "payCurrencyGraphData", default)]
+    pub pay_currency_graph_data: Vec<HistoryPoint>,
+    /// Chaos received per unit, the same direction as `chaosEquivalent`
+    #[serde(rename = "receiveCurrencyGraphData", default)]
+    pub receive_currency_graph_data: Vec<HistoryPoint>,
+}
+
+/// Character aggregates from the builds overview. The `*_use` maps are keyed
+/// by the index into their name list and hold indices into `classes`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BuildOverviewResponse {
+    #[serde(rename = "classNames")]
+    pub class_names: Vec<String>,
+    /// Index into `class_names` for every sampled character
+    pub classes: Vec<usize>,
+    #[serde(rename = "uniqueItems", default)]
+    pub unique_items: Vec<BuildItem>,
+    #[serde(rename = "uniqueItemUse", default)]
+    pub unique_item_use: HashMap<String, Vec<usize>>,
+    #[serde(rename = "activeSkills", default)]
+    pub active_skills: Vec<BuildSkill>,
+    #[serde(rename = "activeSkillUse", default)]
+    pub active_skill_use: HashMap<String, Vec<usize>>,
+    #[serde(default)]
+    pub keystones: Vec<BuildKeystone>,
+    #[serde(rename = "keystoneUse", default)]
+    pub keystone_use: HashMap<String, Vec<usize>>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct BuildItem {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub item_type: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct BuildSkill {
+    pub name: String,
+    pub icon: Option<String>,
+    #[serde(rename = "dpsName")]
+    pub dps_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct BuildKeystone {
+    pub name: String,
+    pub icon: Option<String>,
+    #[serde(rename = "isKeystone", default)]
+    pub is_keystone: bool,
+}
+
+/// League list from the poe.ninja index state endpoint (both games)
+#[derive(Debug, Deserialize, Serialize)]
+pub struct IndexStateResponse {
+    #[serde(rename = "economyLeagues")]
+    pub economy_leagues: Vec<EconomyLeague>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct EconomyLeague {
+    pub name: String,
+    pub url: String,
+    #[serde(rename = "displayName")]
+    pub display_name: String,
+    #[serde(default)]
+    pub hardcore: bool,
+    #[serde(default)]
+    pub indexed: bool,
+}
+
+/// PoE2 exchange overview. Values are quoted in `core.primary` and
+/// `core.rates` converts one primary unit into other currencies.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Poe2ExchangeOverviewResponse {
+    pub core: Poe2ExchangeCore,
+    pub lines: Vec<Poe2ExchangeLine>,
+    pub items: Vec<Poe2ItemInfo>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Poe2ExchangeCore {
+    pub items: Vec<Poe2ItemInfo>,
+    pub rates: HashMap<String, f64>,
+    pub primary: String,
+    pub secondary: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Poe2ExchangeLine {
+    pub id: String,
+    #[serde(rename = "primaryValue")]
+    pub primary_value: f64,
+    #[serde(rename = "volumePrimaryValue")]
+    pub volume_primary_value: Option<f64>,
+    #[serde(rename = "maxVolumeCurrency")]
+    pub max_volume_currency: Option<String>,
+    #[serde(rename = "maxVolumeRate")]
+    pub max_volume_rate: Option<f64>,
+    pub sparkline: Option<SparkLine>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Poe2ItemInfo {
+    pub id: String,
+    pub name: String,
+    pub image: Option<String>,
+    pub category: Option<String>,
+    #[serde(rename = "detailsId")]
+    pub details_id: String,
+}
+
+impl Poe2ExchangeOverviewResponse {
+    /// Display info for a line's `id`
+    pub fn item_info(&self, id: &str) -> Option<&Poe2ItemInfo> {
+        self.items
+            .iter()
+            .chain(self.core.items.iter())
+            .find(|item| item.id == id)
+    }
+
+    /// Value of one primary unit in the given currency id (1.0 for the primary itself)
+    pub fn rate(&self, currency_id: &str) -> Option<f64> {
+        if currency_id == self.core.primary {
+            Some(1.0)
+        } else {
+            self.core.rates.get(currency_id).copied()
+        }
+    }
+
+    /// A line's value expressed in the given currency id
+    pub fn value_in(&self, line: &Poe2ExchangeLine, currency_id: &str) -> Option<f64> {
+        self.rate(currency_id).map(|rate| line.primary_value * rate)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -200,6 +371,7 @@ mod tests {
             listing_count: Some(5),
             links: Some(6),
             trade_info: None,
+            extras: Default::default(),
         };
 
         let price_info = PriceInfo::from_item(&item);
@@ -247,6 +419,7 @@ mod tests {
                 total_change: None,
             },
             details_id: "divine-orb".to_string(),
+            extras: Default::default(),
         };
 
         let price_info = PriceInfo::from_currency(&currency);
@@ -284,6 +457,7 @@ mod tests {
                 total_change: None,
             },
             details_id: "chaos-orb".to_string(),
+            extras: Default::default(),
         };
 
         let json = serde_json::to_string(&currency).expect("Failed to serialize");
@@ -441,4 +615,61 @@ mod tests {
         
         println!("✓ Optional fields handling works correctly");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_poe2_exchange_overview_fixture() {
+        let json = include_str!("../tests/fixtures/poe2/exchange_overview_currency.json");
+        let response: Poe2ExchangeOverviewResponse = serde_json::from_str(json)
+            .expect("Failed to deserialize PoE2 exchange overview");
+
+        assert_eq!(response.core.primary, "divine");
+        assert_eq!(response.lines.len(), 4);
+        assert_eq!(response.rate("divine"), Some(1.0));
+        assert_eq!(response.rate("exalted"), Some(412.5));
+        assert_eq!(response.rate("unknown"), None);
+        let mirror = &response.lines[2];
+        assert_eq!(response.value_in(mirror, "exalted"), Some(1450.0 * 412.5));
+
+        let chaos = &response.lines[1];
+        assert_eq!(response.item_info(&chaos.id).unwrap().name, "Chaos Orb");
+        let sparkline = chaos.sparkline.as_ref().unwrap();
+        assert_eq!(sparkline.total_change, Some(-1.4));
+        assert_eq!(sparkline.data.as_ref().unwrap()[4], None);
+
+        println!("✓ PoE2 exchange overview fixture parses");
+    }
+
+    #[test]
+    fn test_index_state_fixtures() {
+        let poe1: IndexStateResponse =
+            serde_json::from_str(include_str!("../tests/fixtures/poe1/index_state.json"))
+                .expect("Failed to deserialize PoE1 index state");
+        let poe2: IndexStateResponse =
+            serde_json::from_str(include_str!("../tests/fixtures/poe2/index_state.json"))
+                .expect("Failed to deserialize PoE2 index state");
+
+        assert_eq!(poe1.economy_leagues[0].name, "Settlers");
+        assert!(poe1.economy_leagues[1].hardcore);
+        assert_eq!(poe2.economy_leagues.len(), 3);
+        assert_eq!(poe2.economy_leagues[1].display_name, "Hardcore Rise of the Abyssal");
+
+        println!("✓ Index state fixtures parse for both games");
+    }
+
+    #[test]
+    fn test_build_overview_fixture() {
+        let json = include_str!("../tests/fixtures/builds/build_overview.json");
+        let response: BuildOverviewResponse = serde_json::from_str(json)
+            .expect("Failed to deserialize build overview");
+
+        assert_eq!(response.classes.len(), 10);
+        assert_eq!(response.class_names[response.classes[3]], "Deadeye");
+        assert_eq!(response.unique_items[1].item_type, "Leather Belt");
+        assert_eq!(response.unique_item_use["2"].len(), 5);
+        assert_eq!(response.active_skills[2].dps_name, None);
+        assert!(response.keystones[1].is_keystone);
+        assert_eq!(response.keystones[1].icon, None);
+
+        println!("✓ Build overview fixture parses");
+    }
+}
@@ -0,0 +1,365 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::api::Game;
+use crate::pricing::{chaos_to_divine, PriceBook, PriceMatch, PriceQuery};
+use crate::source::PriceSource;
+
+/// One watched entry, saved in the config file:
+///
+/// ```toml
+/// [[watchlists.morning]]
+/// category = "UniqueArmour"
+/// name = "Tabula Rasa"
+/// links = 6
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WatchItem {
+    pub category: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub links: Option<i32>,
+}
+
+impl WatchItem {
+    pub fn query(&self) -> PriceQuery<'_> {
+        PriceQuery {
+            name: &self.name,
+            category: Some(&self.category),
+            variant: self.variant.as_deref(),
+            links: self.links,
+            ..Default::default()
+        }
+    }
+
+    /// Stable key for the last-view state, e.g. `UniqueArmour/tabula rasa//6`
+    pub fn key(&self) -> String {
+        format!(
+            "{}/{}/{}/{}",
+            self.category,
+            self.name.to_lowercase(),
+            self.variant.as_deref().unwrap_or_default().to_lowercase(),
+            self.links.map(|links| links.to_string()).unwrap_or_default()
+        )
+    }
+}
+
+/// Chaos values seen the last time a watchlist was shown
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct LastView {
+    /// Unix seconds
+    pub viewed_at: u64,
+    pub values: BTreeMap<String, f64>,
+}
+
+impl LastView {
+    /// A missing file means the list was never shown
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse watchlist state '{}'", path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err).with_context(|| format!("Failed to read watchlist state '{}'", path.display())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).with_context(|| format!("Failed to create '{}'", parent.display()))?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write watchlist state '{}'", path.display()))
+    }
+
+    /// Record the values of `rows` as seen now
+    pub fn from_rows(rows: &[WatchRow]) -> Self {
+        Self {
+            viewed_at: now(),
+            values: rows
+                .iter()
+                .filter_map(|row| Some((row.item.key(), row.price.as_ref()?.chaos_value)))
+                .collect(),
+        }
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// Where the last view of a list is kept: `watchlists/<name>.<game>.<league>.json` next to
+/// the config file, so viewing the same list in another league does not overwrite it
+pub fn state_path(config_path: &Path, list: &str, game: Game, league: &str) -> PathBuf {
+    let slug = crate::pricing::slugify;
+    config_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join("watchlists")
+        .join(format!("{}.{}.{}.json", slug(list), game.slug(), slug(league)))
+}
+
+/// A watched entry with its current price and the value at the last view
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchRow {
+    pub item: WatchItem,
+    pub price: Option<PriceMatch>,
+    pub previous: Option<f64>,
+}
+
+impl WatchRow {
+    /// Percent change since the last view
+    pub fn change_percent(&self) -> Option<f64> {
+        let current = self.price.as_ref()?.chaos_value;
+        let previous = self.previous.filter(|previous| *previous > 0.0)?;
+        Some((current - previous) / previous * 100.0)
+    }
+}
+
+/// Fetch each category the list needs exactly once, plus Currency for the divine rate
+pub async fn load_price_book(source: &dyn PriceSource, league: &str, items: &[WatchItem]) -> Result<PriceBook> {
+    let categories: BTreeSet<&str> = items
+        .iter()
+        .map(|item| item.category.as_str())
+        .chain(["Currency"])
+        .collect();
+
+    let mut book = PriceBook::new();
+    for category in categories {
+        book.ensure_category(source, league, category).await?;
+    }
+    Ok(book)
+}
+
+/// Price every entry, keeping the order of the list
+pub fn price_watchlist(book: &PriceBook, items: &[WatchItem], last: &LastView) -> Vec<WatchRow> {
+    items
+        .iter()
+        .map(|item| WatchRow {
+            item: item.clone(),
+            price: book.lookup(&item.query()),
+            previous: last.values.get(&item.key()).copied(),
+        })
+        .collect()
+}
+
+/// One line of a batch file:
+///
+/// ```text
+/// {"category": "UniqueWeapon", "name": "Starforge"}
+/// {"category": "Currency", "name": "Divine Orb", "league": "Hardcore"}
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct BatchQuery {
+    pub category: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub league: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub links: Option<i32>,
+}
+
+impl BatchQuery {
+    pub fn watch_item(&self) -> WatchItem {
+        WatchItem {
+            category: self.category.clone(),
+            name: self.name.clone(),
+            variant: self.variant.clone(),
+            links: self.links,
+        }
+    }
+}
+
+/// Read batch queries, skipping blank lines and `#` comments.
+/// Lines that fail to parse are returned as errors so they can be reported in place.
+pub fn read_batch(reader: impl BufRead) -> Result<Vec<(usize, Result<BatchQuery>)>> {
+    let mut queries = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.context("Failed to read batch input")?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let query = serde_json::from_str(line).with_context(|| format!("Invalid query on line {}", index + 1));
+        queries.push((index + 1, query));
+    }
+    Ok(queries)
+}
+
+/// One NDJSON output line: the query plus either its price or an error
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BatchResult {
+    pub line: usize,
+    pub league: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<BatchQuery>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chaos_value: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub divine_value: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BatchResult {
+    pub fn failed(line: usize, league: &str, query: Option<BatchQuery>, error: &anyhow::Error) -> Self {
+        Self {
+            line,
+            league: league.to_string(),
+            query,
+            name: None,
+            details_id: None,
+            chaos_value: None,
+            divine_value: None,
+            error: Some(format!("{:#}", error)),
+        }
+    }
+}
+
+/// Price batch queries, loading each league's categories once
+pub async fn run_batch(
+    source: &dyn PriceSource,
+    default_league: &str,
+    queries: Vec<(usize, Result<BatchQuery>)>,
+) -> Vec<BatchResult> {
+    let mut books: BTreeMap<String, PriceBook> = BTreeMap::new();
+    let mut results = Vec::new();
+
+    for (line, query) in queries {
+        let query = match query {
+            Ok(query) => query,
+            Err(err) => {
+                results.push(BatchResult::failed(line, default_league, None, &err));
+                continue;
+            }
+        };
+
+        let league = query.league.clone().unwrap_or_else(|| default_league.to_string());
+        let book = books.entry(league.clone()).or_default();
+        let loaded = async {
+            book.ensure_category(source, &league, "Currency").await?;
+            book.ensure_category(source, &league, &query.category).await
+        }
+        .await;
+        if let Err(err) = loaded {
+            results.push(BatchResult::failed(line, &league, Some(query), &err));
+            continue;
+        }
+
+        let item = query.watch_item();
+        match book.lookup(&item.query()) {
+            Some(price) => results.push(BatchResult {
+                line,
+                league,
+                divine_value: book.divine_rate().map(|rate| chaos_to_divine(price.chaos_value, rate)),
+                chaos_value: Some(price.chaos_value),
+                name: Some(price.name),
+                details_id: Some(price.details_id),
+                query: Some(query),
+                error: None,
+            }),
+            None => {
+                let err = anyhow::anyhow!("No price for '{}' in {}", query.name, query.category);
+                results.push(BatchResult::failed(line, &league, Some(query), &err));
+            }
+        }
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::MockSource;
+    use crate::types::*;
+
+    fn source() -> MockSource {
+        let currencies: CurrencyOverviewResponse = serde_json::from_str(include_str!(
+            "../tests/fixtures/replay/poe1/currencyoverview/standard/Currency.json"
+        ))
+        .unwrap();
+        let items: ItemOverviewResponse = serde_json::from_str(include_str!(
+            "../tests/fixtures/replay/poe1/itemoverview/standard/UniqueWeapon.json"
+        ))
+        .unwrap();
+        MockSource::new()
+            .with_currencies("Standard", "Currency", currencies)
+            .with_items("Standard", "UniqueWeapon", items.lines)
+    }
+
+    fn watch(category: &str, name: &str) -> WatchItem {
+        WatchItem {
+            category: category.to_string(),
+            name: name.to_string(),
+            variant: None,
+            links: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_price_watchlist_with_last_view() {
+        let items = vec![watch("UniqueWeapon", "Starforge"), watch("Currency", "Divine Orb"), watch("UniqueWeapon", "Mageblood")];
+        let book = load_price_book(&source(), "Standard", &items).await.unwrap();
+
+        let first = price_watchlist(&book, &items, &LastView::default());
+        assert_eq!(first[0].price.as_ref().unwrap().chaos_value, 310.0);
+        assert!(first[2].price.is_none());
+        assert!(first.iter().all(|row| row.change_percent().is_none()));
+
+        let mut last = LastView::from_rows(&first);
+        assert_eq!(last.values.len(), 2);
+        last.values.insert(items[0].key(), 248.0);
+
+        let second = price_watchlist(&book, &items, &last);
+        assert_eq!(second[0].change_percent(), Some(25.0));
+        assert_eq!(second[1].change_percent(), Some(0.0));
+    }
+
+    #[test]
+    fn test_state_path_per_game_and_league() {
+        let config = Path::new("/home/exile/.config/poe-ninja-cli/config.toml");
+        assert_eq!(
+            state_path(config, "Morning Run", Game::Poe1, "Hardcore Settlers"),
+            PathBuf::from("/home/exile/.config/poe-ninja-cli/watchlists/morning-run.poe1.hardcore-settlers.json")
+        );
+        assert_ne!(
+            state_path(config, "morning", Game::Poe1, "Standard"),
+            state_path(config, "morning", Game::Poe2, "Standard")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_batch() {
+        let input = "# morning\n{\"category\": \"UniqueWeapon\", \"name\": \"starforge\"}\n\nnot json\n{\"category\": \"Oil\", \"name\": \"Golden Oil\"}\n{\"category\": \"Currency\", \"name\": \"Mirror\"}\n";
+        let queries = read_batch(input.as_bytes()).unwrap();
+        assert_eq!(queries.len(), 4);
+
+        let results = run_batch(&source(), "Standard", queries).await;
+        assert_eq!(results[0].line, 2);
+        assert_eq!(results[0].name.as_deref(), Some("Starforge"));
+        assert_eq!(results[0].divine_value, Some(310.0 / 207.5));
+        assert!(results[1].error.as_deref().unwrap().contains("line 4"));
+        assert!(results[2].error.as_deref().unwrap().contains("No mock item data"));
+        assert!(results[3].error.as_deref().unwrap().contains("No price for 'Mirror'"));
+
+        let json = serde_json::to_string(&results[0]).unwrap();
+        assert!(!json.contains("error"));
+    }
+}